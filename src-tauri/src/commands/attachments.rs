@@ -0,0 +1,203 @@
+//! Encrypted attachments referenced from notes
+//!
+//! Each attachment is stored like a note, as `.attachments/<id>.enc` plus its
+//! wrapped DEK in `.key`, bound to its own path. Notes refer to it by the
+//! token `attachment:<id>`, e.g. `![photo](attachment:<id>)`.
+
+use crate::commands::notes::{
+    bound_note_id, checked_note_path, decrypt_note_bytes, enc_path, encrypt_note_files, key_path,
+    note_file_path, secure_delete, slugify, ATTACHMENTS_DIR,
+};
+use crate::commands::vault::VaultState;
+use crate::error::GhostError;
+use crate::AppState;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tauri::State;
+use walkdir::WalkDir;
+use zeroize::Zeroizing;
+
+/// What a note writes before an attachment id to reference it
+const TOKEN_PREFIX: &str = "attachment:";
+
+/// Longest slug of the original file name kept in an id
+const MAX_NAME_LEN: usize = 40;
+
+/// Whether `id` could have come from `save_attachment`
+///
+/// Keeps tokens from naming anything outside the attachments folder.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_alphanumeric() || c == '-')
+}
+
+/// The attachment id a token names
+fn token_id(token: &str) -> Result<&str, GhostError> {
+    token
+        .trim()
+        .strip_prefix(TOKEN_PREFIX)
+        .filter(|id| is_valid_id(id))
+        .ok_or_else(|| GhostError::Invalid(format!("'{}' is not an attachment token", token)))
+}
+
+/// Path of an attachment relative to the notes folder, which its
+/// ciphertext is bound to
+fn attachment_rel(id: &str) -> String {
+    format!("{}/{}", ATTACHMENTS_DIR, id)
+}
+
+/// Ids of every attachment referenced in `content`
+fn referenced_ids(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .match_indices(TOKEN_PREFIX)
+        .filter_map(|(start, _)| {
+            let rest = &content[start + TOKEN_PREFIX.len()..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '-'))
+                .unwrap_or(rest.len());
+            Some(&rest[..end]).filter(|id| !id.is_empty())
+        })
+}
+
+/// Encrypt a file under its own DEK and return the token that references it
+///
+/// The attachment belongs to no note in particular: any note holding the
+/// token can show it.
+#[tauri::command]
+pub fn save_attachment(
+    note_path: String,
+    filename: String,
+    bytes: Vec<u8>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let note_path = checked_note_path(&notes_dir, &note_path)?;
+    if !note_file_path(&notes_dir, &note_path).is_file() {
+        return Err(GhostError::NotFound(format!(
+            "Note '{}' not found",
+            note_path
+        )));
+    }
+
+    let attachments_dir = notes_dir.join(ATTACHMENTS_DIR);
+    fs::create_dir_all(&attachments_dir)?;
+
+    // A random id, with the file name kept readable after it
+    let name: String = slugify(&filename).chars().take(MAX_NAME_LEN).collect();
+    let id = loop {
+        let random = format!("{:016x}", rand::random::<u64>());
+        let id = match name.trim_end_matches('-') {
+            "" => random,
+            name => format!("{}-{}", random, name),
+        };
+        if !enc_path(&attachments_dir.join(&id)).exists() {
+            break id;
+        }
+    };
+
+    encrypt_note_files(
+        &attachments_dir.join(&id),
+        &attachment_rel(&id),
+        &bytes,
+        &vault,
+        None,
+    )?;
+    Ok(format!("{}{}", TOKEN_PREFIX, id))
+}
+
+/// Ids of the attachments stored in the vault
+pub(crate) fn stored_attachment_ids(notes_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(notes_dir.join(ATTACHMENTS_DIR)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "enc"))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().to_string()))
+        .collect()
+}
+
+/// Decrypt the attachment `id`
+pub(crate) fn decrypt_attachment(
+    notes_dir: &Path,
+    id: &str,
+    vault: &VaultState,
+) -> Result<Zeroizing<Vec<u8>>, GhostError> {
+    let base_path = notes_dir.join(ATTACHMENTS_DIR).join(id);
+    if !enc_path(&base_path).is_file() {
+        return Err(GhostError::NotFound(format!(
+            "Attachment '{}' not found",
+            id
+        )));
+    }
+
+    let (_, bytes) = decrypt_note_bytes(&base_path, &attachment_rel(id), vault)?;
+    Ok(bytes)
+}
+
+/// Decrypt an attachment for display
+#[tauri::command]
+pub fn read_attachment(
+    token: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<u8>, GhostError> {
+    let id = token_id(&token)?;
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    Ok(decrypt_attachment(&notes_dir, id, &vault)?.to_vec())
+}
+
+/// Delete the attachments that no note references, returning how many went
+///
+/// Notes in the trash and earlier versions of notes count as references, so
+/// restoring either brings its attachments back with it. Nothing is deleted
+/// if any note can't be read, since its references can't be known. With
+/// `secure` the files are overwritten before they're unlinked.
+pub(crate) fn remove_unreferenced_attachments(
+    notes_dir: &Path,
+    vault: &VaultState,
+    secure: bool,
+) -> Result<usize, GhostError> {
+    let attachments_dir = notes_dir.join(ATTACHMENTS_DIR);
+    let stored = stored_attachment_ids(notes_dir);
+    if stored.is_empty() {
+        return Ok(0);
+    }
+
+    let mut referenced: HashSet<String> = HashSet::new();
+    for entry in WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path().starts_with(notes_dir.join(".vault")))
+        .filter(|e| !e.path().starts_with(&attachments_dir))
+    {
+        let path = entry.path();
+        let rel = path.strip_prefix(notes_dir).unwrap_or(path);
+        let bytes = match path.extension().and_then(|e| e.to_str()) {
+            Some("enc") => {
+                let aad_path = bound_note_id(&rel.with_extension(""));
+                decrypt_note_bytes(&path.with_extension(""), &aad_path, vault)?.1
+            }
+            Some("md" | "txt") => Zeroizing::new(fs::read(path)?),
+            _ => continue,
+        };
+        let content = String::from_utf8_lossy(&bytes);
+        referenced.extend(referenced_ids(&content).map(str::to_string));
+    }
+
+    let mut removed = 0;
+    for id in stored.iter().filter(|id| !referenced.contains(*id)) {
+        let base_path = attachments_dir.join(id);
+        if secure {
+            secure_delete(&enc_path(&base_path))?;
+            let _ = secure_delete(&key_path(&base_path));
+        } else {
+            fs::remove_file(enc_path(&base_path))?;
+            let _ = fs::remove_file(key_path(&base_path));
+        }
+        removed += 1;
+    }
+    Ok(removed)
+}
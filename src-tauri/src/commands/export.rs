@@ -0,0 +1,1102 @@
+use crate::commands::attachments::{decrypt_attachment, stored_attachment_ids};
+use crate::commands::notes::{
+    checked_note_path, extract_title, is_inside_notes_dir, load_notes_meta, note_created_date,
+    parse_frontmatter, read_note_content, rel_note_path, unique_legacy_path, unique_note_path,
+    walk_note_files, with_tag_map, write_encrypted_note, RESTORE_DIR, TRASH_DIR,
+};
+use crate::commands::search::index_note;
+use crate::commands::vault::{
+    decrypt, derive_verified_kek, encrypt, generate_salt, is_vault_initialized, lock_and_clear,
+    KdfParams, Kek, VaultConfig, VaultState,
+};
+use crate::AppState;
+use rusqlite::{params, Connection, MAIN_DB};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use walkdir::WalkDir;
+use zeroize::Zeroizing;
+
+/// Header identifying an encrypted SQLite export
+const SQLITE_EXPORT_MAGIC: &[u8; 8] = b"GNSQLv01";
+
+/// Header identifying a passphrase-wrapped vault backup
+const BACKUP_MAGIC: &[u8; 8] = b"GNBAKv01";
+
+/// Header identifying a passphrase-wrapped snapshot patch
+const PATCH_MAGIC: &[u8; 8] = b"GNPATv01";
+
+/// Header identifying a passphrase-encrypted tag index
+const TAG_INDEX_MAGIC: &[u8; 8] = b"GNTAGv01";
+
+const SALT_SIZE: usize = 32;
+
+const SQLITE_SCHEMA: &str = "CREATE TABLE notes (
+    path TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    created TEXT,
+    modified INTEGER
+)";
+
+/// What `export_to_sqlite` wrote
+#[derive(Debug, Serialize)]
+pub struct SqliteExport {
+    pub notes: usize,
+    /// Notes that couldn't be read and were left out
+    pub failed: Vec<String>,
+}
+
+/// Export every note into a passphrase-encrypted SQLite database
+///
+/// The database is built in memory and only its encrypted image is written,
+/// so no plaintext copy touches disk. File layout: magic, Argon2 salt, then
+/// the AES-GCM encrypted database bytes. Notes that can't be read are listed
+/// rather than stopping the export.
+#[tauri::command]
+pub fn export_to_sqlite(
+    dest_path: String,
+    passphrase: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<SqliteExport, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    if passphrase.is_empty() {
+        return Err("Passphrase cannot be empty".to_string());
+    }
+    if is_inside_notes_dir(&notes_dir, Path::new(&dest_path)) {
+        return Err("Export destination must be outside the notes folder".to_string());
+    }
+
+    let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    conn.execute(SQLITE_SCHEMA, []).map_err(|e| e.to_string())?;
+
+    let mut export = SqliteExport {
+        notes: 0,
+        failed: Vec::new(),
+    };
+    for file in walk_note_files(&notes_dir) {
+        let rel_path = rel_note_path(&notes_dir, &file);
+        let Ok(content) = read_note_content(&notes_dir, &rel_path, &vault) else {
+            export.failed.push(rel_path);
+            continue;
+        };
+
+        let created = note_created_date(&file).map(|d| d.format("%Y-%m-%d").to_string());
+        let modified = fs::metadata(&file)
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+
+        // A JSON array, so queries can use `json_each(tags)`
+        let tags = serde_json::to_string(&parse_frontmatter(&content).0.tags)
+            .map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO notes (path, title, content, tags, created, modified) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                rel_path,
+                extract_title(&content, &file),
+                content,
+                tags,
+                created,
+                modified
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        export.notes += 1;
+    }
+
+    let db_bytes = Zeroizing::new(
+        conn.serialize(MAIN_DB)
+            .map_err(|e| format!("Failed to serialize database: {}", e))?
+            .to_vec(),
+    );
+    drop(conn);
+
+    let salt = generate_salt();
+    let key = Kek::derive(&passphrase, &salt, &KdfParams::V1)?;
+    let encrypted = encrypt(key.as_bytes(), &db_bytes)?;
+
+    let mut out = Vec::with_capacity(SQLITE_EXPORT_MAGIC.len() + SALT_SIZE + encrypted.len());
+    out.extend_from_slice(SQLITE_EXPORT_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&encrypted);
+    fs::write(&dest_path, out).map_err(|e| format!("Failed to write export: {}", e))?;
+
+    Ok(export)
+}
+
+/// Import notes from a database written by `export_to_sqlite`
+///
+/// Each note is re-encrypted into the vault at its original path; if a note
+/// already exists there, the import lands next to it with a numeric suffix.
+/// Returns the imported relative paths.
+#[tauri::command]
+pub fn import_from_sqlite(
+    src_path: String,
+    passphrase: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<String>, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    let data = fs::read(&src_path).map_err(|e| format!("Failed to read export: {}", e))?;
+    let header_len = SQLITE_EXPORT_MAGIC.len() + SALT_SIZE;
+    if data.len() < header_len || &data[..SQLITE_EXPORT_MAGIC.len()] != SQLITE_EXPORT_MAGIC {
+        return Err("Not a ghostnote SQLite export".to_string());
+    }
+
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&data[SQLITE_EXPORT_MAGIC.len()..header_len]);
+    let key = Kek::derive(&passphrase, &salt, &KdfParams::V1)?;
+    let db_bytes = Zeroizing::new(
+        decrypt(key.as_bytes(), &data[header_len..]).map_err(|_| "Wrong passphrase".to_string())?,
+    );
+
+    let mut conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    conn.deserialize_read_exact(MAIN_DB, &db_bytes[..], db_bytes.len(), true)
+        .map_err(|e| format!("Invalid database: {}", e))?;
+
+    let rows: Vec<(String, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT path, content FROM notes ORDER BY path")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+
+    let mut imported = Vec::new();
+    for (path, content) in rows {
+        let path = checked_note_path(&notes_dir, &path)?;
+        // Only a legacy note's own extension goes; `v1.2-notes` keeps its dot
+        let base = match Path::new(&path).extension().and_then(|e| e.to_str()) {
+            Some("md" | "txt") => Path::new(&path).with_extension(""),
+            _ => PathBuf::from(&path),
+        };
+        let folder_path = notes_dir.join(base.parent().unwrap_or(Path::new("")));
+        let base_name = base
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "untitled".to_string());
+
+        fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+        let base_path = unique_note_path(&folder_path, &base_name);
+        let rel_path = rel_note_path(&notes_dir, &base_path);
+
+        write_encrypted_note(&notes_dir, &rel_path, &content, &vault, None)?;
+        index_note(&state, &rel_path, &content);
+        imported.push(rel_path);
+    }
+
+    if !imported.is_empty() {
+        vault.bump_content_version();
+    }
+
+    Ok(imported)
+}
+
+/// Whether a path relative to the notes folder belongs in a vault backup:
+/// the `.vault` metadata plus each note's ciphertext, wrapped key and sidecar
+fn is_backup_file(rel_path: &Path) -> bool {
+    if rel_path.starts_with(".vault") {
+        return true;
+    }
+    if rel_path.starts_with(TRASH_DIR) || rel_path.starts_with(RESTORE_DIR) {
+        return false;
+    }
+    matches!(
+        rel_path.extension().and_then(|e| e.to_str()),
+        Some("enc" | "key" | "meta")
+    )
+}
+
+/// Files under `notes_dir` that go into a backup, relative to it
+fn backup_files(notes_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.path().strip_prefix(notes_dir).ok().map(Path::to_path_buf))
+        .filter(|rel| is_backup_file(rel))
+        .collect()
+}
+
+/// Encrypt `data` under `password` behind `magic` (magic, Argon2 salt, then
+/// the AES-GCM encrypted data), or return it as it is without a password
+fn seal(magic: &[u8; 8], data: Vec<u8>, password: Option<String>) -> Result<Vec<u8>, String> {
+    let Some(password) = password.filter(|p| !p.is_empty()) else {
+        return Ok(data);
+    };
+    let salt = generate_salt();
+    let key = Kek::derive(&password, &salt, &KdfParams::V1)?;
+    let encrypted = encrypt(key.as_bytes(), &data)?;
+
+    let mut out = Vec::with_capacity(magic.len() + SALT_SIZE + encrypted.len());
+    out.extend_from_slice(magic);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&encrypted);
+    Ok(out)
+}
+
+/// Undo `seal`; data without `magic` wasn't encrypted and is returned as is
+fn unseal(
+    magic: &[u8; 8],
+    data: Vec<u8>,
+    password: Option<String>,
+    what: &str,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    if !data.starts_with(magic) {
+        return Ok(Zeroizing::new(data));
+    }
+    let header_len = magic.len() + SALT_SIZE;
+    if data.len() < header_len {
+        return Err(format!("The {} file is truncated", what));
+    }
+    let password = password
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| format!("This {} is protected by a passphrase", what))?;
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&data[magic.len()..header_len]);
+    let key = Kek::derive(&password, &salt, &KdfParams::V1)?;
+    Ok(Zeroizing::new(
+        decrypt(key.as_bytes(), &data[header_len..]).map_err(|_| "Wrong passphrase".to_string())?,
+    ))
+}
+
+/// The files in a backup archive, by path relative to the notes folder,
+/// checking each is something a backup may hold
+fn backup_entries(notes_dir: &Path, archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut entries = Vec::new();
+    for entry in tar::Archive::new(archive)
+        .entries()
+        .map_err(|e| format!("Invalid backup: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Invalid backup: {}", e))?;
+        let rel_path = entry
+            .path()
+            .map_err(|e| format!("Invalid backup: {}", e))?
+            .into_owned();
+        let rel_str = checked_note_path(notes_dir, &rel_path.to_string_lossy())?;
+        if !entry.header().entry_type().is_file() || !is_backup_file(Path::new(&rel_str)) {
+            return Err(format!("Unexpected file in backup: {}", rel_path.display()));
+        }
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut contents)
+            .map_err(|e| format!("Invalid backup: {}", e))?;
+        entries.push((rel_str, contents));
+    }
+    Ok(entries)
+}
+
+/// Bundle the vault's ciphertext into a single tar archive
+///
+/// Notes are already encrypted on disk, so the archive is a plain tar of the
+/// `.enc`/`.key`/`.meta` files and `.vault` metadata and the vault doesn't
+/// need to be unlocked. With a passphrase the whole archive is additionally
+/// encrypted (magic, Argon2 salt, then the AES-GCM encrypted tar) so it can
+/// be carried around without exposing file names. Returns the file count.
+#[tauri::command]
+pub fn export_vault_backup(
+    dest_path: String,
+    password: Option<String>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    if is_inside_notes_dir(&notes_dir, Path::new(&dest_path)) {
+        return Err("Backup destination must be outside the notes folder".to_string());
+    }
+
+    let files = backup_files(&notes_dir);
+    let mut builder = tar::Builder::new(Vec::new());
+    for rel_path in &files {
+        builder
+            .append_path_with_name(notes_dir.join(rel_path), rel_path)
+            .map_err(|e| format!("Failed to add {} to backup: {}", rel_path.display(), e))?;
+    }
+    let archive = builder
+        .into_inner()
+        .map_err(|e| format!("Failed to build backup: {}", e))?;
+
+    let out = seal(BACKUP_MAGIC, archive, password)?;
+    fs::write(&dest_path, out).map_err(|e| format!("Failed to write backup: {}", e))?;
+
+    Ok(files.len())
+}
+
+/// Restore a backup written by `export_vault_backup` into the notes folder
+///
+/// Refuses to touch a folder that already holds a vault or notes unless
+/// `force` is set, in which case the existing vault metadata, note files and
+/// trash are replaced so nothing is left encrypted under the old password.
+/// The backup is written out in full before anything existing is touched.
+/// The vault is locked afterwards; unlock with the backup's master password.
+/// Returns the number of files restored.
+#[tauri::command]
+pub fn import_vault_backup(
+    src_path: String,
+    password: Option<String>,
+    force: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<usize, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let config = VaultConfig::new(&notes_dir);
+
+    let data = fs::read(&src_path).map_err(|e| format!("Failed to read backup: {}", e))?;
+    let archive = unseal(BACKUP_MAGIC, data, password, "backup")?;
+
+    // Check the archive before deleting anything
+    let entries = backup_entries(&notes_dir, &archive)?;
+    if !entries
+        .iter()
+        .any(|(rel, _)| Path::new(rel) == Path::new(".vault/salt"))
+    {
+        return Err("Backup doesn't contain a vault".to_string());
+    }
+
+    let existing = backup_files(&notes_dir);
+    if (!existing.is_empty() || notes_dir.join(TRASH_DIR).exists() || is_vault_initialized(&config))
+        && !force.unwrap_or(false)
+    {
+        return Err("The notes folder already contains a vault".to_string());
+    }
+
+    let restore_dir = notes_dir.join(RESTORE_DIR);
+    let _ = fs::remove_dir_all(&restore_dir);
+    let staged_dir = restore_dir.join("new");
+    for (rel_path, contents) in &entries {
+        let path = staged_dir.join(rel_path);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, contents));
+        if let Err(e) = written {
+            let _ = fs::remove_dir_all(&restore_dir);
+            return Err(format!("Failed to restore {}: {}", rel_path, e));
+        }
+    }
+
+    // The restored notes are wrapped under the backup's KEK, not the one in memory
+    lock_and_clear(&vault, &state);
+    swap_in_backup(&notes_dir, &existing, &entries)?;
+    state.content_hashes.lock().unwrap().clear();
+    vault.bump_content_version();
+
+    Ok(entries.len())
+}
+
+/// Replace the vault's files and trash with the backup staged in
+/// `RESTORE_DIR/new`
+///
+/// The old files are moved aside to `RESTORE_DIR/old` and deleted only once
+/// every restored file is in place; if that fails part way they're moved
+/// back.
+fn swap_in_backup(
+    notes_dir: &Path,
+    existing: &[PathBuf],
+    entries: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let restore_dir = notes_dir.join(RESTORE_DIR);
+    let staged_dir = restore_dir.join("new");
+    let old_dir = restore_dir.join("old");
+
+    // `.vault` and the trash go aside whole, everything else file by file
+    let mut old: Vec<PathBuf> = existing
+        .iter()
+        .filter(|rel| !rel.starts_with(".vault"))
+        .cloned()
+        .collect();
+    old.extend(
+        [".vault", TRASH_DIR]
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|rel| notes_dir.join(rel).exists()),
+    );
+    let restored: Vec<PathBuf> = entries.iter().map(|(rel, _)| PathBuf::from(rel)).collect();
+
+    let mut moved_aside = Vec::new();
+    let mut moved_in = Vec::new();
+    let swapped = move_all(notes_dir, &old_dir, &old, &mut moved_aside)
+        .and_then(|_| move_all(&staged_dir, notes_dir, &restored, &mut moved_in));
+    if let Err(e) = swapped {
+        let undone = move_all(notes_dir, &staged_dir, &moved_in, &mut Vec::new())
+            .and_then(|_| move_all(&old_dir, notes_dir, &moved_aside, &mut Vec::new()));
+        if undone.is_ok() {
+            let _ = fs::remove_dir_all(&restore_dir);
+        }
+        return Err(format!("Failed to restore backup: {}", e));
+    }
+
+    fs::remove_dir_all(&restore_dir)
+        .map_err(|e| format!("Failed to remove the replaced vault: {}", e))
+}
+
+/// Move each of `rel_paths` from under `from` to the same place under `to`,
+/// recording in `moved` the ones that were
+fn move_all(
+    from: &Path,
+    to: &Path,
+    rel_paths: &[PathBuf],
+    moved: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for rel in rel_paths {
+        let dest = to.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from.join(rel), &dest)?;
+        moved.push(rel.clone());
+    }
+    Ok(())
+}
+
+/// Name of the manifest inside a snapshot patch
+const PATCH_MANIFEST: &str = "patch.json";
+
+/// Folder inside a snapshot patch holding the added and changed files
+const PATCH_FILES_DIR: &str = "files";
+
+/// What a snapshot patch changes, stored as `patch.json` inside it
+#[derive(Serialize, Deserialize)]
+struct PatchManifest {
+    /// BLAKE3 of every file in the snapshot the patch applies to, by path
+    base: BTreeMap<String, String>,
+    /// Files added or changed, stored under `files/` in the patch
+    written: Vec<String>,
+    removed: Vec<String>,
+}
+
+/// Files that differ between two snapshots, by path
+#[derive(Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Read a backup written by `export_vault_backup` into its files, by path
+fn read_snapshot(
+    notes_dir: &Path,
+    path: &str,
+    password: Option<String>,
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let archive = unseal(BACKUP_MAGIC, data, password, "backup")?;
+    Ok(backup_entries(notes_dir, &archive)?.into_iter().collect())
+}
+
+/// Tar up in-memory `files`, each under its path
+fn build_archive<'a>(
+    files: impl IntoIterator<Item = (String, &'a [u8])>,
+) -> Result<Vec<u8>, String> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o600);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &path, contents)
+            .map_err(|e| format!("Failed to add {}: {}", path, e))?;
+    }
+    builder
+        .into_inner()
+        .map_err(|e| format!("Failed to build archive: {}", e))
+}
+
+/// Write the difference between two vault backups to `dest_patch`
+///
+/// Files are compared by path and ciphertext hash, so nothing is decrypted
+/// and the vault needn't be unlocked. The patch holds the added and changed
+/// files plus a manifest of the old snapshot's hashes, so it's only ever
+/// applied to the snapshot it was made from. `password` opens both
+/// snapshots and, if given, encrypts the patch the way backups are.
+#[tauri::command]
+pub fn diff_snapshots(
+    old_snapshot: String,
+    new_snapshot: String,
+    dest_patch: String,
+    password: Option<String>,
+    state: State<AppState>,
+) -> Result<SnapshotDiff, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    if is_inside_notes_dir(&notes_dir, Path::new(&dest_patch)) {
+        return Err("Patch destination must be outside the notes folder".to_string());
+    }
+
+    let old = read_snapshot(&notes_dir, &old_snapshot, password.clone())?;
+    let new = read_snapshot(&notes_dir, &new_snapshot, password.clone())?;
+
+    let mut diff = SnapshotDiff {
+        added: Vec::new(),
+        changed: Vec::new(),
+        removed: Vec::new(),
+    };
+    for (path, contents) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old_contents) if old_contents != contents => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .cloned()
+        .collect();
+
+    let manifest = PatchManifest {
+        base: old
+            .iter()
+            .map(|(path, contents)| (path.clone(), blake3::hash(contents).to_hex().to_string()))
+            .collect(),
+        written: diff.added.iter().chain(&diff.changed).cloned().collect(),
+        removed: diff.removed.clone(),
+    };
+    let manifest_json =
+        serde_json::to_vec(&manifest).map_err(|e| format!("Failed to serialize patch: {}", e))?;
+
+    let files = manifest
+        .written
+        .iter()
+        .map(|path| (format!("{}/{}", PATCH_FILES_DIR, path), &new[path][..]));
+    let archive = build_archive(
+        std::iter::once((PATCH_MANIFEST.to_string(), &manifest_json[..])).chain(files),
+    )?;
+    let out = seal(PATCH_MAGIC, archive, password)?;
+    fs::write(&dest_patch, out).map_err(|e| format!("Failed to write patch: {}", e))?;
+
+    Ok(diff)
+}
+
+/// Rebuild the newer snapshot from the older one and a `diff_snapshots` patch
+///
+/// `base` must be exactly the snapshot the patch was made from. The result
+/// is written to `dest` as a backup `import_vault_backup` can restore,
+/// encrypted with `password` if one is given. Returns its file count.
+#[tauri::command]
+pub fn apply_snapshot_patch(
+    base: String,
+    patch: String,
+    dest: String,
+    password: Option<String>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    if is_inside_notes_dir(&notes_dir, Path::new(&dest)) {
+        return Err("Snapshot destination must be outside the notes folder".to_string());
+    }
+
+    let mut files = read_snapshot(&notes_dir, &base, password.clone())?;
+
+    let data = fs::read(&patch).map_err(|e| format!("Failed to read patch: {}", e))?;
+    let archive = unseal(PATCH_MAGIC, data, password.clone(), "patch")?;
+    let mut manifest = None;
+    let mut written = BTreeMap::new();
+    for entry in tar::Archive::new(&archive[..])
+        .entries()
+        .map_err(|e| format!("Invalid patch: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Invalid patch: {}", e))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("Invalid patch: {}", e))?
+            .to_string_lossy()
+            .to_string();
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut contents)
+            .map_err(|e| format!("Invalid patch: {}", e))?;
+        if path == PATCH_MANIFEST {
+            let parsed: PatchManifest = serde_json::from_slice(&contents)
+                .map_err(|e| format!("Invalid patch manifest: {}", e))?;
+            manifest = Some(parsed);
+        } else if let Some(rel) = path.strip_prefix(&format!("{}/", PATCH_FILES_DIR)) {
+            written.insert(checked_note_path(&notes_dir, rel)?, contents);
+        } else {
+            return Err(format!("Unexpected file in patch: {}", path));
+        }
+    }
+    let manifest = manifest.ok_or("Patch has no manifest")?;
+
+    let base_hashes: BTreeMap<String, String> = files
+        .iter()
+        .map(|(path, contents)| (path.clone(), blake3::hash(contents).to_hex().to_string()))
+        .collect();
+    if base_hashes != manifest.base {
+        return Err("This patch was made from a different snapshot".to_string());
+    }
+
+    for path in &manifest.removed {
+        files.remove(path);
+    }
+    for path in &manifest.written {
+        let rel = checked_note_path(&notes_dir, path)?;
+        if !is_backup_file(Path::new(&rel)) {
+            return Err(format!("Unexpected file in patch: {}", path));
+        }
+        let contents = written
+            .remove(&rel)
+            .ok_or_else(|| format!("Patch is missing {}", path))?;
+        files.insert(rel, contents);
+    }
+
+    let archive = build_archive(
+        files
+            .iter()
+            .map(|(path, contents)| (path.clone(), &contents[..])),
+    )?;
+    let out = seal(BACKUP_MAGIC, archive, password)?;
+    fs::write(&dest, out).map_err(|e| format!("Failed to write snapshot: {}", e))?;
+
+    Ok(files.len())
+}
+
+/// Folder under a plaintext export holding the decrypted attachments
+const PLAINTEXT_ATTACHMENTS_DIR: &str = "attachments";
+
+/// What `export_plaintext` wrote
+#[derive(Debug, Serialize)]
+pub struct PlaintextExport {
+    pub notes: usize,
+    pub attachments: usize,
+    /// Notes and attachments that couldn't be decrypted and were left out
+    pub failed: Vec<String>,
+    /// Reminder that the export is readable without the password
+    pub warning: String,
+}
+
+/// Decrypt the whole vault into `dest_dir`, for leaving the app
+///
+/// Each note becomes a `.md` file at its place in the folder tree (legacy
+/// `.md`/`.txt` notes keep their names) and each attachment a file named by
+/// its id under `attachments/`. Where an encrypted `foo` and a legacy
+/// `foo.md` would share a file, the second one written gets a numbered name.
+/// The master password must be entered again, and `dest_dir` must be an
+/// empty or new folder outside the notes folder. Notes that fail to decrypt
+/// are listed rather than stopping the export.
+#[tauri::command]
+pub fn export_plaintext(
+    dest_dir: String,
+    password: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<PlaintextExport, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let dest_dir = PathBuf::from(dest_dir);
+
+    if !vault.is_unlocked() {
+        return Err("Vault is locked".to_string());
+    }
+    derive_verified_kek(&vault.config()?, &password)?;
+
+    if is_inside_notes_dir(&notes_dir, &dest_dir) {
+        return Err("Export destination must be outside the notes folder".to_string());
+    }
+    if fs::read_dir(&dest_dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err("Export destination must be an empty folder".to_string());
+    }
+    fs::create_dir_all(&dest_dir).map_err(|e| format!("Failed to create export folder: {}", e))?;
+
+    let mut export = PlaintextExport {
+        notes: 0,
+        attachments: 0,
+        failed: Vec::new(),
+        warning: format!(
+            "Notes were written to {} without encryption. Anyone who can read that folder \
+             can read them; delete it securely once you're done with it.",
+            dest_dir.display()
+        ),
+    };
+
+    for file in walk_note_files(&notes_dir) {
+        let rel_path = rel_note_path(&notes_dir, &file);
+        let encrypted = file.extension().is_some_and(|ext| ext == "enc");
+        // A legacy `foo.md` is read from its own file, not the encrypted
+        // `foo` that its id also names
+        let content = if encrypted {
+            read_note_content(&notes_dir, &rel_path, &vault).ok()
+        } else {
+            fs::read_to_string(&file).ok()
+        };
+        let Some(content) = content.map(Zeroizing::new) else {
+            export.failed.push(rel_path);
+            continue;
+        };
+        let out_path = if encrypted {
+            dest_dir.join(format!("{}.md", rel_path))
+        } else {
+            dest_dir.join(&rel_path)
+        };
+        let folder = out_path.parent().unwrap_or(&dest_dir);
+        fs::create_dir_all(folder).map_err(|e| format!("Failed to create folder: {}", e))?;
+        let out_path = unique_legacy_path(folder, &out_path);
+        fs::write(&out_path, content.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", out_path.display(), e))?;
+        export.notes += 1;
+    }
+
+    let attachments_dir = dest_dir.join(PLAINTEXT_ATTACHMENTS_DIR);
+    for id in stored_attachment_ids(&notes_dir) {
+        let bytes = match decrypt_attachment(&notes_dir, &id, &vault) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                export
+                    .failed
+                    .push(format!("{}/{}", PLAINTEXT_ATTACHMENTS_DIR, id));
+                continue;
+            }
+        };
+        fs::create_dir_all(&attachments_dir)
+            .map_err(|e| format!("Failed to create folder: {}", e))?;
+        fs::write(attachments_dir.join(&id), &*bytes)
+            .map_err(|e| format!("Failed to write attachment {}: {}", id, e))?;
+        export.attachments += 1;
+    }
+
+    Ok(export)
+}
+
+/// A note carrying a tag, as listed in a tag index
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaggedNote {
+    pub id: String,
+    pub title: String,
+}
+
+/// Every frontmatter tag with the notes carrying it, as `export_tag_index`
+/// writes it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagIndex {
+    pub tags: BTreeMap<String, Vec<TaggedNote>>,
+}
+
+/// Write every tag and the notes carrying it to `dest_path` as JSON
+///
+/// The index holds note titles, so with a passphrase it is encrypted the way
+/// `export_to_sqlite` encrypts its database (magic, Argon2 salt, then the
+/// AES-GCM encrypted JSON); without one it is written as plain JSON. Notes
+/// under a tag are most recently modified first. Returns the number of tags.
+#[tauri::command]
+pub fn export_tag_index(
+    dest_path: String,
+    passphrase: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<usize, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    if is_inside_notes_dir(&notes_dir, Path::new(&dest_path)) {
+        return Err("Export destination must be outside the notes folder".to_string());
+    }
+
+    let tags = with_tag_map(&state, &vault, |notes| notes.clone())?;
+    let index = TagIndex {
+        tags: tags
+            .into_iter()
+            .map(|(tag, paths)| {
+                let notes = load_notes_meta(&notes_dir, &paths, &vault)
+                    .into_iter()
+                    .map(|meta| TaggedNote {
+                        id: meta.id,
+                        title: meta.title,
+                    })
+                    .collect();
+                (tag, notes)
+            })
+            .collect(),
+    };
+
+    let json = serde_json::to_vec_pretty(&index)
+        .map_err(|e| format!("Failed to serialize tag index: {}", e))?;
+    let out = seal(TAG_INDEX_MAGIC, json, passphrase)?;
+    fs::write(&dest_path, out).map_err(|e| format!("Failed to write tag index: {}", e))?;
+
+    Ok(index.tags.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::notes::list_tags;
+    use crate::commands::testing::TestVault;
+
+    fn tagged_notes(vault: &TestVault) {
+        vault.write("alpha", "---\ntags: [work, ideas]\n---\n# Alpha\n");
+        vault.write("beta", "---\ntags: work\n---\n# Beta\n");
+        vault.write("gamma", "# Gamma\n");
+    }
+
+    #[tokio::test]
+    async fn tag_index_matches_list_tags() {
+        let vault = TestVault::new().await;
+        tagged_notes(&vault);
+
+        let dest = vault.outside("tags.json");
+        let exported = export_tag_index(
+            dest.to_string_lossy().to_string(),
+            None,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        let index: TagIndex = serde_json::from_slice(&fs::read(&dest).unwrap()).unwrap();
+
+        let tags = list_tags(vault.state(), vault.vault()).unwrap();
+        assert_eq!(exported, tags.len());
+        assert_eq!(index.tags.len(), tags.len());
+        for tag in &tags {
+            assert_eq!(index.tags[&tag.tag].len(), tag.count, "tag {}", tag.tag);
+        }
+
+        let mut work: Vec<_> = index.tags["work"]
+            .iter()
+            .map(|note| (note.id.as_str(), note.title.as_str()))
+            .collect();
+        work.sort();
+        assert_eq!(work, [("alpha", "Alpha"), ("beta", "Beta")]);
+        assert_eq!(index.tags["ideas"][0].id, "alpha");
+    }
+
+    #[tokio::test]
+    async fn tag_index_is_encrypted_with_a_passphrase() {
+        let vault = TestVault::new().await;
+        tagged_notes(&vault);
+
+        let dest = vault.outside("tags.bin");
+        export_tag_index(
+            dest.to_string_lossy().to_string(),
+            Some("index passphrase".to_string()),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+
+        let data = fs::read(&dest).unwrap();
+        assert!(data.starts_with(TAG_INDEX_MAGIC));
+        let header_len = TAG_INDEX_MAGIC.len() + SALT_SIZE;
+        let mut salt = [0u8; SALT_SIZE];
+        salt.copy_from_slice(&data[TAG_INDEX_MAGIC.len()..header_len]);
+        let key = Kek::derive("index passphrase", &salt, &KdfParams::V1).unwrap();
+        let json = decrypt(key.as_bytes(), &data[header_len..]).unwrap();
+        let index: TagIndex = serde_json::from_slice(&json).unwrap();
+        assert_eq!(index.tags["work"].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn tag_index_refuses_a_destination_in_the_notes_folder() {
+        let vault = TestVault::new().await;
+        let dest = vault.path().join("tags.json");
+
+        let result = export_tag_index(
+            dest.to_string_lossy().to_string(),
+            None,
+            vault.state(),
+            vault.vault(),
+        );
+        assert!(result.is_err());
+        assert!(!dest.exists());
+    }
+
+    fn path_string(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn snapshot_patch_reproduces_the_newer_snapshot() {
+        let vault = TestVault::new().await;
+        vault.write("keep", "# Keep\n");
+        vault.write("change", "# Before\n");
+        vault.write("remove", "# Remove\n");
+        let old = path_string(&vault.outside("old.tar"));
+        export_vault_backup(old.clone(), None, vault.state()).unwrap();
+
+        vault.write("change", "# After\n");
+        fs::remove_file(vault.path().join("remove.enc")).unwrap();
+        fs::remove_file(vault.path().join("remove.key")).unwrap();
+        vault.write("add", "# Add\n");
+        let new = path_string(&vault.outside("new.tar"));
+        export_vault_backup(new.clone(), None, vault.state()).unwrap();
+
+        let patch = path_string(&vault.outside("delta.patch"));
+        let diff =
+            diff_snapshots(old.clone(), new.clone(), patch.clone(), None, vault.state()).unwrap();
+        assert!(diff.added.contains(&"add.enc".to_string()));
+        assert!(diff.changed.contains(&"change.enc".to_string()));
+        assert!(!diff.changed.contains(&"keep.enc".to_string()));
+        assert!(diff.removed.contains(&"remove.enc".to_string()));
+        assert!(diff.removed.contains(&"remove.key".to_string()));
+
+        let rebuilt = path_string(&vault.outside("rebuilt.tar"));
+        let count = apply_snapshot_patch(old, patch, rebuilt.clone(), None, vault.state()).unwrap();
+
+        let expected = read_snapshot(vault.path(), &new, None).unwrap();
+        assert_eq!(count, expected.len());
+        assert_eq!(
+            read_snapshot(vault.path(), &rebuilt, None).unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_patch_refuses_a_different_base() {
+        let vault = TestVault::new().await;
+        vault.write("note", "# One\n");
+        let old = path_string(&vault.outside("old.tar"));
+        export_vault_backup(old.clone(), None, vault.state()).unwrap();
+        vault.write("note", "# Two\n");
+        let new = path_string(&vault.outside("new.tar"));
+        export_vault_backup(new.clone(), None, vault.state()).unwrap();
+
+        let patch = path_string(&vault.outside("delta.patch"));
+        diff_snapshots(old, new.clone(), patch.clone(), None, vault.state()).unwrap();
+
+        let rebuilt = path_string(&vault.outside("rebuilt.tar"));
+        let result = apply_snapshot_patch(new, patch, rebuilt.clone(), None, vault.state());
+        assert!(result.is_err());
+        assert!(!Path::new(&rebuilt).exists());
+    }
+
+    #[tokio::test]
+    async fn sqlite_export_round_trips_into_another_vault() {
+        let source = TestVault::new().await;
+        source.write(
+            "alpha",
+            "---\ntags: [work, ideas]\n---\n# Alpha\n\nFirst note\n",
+        );
+        source.write("projects/beta", "# Beta\n\nSecond note\n");
+        source.write("v1.2-notes", "# Release\n");
+        source.write("broken", "# Broken\n");
+        fs::write(source.path().join("broken.enc"), b"not ciphertext").unwrap();
+
+        let dest = source.outside("notes.db");
+        let exported = export_to_sqlite(
+            path_string(&dest),
+            "export passphrase".to_string(),
+            source.state(),
+            source.vault(),
+        )
+        .unwrap();
+        assert_eq!(exported.notes, 3);
+        assert_eq!(exported.failed, ["broken"]);
+
+        let data = fs::read(&dest).unwrap();
+        assert!(data.starts_with(SQLITE_EXPORT_MAGIC));
+        let header_len = SQLITE_EXPORT_MAGIC.len() + SALT_SIZE;
+        let mut salt = [0u8; SALT_SIZE];
+        salt.copy_from_slice(&data[SQLITE_EXPORT_MAGIC.len()..header_len]);
+        let key = Kek::derive("export passphrase", &salt, &KdfParams::V1).unwrap();
+        let db_bytes = decrypt(key.as_bytes(), &data[header_len..]).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.deserialize_read_exact(MAIN_DB, &db_bytes[..], db_bytes.len(), true)
+            .unwrap();
+        let (title, tags): (String, String) = conn
+            .query_row(
+                "SELECT title, tags FROM notes WHERE path = 'alpha'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "Alpha");
+        assert_eq!(tags, r#"["work","ideas"]"#);
+
+        let target = TestVault::new().await;
+        let result = import_from_sqlite(
+            path_string(&dest),
+            "wrong passphrase".to_string(),
+            target.state(),
+            target.vault(),
+        );
+        assert!(result.is_err());
+
+        let imported = import_from_sqlite(
+            path_string(&dest),
+            "export passphrase".to_string(),
+            target.state(),
+            target.vault(),
+        )
+        .unwrap();
+        assert_eq!(imported, ["alpha", "projects/beta", "v1.2-notes"]);
+        for rel in imported {
+            assert_eq!(
+                read_note_content(target.path(), &rel, &target.vault()).unwrap(),
+                read_note_content(source.path(), &rel, &source.vault()).unwrap(),
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn forced_backup_restore_replaces_the_vault_and_its_trash() {
+        use crate::commands::notes::delete_note;
+        use crate::commands::testing::PASSWORD;
+        use crate::commands::vault::derive_verified_kek;
+
+        let source = TestVault::new().await;
+        source.write("alpha", "# Alpha\n");
+        let backup = path_string(&source.outside("vault.backup"));
+        export_vault_backup(backup.clone(), None, source.state()).unwrap();
+
+        let target = TestVault::new().await;
+        target.write("stale", "# Stale\n");
+        target.write("discarded", "# Discarded\n");
+        delete_note("discarded".to_string(), target.state(), target.vault()).unwrap();
+        assert!(target.path().join(TRASH_DIR).exists());
+
+        let refused =
+            import_vault_backup(backup.clone(), None, None, target.state(), target.vault());
+        assert!(refused.is_err());
+        assert!(target.path().join("stale.enc").exists());
+
+        import_vault_backup(backup, None, Some(true), target.state(), target.vault()).unwrap();
+        assert!(!target.vault().is_unlocked());
+        assert!(!target.path().join("stale.enc").exists());
+        assert!(!target.path().join(TRASH_DIR).exists());
+        assert!(!target.path().join(RESTORE_DIR).exists());
+
+        // Only the backup's key opens the vault now
+        let config = target.vault().config().unwrap();
+        target
+            .vault()
+            .unlock(derive_verified_kek(&config, PASSWORD).unwrap());
+        assert_eq!(
+            read_note_content(target.path(), "alpha", &target.vault()).unwrap(),
+            "# Alpha\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn plaintext_export_keeps_notes_that_share_a_file_name() {
+        use crate::commands::testing::PASSWORD;
+
+        let vault = TestVault::new().await;
+        vault.write("foo", "# Encrypted\n");
+        fs::write(vault.path().join("foo.md"), "# Legacy\n").unwrap();
+
+        let dest = vault.outside("export");
+        let export = export_plaintext(
+            path_string(&dest),
+            PASSWORD.to_string(),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert_eq!(export.notes, 2);
+        let mut contents: Vec<String> = fs::read_dir(&dest)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        contents.sort();
+        assert_eq!(contents, ["# Encrypted\n", "# Legacy\n"]);
+    }
+}
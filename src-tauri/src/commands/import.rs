@@ -0,0 +1,485 @@
+use crate::commands::notes::{
+    checked_note_path, enc_path, ensure_writable, note_created_date, rel_note_path, secure_delete,
+    slugify, unique_note_path, write_encrypted_note, TRASH_DIR,
+};
+use crate::commands::search::{index_note, unindex_note};
+use crate::commands::vault::VaultState;
+use crate::commands::watcher::record_own_write;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use walkdir::WalkDir;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncodingIssue {
+    pub path: String,
+    pub detected_encoding: String,
+}
+
+/// A bookmark parsed from a Netscape bookmarks file
+struct Bookmark {
+    title: String,
+    url: String,
+    /// Bookmark folder names from the outermost down
+    folders: Vec<String>,
+}
+
+/// Decode the handful of entities browsers emit in bookmark exports
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Get an attribute value from the inside of a tag (`A HREF="..." ...`)
+fn tag_attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let start = lower.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = start + tag[start..].find('"')?;
+    Some(decode_entities(&tag[start..end]))
+}
+
+/// Parse a Netscape-format bookmarks file (the format every browser exports)
+///
+/// Folders are `<H3>` headings followed by a nested `<DL>` list; bookmarks
+/// are `<A HREF>` links. Entries without a usable URL are skipped.
+fn parse_bookmarks(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks = Vec::new();
+    // Folder stack; `None` for lists that aren't introduced by a heading
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut pending_folder: Option<String> = None;
+
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find('>') else { break };
+        let tag = &rest[..close];
+        rest = &rest[close + 1..];
+
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match name.as_str() {
+            "h3" => {
+                let end = rest.to_ascii_lowercase().find("</h3>").unwrap_or(rest.len());
+                pending_folder = Some(decode_entities(&rest[..end]));
+                rest = &rest[end..];
+            }
+            "dl" => stack.push(pending_folder.take()),
+            "/dl" => {
+                stack.pop();
+            }
+            "a" => {
+                let end = rest.to_ascii_lowercase().find("</a>").unwrap_or(rest.len());
+                let text = decode_entities(&rest[..end]);
+                rest = &rest[end..];
+
+                let Some(url) = tag_attribute(tag, "href").filter(|u| !u.is_empty()) else {
+                    continue;
+                };
+                if url.starts_with("javascript:") || url.starts_with("place:") {
+                    continue;
+                }
+
+                bookmarks.push(Bookmark {
+                    title: if text.is_empty() { url.clone() } else { text },
+                    url,
+                    folders: stack.iter().flatten().cloned().collect(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    bookmarks
+}
+
+/// Turn a bookmark folder name into a single safe path segment
+fn folder_segment(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '-' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').to_string();
+    if cleaned.is_empty() {
+        "untitled".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// Import a browser bookmarks export as one note per bookmark
+///
+/// Bookmark folders become note folders under `folder`. Returns the relative
+/// paths of the created notes.
+#[tauri::command]
+pub fn import_bookmarks_html(
+    src_html: String,
+    folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<String>, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, &folder)?;
+
+    let bytes = fs::read(&src_html).map_err(|e| format!("Failed to read bookmarks: {}", e))?;
+    let html = String::from_utf8_lossy(&bytes);
+
+    let date_str = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut imported = Vec::new();
+
+    for bookmark in parse_bookmarks(&html) {
+        let mut folder_path: PathBuf = notes_dir.join(&folder);
+        for name in &bookmark.folders {
+            folder_path.push(folder_segment(name));
+        }
+        fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+
+        let slug = match slugify(&bookmark.title) {
+            s if s.is_empty() => "bookmark".to_string(),
+            s => s,
+        };
+        let base_path = unique_note_path(&folder_path, &format!("{}-{}", date_str, slug));
+        let rel_path = base_path
+            .strip_prefix(&notes_dir)
+            .unwrap_or(Path::new(""))
+            .to_string_lossy()
+            .to_string();
+
+        let content = format!(
+            "# {}\n\n[{}]({})\n",
+            bookmark.title, bookmark.title, bookmark.url
+        );
+        write_encrypted_note(&notes_dir, &rel_path, &content, &vault, None)?;
+        index_note(&state, &rel_path, &content);
+        imported.push(rel_path);
+    }
+
+    if !imported.is_empty() {
+        vault.bump_content_version();
+    }
+
+    Ok(imported)
+}
+
+/// Import a tree of Markdown files (an Obsidian vault, say) as encrypted notes
+///
+/// The source folder hierarchy is recreated under `dest_folder`. Each `.md`
+/// file becomes a note named like `create_note` would name it: the date
+/// (taken from a leading `YYYY-MM-DD` in the file name, else the file's
+/// creation date) followed by the slugified name. Hidden folders such as
+/// `.obsidian` and non-Markdown files are skipped. Returns the relative paths
+/// of the created notes.
+#[tauri::command]
+pub fn import_markdown_folder(
+    src_dir: String,
+    dest_folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<String>, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let dest_folder = checked_note_path(&notes_dir, &dest_folder)?;
+    let src_dir = PathBuf::from(&src_dir);
+
+    if !src_dir.is_dir() {
+        return Err(format!("'{}' is not a folder", src_dir.display()));
+    }
+    if src_dir.starts_with(&notes_dir) || notes_dir.starts_with(&src_dir) {
+        return Err("Can't import a folder that overlaps the notes folder".to_string());
+    }
+
+    let today = chrono::Local::now().date_naive();
+    let mut imported = Vec::new();
+
+    let entries = WalkDir::new(&src_dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok());
+
+    for entry in entries {
+        let path = entry.path();
+        let rel_dir = if entry.file_type().is_dir() {
+            path.strip_prefix(&src_dir)
+        } else {
+            path.parent().unwrap_or(&src_dir).strip_prefix(&src_dir)
+        }
+        .unwrap_or(Path::new(""));
+
+        let mut folder_path = notes_dir.join(&dest_folder);
+        for name in rel_dir.iter() {
+            folder_path.push(folder_segment(&name.to_string_lossy()));
+        }
+        fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+
+        let is_markdown = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+        if !entry.file_type().is_file() || !is_markdown {
+            continue;
+        }
+
+        let date = note_created_date(path).unwrap_or(today);
+        let date_str = date.format("%Y-%m-%d").to_string();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = stem.strip_prefix(date_str.as_str()).unwrap_or(&stem);
+        let slug = slugify(name);
+        let base_name = if slug.is_empty() {
+            date_str
+        } else {
+            format!("{}-{}", date_str, slug)
+        };
+        let base_path = unique_note_path(&folder_path, &base_name);
+        let rel_path = rel_note_path(&notes_dir, &base_path);
+
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let content = String::from_utf8_lossy(&bytes);
+        write_encrypted_note(&notes_dir, &rel_path, &content, &vault, None)?;
+        index_note(&state, &rel_path, &content);
+        imported.push(rel_path);
+    }
+
+    if !imported.is_empty() {
+        vault.bump_content_version();
+    }
+
+    Ok(imported)
+}
+
+/// Find legacy plaintext notes that aren't valid UTF-8 and guess their encoding
+#[tauri::command]
+pub fn detect_encoding_issues(state: State<AppState>) -> Result<Vec<EncodingIssue>, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let mut issues = Vec::new();
+
+    for entry in WalkDir::new(&notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path().starts_with(notes_dir.join(".vault")))
+        .filter(|e| !e.path().starts_with(notes_dir.join(TRASH_DIR)))
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext == "md" || ext == "txt")
+                .unwrap_or(false)
+        })
+    {
+        let path = entry.path();
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("Warning: Failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        if std::str::from_utf8(&bytes).is_ok() {
+            continue;
+        }
+
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(&bytes, true);
+        let encoding = detector.guess(None, true);
+
+        issues.push(EncodingIssue {
+            path: path
+                .strip_prefix(&notes_dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            detected_encoding: encoding.name().to_string(),
+        });
+    }
+
+    Ok(issues)
+}
+
+/// Re-read a legacy note in `from_encoding` and rewrite it as UTF-8
+///
+/// Only plaintext `.md`/`.txt` notes qualify, and not read-only ones. With
+/// `encrypt` set the converted note is stored encrypted and the plaintext
+/// file shredded. Returns the note's (possibly new) relative path.
+#[tauri::command]
+pub fn fix_encoding(
+    path: String,
+    from_encoding: String,
+    encrypt: bool,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, String> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let full_path = notes_dir.join(&path);
+    let is_legacy = matches!(
+        full_path.extension().and_then(|e| e.to_str()),
+        Some("md" | "txt")
+    );
+    if !is_legacy || !full_path.is_file() {
+        return Err(format!("'{}' is not a plaintext note", path));
+    }
+    ensure_writable(&notes_dir, &path)?;
+
+    let encoding = encoding_rs::Encoding::for_label(from_encoding.as_bytes())
+        .ok_or_else(|| format!("Unknown encoding '{}'", from_encoding))?;
+
+    let bytes = fs::read(&full_path).map_err(|e| e.to_string())?;
+    let (content, _, had_errors) = encoding.decode(&bytes);
+    if had_errors {
+        return Err(format!("'{}' is not valid {}", path, encoding.name()));
+    }
+
+    if encrypt {
+        let rel_base = Path::new(&path).with_extension("").to_string_lossy().to_string();
+        if enc_path(&notes_dir.join(&rel_base)).exists() {
+            return Err(format!("An encrypted note already exists at '{}'", rel_base));
+        }
+        record_own_write(&state, &rel_base);
+        write_encrypted_note(&notes_dir, &rel_base, &content, &vault, None)?;
+        secure_delete(&full_path).map_err(|e| format!("Failed to delete plaintext: {}", e))?;
+        unindex_note(&state, &path);
+        index_note(&state, &rel_base, &content);
+        vault.bump_content_version();
+        Ok(rel_base)
+    } else {
+        record_own_write(&state, &path);
+        fs::write(&full_path, content.as_bytes()).map_err(|e| e.to_string())?;
+        index_note(&state, &path, &content);
+        vault.bump_content_version();
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::notes::read_note_content;
+    use crate::commands::testing::TestVault;
+
+    #[tokio::test]
+    async fn bookmarks_become_notes_with_their_titles_and_urls() {
+        let vault = TestVault::new().await;
+        let html = vault.outside("bookmarks.html");
+        fs::write(
+            &html,
+            r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<TITLE>Bookmarks</TITLE>
+<DL><p>
+    <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1700000000">Rust</A>
+    <DT><H3>Reading</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/?a=1&amp;b=2">Tips &amp; Tricks</A>
+        <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+    </DL><p>
+</DL><p>
+"#,
+        )
+        .unwrap();
+
+        let imported = import_bookmarks_html(
+            html.to_string_lossy().to_string(),
+            "imports".to_string(),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+
+        let contents: Vec<_> = imported
+            .iter()
+            .map(|path| read_note_content(vault.path(), path, &vault.vault()).unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "# Rust\n\n[Rust](https://www.rust-lang.org/)\n",
+                "# Tips & Tricks\n\n[Tips & Tricks](https://example.com/?a=1&b=2)\n",
+            ]
+        );
+        assert!(imported[0].starts_with("imports/"));
+        assert!(imported[1].starts_with("imports/Reading/"));
+    }
+
+    #[tokio::test]
+    async fn latin1_notes_are_detected_and_converted() {
+        let vault = TestVault::new().await;
+        let text = "# Caf\u{e9}\n\nCr\u{e8}me br\u{fb}l\u{e9}e \u{e0} la fran\u{e7}aise, na\u{ef}ve fa\u{e7}ade.\n";
+        let latin1: Vec<u8> = text.chars().map(|c| c as u8).collect();
+        fs::write(vault.path().join("menu.md"), &latin1).unwrap();
+        fs::write(vault.path().join("recipe.md"), &latin1).unwrap();
+        fs::write(vault.path().join("fine.md"), "Already UTF-8: caf\u{e9}\n").unwrap();
+
+        let mut issues = detect_encoding_issues(vault.state()).unwrap();
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.path.as_str(), issue.detected_encoding.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [("menu.md", "windows-1252"), ("recipe.md", "windows-1252")]
+        );
+
+        let path = fix_encoding(
+            "menu.md".to_string(),
+            issues[0].detected_encoding.clone(),
+            false,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(vault.path().join(path)).unwrap(), text);
+
+        let encrypted = fix_encoding(
+            "recipe.md".to_string(),
+            "latin1".to_string(),
+            true,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert_eq!(encrypted, "recipe");
+        assert!(!vault.path().join("recipe.md").exists());
+        assert_eq!(
+            read_note_content(vault.path(), &encrypted, &vault.vault()).unwrap(),
+            text
+        );
+        assert!(detect_encoding_issues(vault.state()).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_writable_plaintext_notes_are_converted() {
+        use crate::commands::notes::set_note_readonly;
+
+        let vault = TestVault::new().await;
+        vault.write("secret", "# Secret\n");
+        let latin1: Vec<u8> = "Caf\u{e9}\n".chars().map(|c| c as u8).collect();
+        fs::write(vault.path().join("locked.md"), &latin1).unwrap();
+        set_note_readonly("locked.md".to_string(), true, vault.state()).unwrap();
+
+        let fix = |path: &str| {
+            fix_encoding(
+                path.to_string(),
+                "latin1".to_string(),
+                true,
+                vault.state(),
+                vault.vault(),
+            )
+        };
+        for path in ["secret.enc", "secret.key", "locked.md"] {
+            assert!(fix(path).is_err(), "{} was converted", path);
+        }
+        assert_eq!(
+            read_note_content(vault.path(), "secret", &vault.vault()).unwrap(),
+            "# Secret\n"
+        );
+        assert_eq!(fs::read(vault.path().join("locked.md")).unwrap(), latin1);
+    }
+}
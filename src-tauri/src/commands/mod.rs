@@ -1,5 +1,14 @@
+pub mod attachments;
 pub mod audio;
+pub mod export;
+pub mod import;
 pub mod notes;
+pub mod pdf;
+pub mod search;
 pub mod settings;
 pub mod vault;
+pub mod watcher;
 pub mod whisper;
+
+#[cfg(test)]
+pub(crate) mod testing;
@@ -1,32 +1,240 @@
-use crate::commands::vault::{decrypt, encrypt, unwrap_dek, wrap_dek, Dek, VaultState};
+use crate::commands::attachments::remove_unreferenced_attachments;
+use crate::commands::pdf::markdown_to_pdf;
+use crate::commands::search::{
+    clear_index, index_note, indexed_note_ids, reindex_moved_note, unindex_note,
+};
+use crate::commands::settings::load_settings;
+use crate::commands::vault::{
+    decrypt, decrypt_file, encrypt_to_file, encrypt_with, read_salt, unwrap_dek, worth_compressing,
+    wrap_dek, Dek, Kek, VaultConfig, VaultState,
+};
+use crate::commands::watcher::record_own_write;
+use crate::error::{GhostError, NoteConflict};
 use crate::AppState;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::State;
 use walkdir::WalkDir;
+use zeroize::Zeroizing;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FolderInfo {
     pub name: String,
     pub path: String,
     pub children: Vec<FolderInfo>,
+    /// Notes directly in this folder, when counts were requested
+    pub note_count: Option<usize>,
+    /// Notes in this folder and every folder below it, when counts were requested
+    pub total_count: Option<usize>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NoteMeta {
     pub id: String,
     pub path: String,
     pub title: String,
     pub preview: String,
     pub modified: String,
+    /// Modification time in milliseconds since the epoch, if the file has one
+    pub modified_ms: Option<u64>,
+    pub created: String,
+    /// Creation time in milliseconds since the epoch, if it could be told
+    pub created_ms: Option<u64>,
     pub word_count: usize,
+    /// Characters of prose, not counting Markdown syntax
+    pub char_count: Option<usize>,
+    /// Tags from the note's frontmatter
+    pub tags: Vec<String>,
+    /// Whether the frontmatter pins the note
+    pub pinned: bool,
+    /// Set when the note couldn't be read, e.g. a bad key or truncated file
+    pub corrupted: bool,
+    /// Why the note couldn't be read
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NoteContent {
     pub path: String,
     pub content: String,
+    /// BLAKE3 of `content`, to pass back as `save_note`'s `expected_hash`
+    pub content_hash: String,
+    /// Bytes that weren't valid UTF-8 were replaced to show the note
+    pub had_invalid_utf8: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub level: usize,
+    pub text: String,
+    pub line_number: usize,
+}
+
+/// Everything the editor needs to open a note, from a single decryption
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenedNote {
+    pub path: String,
+    pub content: String,
+    /// BLAKE3 of `content`, to pass back as `save_note`'s `expected_hash`
+    pub content_hash: String,
+    pub title: String,
+    pub word_count: usize,
+    /// Tags from the note's frontmatter
+    pub tags: Vec<String>,
+    pub outline: Vec<OutlineEntry>,
+    pub created: String,
+    pub modified: String,
+    pub encrypted: bool,
+    pub readonly: bool,
+    /// Bytes that weren't valid UTF-8 were replaced to show the note
+    pub had_invalid_utf8: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AnomalyKind {
+    InFuture,
+    BeforeEpoch,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimestampAnomaly {
+    pub path: String,
+    pub modified_unix: i64,
+    pub kind: AnomalyKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityDay {
+    pub date: String,
+    pub created: usize,
+    pub modified: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteReference {
+    pub id: String,
+    pub uri: String,
+}
+
+/// Whether a note's content is compressed before it's encrypted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteCompression {
+    /// Compress when a sample of the content shrinks enough
+    #[default]
+    Auto,
+    /// Never compress, for content known to be compressed already
+    Off,
+}
+
+/// Per-note flags stored in a plaintext `.meta` sidecar next to the note
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteSidecar {
+    pub readonly: bool,
+    /// Keep the file name when the note is retitled
+    pub lock_filename: bool,
+    pub compression: NoteCompression,
+    /// Id handed out by `get_note_reference`, kept so later saves and moves
+    /// can't change it
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum DerivedStateKind {
+    CurrentFolder,
+    ContentHash,
+    Sidecar,
+    /// An entry in the recently opened notes list
+    RecentNote,
+    /// A note in the search index
+    IndexedNote,
+    /// A note in the cached link graph
+    LinkedNote,
+}
+
+/// A stored reference to a note or folder that no longer exists
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DanglingReference {
+    pub kind: DerivedStateKind,
+    pub reference: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum IntegrityStatus {
+    Ok,
+    MissingKey,
+    UnwrapFailed,
+    DecryptFailed,
+    InvalidUtf8,
+}
+
+/// Result of checking one `.enc` file with `verify_vault_integrity`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntegrityEntry {
+    /// Relative to the notes folder, without the extension
+    pub path: String,
+    pub status: IntegrityStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Every encrypted note, trashed note, version and attachment
+    pub files: Vec<IntegrityEntry>,
+    /// `.key` files with no `.enc` beside them
+    pub orphan_keys: Vec<String>,
+}
+
+/// A deleted note waiting in the trash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub original_path: String,
+    pub deleted_at: String,
+}
+
+/// An earlier version of a note kept in its history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteVersion {
+    pub id: String,
+    pub saved_at: String,
+}
+
+/// Format for `export_note`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// The decrypted Markdown as is
+    Markdown,
+    /// A standalone HTML page
+    Html,
+    Pdf,
+}
+
+/// A legacy note that couldn't be encrypted
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Outcome of encrypting legacy plaintext notes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub migrated: usize,
+    pub failed: Vec<MigrationFailure>,
+}
+
+/// Sidecar stored next to a trashed note (`.trash/<id>.json`)
+#[derive(Debug, Serialize, Deserialize)]
+struct TrashRecord {
+    original_path: String,
+    deleted_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,16 +242,219 @@ pub struct SearchResult {
     pub path: String,
     pub title: String,
     pub matches: Vec<SearchMatch>,
+    /// Set when the note couldn't be read, so it couldn't be searched
+    pub corrupted: bool,
+    /// Why the note couldn't be read
+    pub error: Option<String>,
+    /// Times the query matched, plus a boost if it matched the title and
+    /// that was asked for; higher ranks first
+    pub score: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchMatch {
     pub line_number: usize,
     pub line_content: String,
+    /// Up to `context_lines` lines before and after the match
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+    /// Char (not byte) range of the match within `line_content`
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+/// How `search_notes` matches the query against each line
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SearchMode {
+    /// The query appears anywhere in the line
+    #[default]
+    Substring,
+    /// The query appears as a whole word
+    WholeWord,
+    /// The query is a regular expression
+    Regex,
+}
+
+/// Compile a search query into the pattern lines are matched with
+pub(crate) fn build_search_pattern(
+    query: &str,
+    mode: SearchMode,
+    case_sensitive: bool,
+) -> Result<Regex, GhostError> {
+    let pattern = match mode {
+        SearchMode::Substring => regex::escape(query),
+        SearchMode::WholeWord => format!(r"\b{}\b", regex::escape(query)),
+        SearchMode::Regex => query.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| GhostError::Invalid(format!("Invalid search pattern: {}", e)))
+}
+
+/// Count every occurrence of `pattern`, line by line as `find_matches` scans
+pub(crate) fn count_matches(content: &str, pattern: &Regex) -> usize {
+    content
+        .lines()
+        .map(|line| pattern.find_iter(line.trim()).count())
+        .sum()
+}
+
+/// Every line of `content` matching `pattern`, with `context_lines` of
+/// surrounding text
+pub(crate) fn find_matches(
+    content: &str,
+    pattern: &Regex,
+    context_lines: usize,
+) -> Vec<SearchMatch> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut matches = Vec::new();
+
+    for (line_num, line) in lines.iter().enumerate() {
+        let line_content = line.trim();
+        if let Some(found) = pattern.find(line_content) {
+            // Offsets are in chars so non-ASCII lines highlight correctly
+            let match_start = line_content[..found.start()].chars().count();
+            let match_end = match_start + found.as_str().chars().count();
+            let before = line_num.saturating_sub(context_lines);
+            let after = (line_num + 1 + context_lines).min(lines.len());
+            matches.push(SearchMatch {
+                line_number: line_num + 1,
+                line_content: line_content.to_string(),
+                context_before: lines[before..line_num]
+                    .iter()
+                    .map(|l| l.trim().to_string())
+                    .collect(),
+                context_after: lines[line_num + 1..after]
+                    .iter()
+                    .map(|l| l.trim().to_string())
+                    .collect(),
+                match_start,
+                match_end,
+            });
+        }
+    }
+
+    matches
+}
+
+/// The known keys of a note's YAML frontmatter
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Frontmatter {
+    pub title: Option<String>,
+    #[serde(deserialize_with = "deserialize_tags")]
+    pub tags: Vec<String>,
+    pub pinned: bool,
+}
+
+/// Accept `tags` as either a YAML list or a comma-separated string
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        List(Vec<String>),
+        Text(String),
+    }
+
+    let tags = match Option::<Tags>::deserialize(deserializer)? {
+        Some(Tags::List(tags)) => tags,
+        Some(Tags::Text(text)) => text.split(',').map(str::to_string).collect(),
+        None => Vec::new(),
+    };
+    Ok(tags
+        .into_iter()
+        .map(|t| t.trim().trim_start_matches('#').to_string())
+        .filter(|t| !t.is_empty())
+        .collect())
+}
+
+/// Split a leading `---` YAML block off a note
+///
+/// Returns the parsed frontmatter and the body that follows it. Content
+/// without a closed block comes back whole. A block that isn't valid YAML
+/// is still split off, with default frontmatter.
+pub(crate) fn parse_frontmatter(content: &str) -> (Frontmatter, &str) {
+    let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (Frontmatter::default(), content);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let delimiter = line.trim_end();
+        if delimiter == "---" || delimiter == "..." {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let frontmatter = if yaml.trim().is_empty() {
+                Frontmatter::default()
+            } else {
+                serde_yaml::from_str(yaml).unwrap_or_default()
+            };
+            return (frontmatter, body);
+        }
+        offset += line.len();
+    }
+
+    (Frontmatter::default(), content)
 }
 
-fn extract_title(content: &str, path: &Path) -> String {
-    for line in content.lines() {
+/// Set or clear `pinned` in a note's frontmatter, keeping its other keys as
+/// written
+///
+/// A block is added when pinning a note without one, and dropped again when
+/// unpinning leaves it empty.
+fn set_frontmatter_pinned(content: &str, pinned: bool) -> String {
+    let bom = if content.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+    let (_, body) = parse_frontmatter(content);
+    let block = content
+        .get(bom.len()..content.len() - body.len())
+        .unwrap_or_default();
+
+    if block.is_empty() {
+        return if pinned {
+            format!("{}---\npinned: true\n---\n{}", bom, &content[bom.len()..])
+        } else {
+            content.to_string()
+        };
+    }
+
+    let newline = if block.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<&str> = block.lines().collect();
+    let closer = lines.pop().unwrap_or("---");
+    lines.remove(0);
+    lines.retain(|line| !line.starts_with("pinned:"));
+    if pinned {
+        lines.push("pinned: true");
+    }
+
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return format!("{}{}", bom, body);
+    }
+    let mut updated = format!("{}---{}", bom, newline);
+    for line in lines {
+        updated.push_str(line);
+        updated.push_str(newline);
+    }
+    updated.push_str(closer);
+    updated.push_str(newline);
+    updated.push_str(body);
+    updated
+}
+
+pub(crate) fn extract_title(content: &str, path: &Path) -> String {
+    let (frontmatter, body) = parse_frontmatter(content);
+    if let Some(title) = frontmatter.title.filter(|t| !t.trim().is_empty()) {
+        return title.trim().to_string();
+    }
+
+    for line in body.lines() {
         let trimmed = line.trim();
         if trimmed.starts_with("# ") {
             return trimmed[2..].to_string();
@@ -55,7 +466,8 @@ fn extract_title(content: &str, path: &Path) -> String {
 }
 
 fn extract_preview(content: &str) -> String {
-    let text: String = content
+    let (_, body) = parse_frontmatter(content);
+    let text: String = body
         .lines()
         .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
         .take(2)
@@ -69,13 +481,109 @@ fn extract_preview(content: &str) -> String {
     }
 }
 
-fn format_date(time: std::time::SystemTime) -> String {
+/// The prose on a line, with block markers (headings, bullets, quotes) and
+/// link targets removed. Code fence lines have no prose.
+fn prose_line(line: &str) -> String {
+    let mut text = line.trim_start();
+    if text.starts_with("```") || text.starts_with("~~~") {
+        return String::new();
+    }
+
+    // Block markers can nest, e.g. "> - [ ] item"
+    loop {
+        let hashes = text.len() - text.trim_start_matches('#').len();
+        let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let marker = if hashes > 0 && hashes <= 6 && text[hashes..].starts_with(' ') {
+            hashes
+        } else if text.starts_with("> ")
+            || text.starts_with("- ")
+            || text.starts_with("* ")
+            || text.starts_with("+ ")
+        {
+            1
+        } else if digits > 0
+            && (text[digits..].starts_with(". ") || text[digits..].starts_with(") "))
+        {
+            digits + 1
+        } else if text.starts_with("[ ] ") || text.starts_with("[x] ") || text.starts_with("[X] ") {
+            3
+        } else {
+            break;
+        };
+        text = text[marker..].trim_start();
+    }
+
+    // Keep link and image text, drop the "(url)" part
+    let mut prose = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find("](") {
+        prose.push_str(&rest[..pos]);
+        rest = &rest[pos + 2..];
+        match rest.find(')') {
+            Some(close) => rest = &rest[close + 1..],
+            None => rest = "",
+        }
+    }
+    prose.push_str(rest);
+    prose.replace("![", "[").replace(['[', ']'], "")
+}
+
+/// Words a writer would count: Markdown syntax and tokens with no letters or
+/// digits in them (stray `*`, `---`, table pipes) are ignored
+pub(crate) fn count_words(content: &str) -> usize {
+    content
+        .lines()
+        .map(|line| {
+            prose_line(line)
+                .split_whitespace()
+                .filter(|word| word.chars().any(char::is_alphanumeric))
+                .count()
+        })
+        .sum()
+}
+
+/// Characters of prose, counted on the same text as `count_words`
+pub(crate) fn count_chars(content: &str) -> usize {
+    content
+        .lines()
+        .map(|line| prose_line(line).trim().chars().count())
+        .sum()
+}
+
+fn extract_outline(content: &str) -> Vec<OutlineEntry> {
+    let mut outline = Vec::new();
+    let mut in_code_block = false;
+
+    for (line_num, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            outline.push(OutlineEntry {
+                level,
+                text: trimmed[level..].trim().to_string(),
+                line_number: line_num + 1,
+            });
+        }
+    }
+
+    outline
+}
+
+pub(crate) fn format_date(time: std::time::SystemTime) -> String {
     use chrono::{DateTime, Local};
     let datetime: DateTime<Local> = time.into();
     datetime.format("%b %d").to_string()
 }
 
-fn slugify(text: &str) -> String {
+pub(crate) fn slugify(text: &str) -> String {
     text.to_lowercase()
         .chars()
         .map(|c| if c.is_alphanumeric() { c } else { '-' })
@@ -84,421 +592,5561 @@ fn slugify(text: &str) -> String {
         .to_string()
 }
 
-/// Get the encrypted file path (.enc) from a base path
-fn enc_path(path: &Path) -> PathBuf {
-    path.with_extension("enc")
+/// The `YYYY-MM-DD-` prefix of a note's file stem, if it has one, along with
+/// an `HHMMSS-` time of day after it
+fn date_prefix(stem: &str) -> Option<&str> {
+    let date = stem
+        .get(..11)
+        .filter(|p| p.ends_with('-'))
+        .filter(|p| chrono::NaiveDate::parse_from_str(&p[..10], "%Y-%m-%d").is_ok())?;
+    let with_time = stem
+        .get(..18)
+        .filter(|p| p.ends_with('-'))
+        .filter(|p| chrono::NaiveTime::parse_from_str(&p[11..17], "%H%M%S").is_ok());
+    Some(with_time.unwrap_or(date))
 }
 
-/// Get the key file path (.key) from a base path
-fn key_path(path: &Path) -> PathBuf {
-    path.with_extension("key")
+/// How `create_note` names a new note's file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilenameScheme {
+    /// `2024-05-01-my-title`
+    #[default]
+    DatePrefix,
+    /// `my-title`
+    SlugOnly,
+    /// `2024-05-01-143005-my-title`, down to the second
+    Timestamp,
+    /// A ULID such as `01HWZ3K7Q8M2YF9R4T6V0XBNCE`, sortable by creation
+    /// time and unique without looking at the folder
+    Ulid,
 }
 
-/// Check if a note is encrypted (has .enc file)
-fn is_encrypted(notes_dir: &Path, rel_path: &str) -> bool {
-    let base_path = notes_dir.join(rel_path);
-    enc_path(&base_path).exists()
+impl FilenameScheme {
+    /// File stem for a note whose title slugs to `slug`, created `now`
+    ///
+    /// Collisions are still left to `unique_note_path`.
+    fn base_name(self, slug: &str, now: chrono::DateTime<chrono::Local>) -> String {
+        match self {
+            FilenameScheme::DatePrefix => format!("{}-{}", now.format("%Y-%m-%d"), slug),
+            FilenameScheme::SlugOnly => slug.to_string(),
+            FilenameScheme::Timestamp => format!("{}-{}", now.format("%Y-%m-%d-%H%M%S"), slug),
+            FilenameScheme::Ulid => ulid::Ulid::from_datetime(now.into()).to_string(),
+        }
+    }
 }
 
-/// Read and decrypt a note's content
-fn read_encrypted_note(
-    notes_dir: &Path,
-    rel_path: &str,
-    vault: &VaultState,
-) -> Result<String, String> {
-    let base_path = notes_dir.join(rel_path);
-    let enc_file = enc_path(&base_path);
-    let key_file = key_path(&base_path);
-
-    // Read wrapped DEK
-    let wrapped_dek = fs::read(&key_file)
-        .map_err(|e| format!("Failed to read key file: {}", e))?;
+/// Canonicalize a vault-relative path coming from the frontend
+///
+/// Collapses `.` segments, repeated and trailing separators so the same note
+/// always gets the same id, and rejects `..` or absolute paths outright.
+pub(crate) fn normalize_note_path(path: &str) -> Result<String, GhostError> {
+    use std::path::Component;
 
-    // Unwrap DEK with KEK
-    let dek = vault.with_kek(|kek| unwrap_dek(kek, &wrapped_dek))?;
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(GhostError::Invalid(format!(
+                    "Path '{}' must not contain '..'",
+                    path
+                )));
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(GhostError::Invalid(format!(
+                    "Path '{}' must be relative to the notes folder",
+                    path
+                )));
+            }
+        }
+    }
 
-    // Read and decrypt content
-    let encrypted_content = fs::read(&enc_file)
-        .map_err(|e| format!("Failed to read encrypted file: {}", e))?;
+    Ok(normalized.to_string_lossy().to_string())
+}
 
-    let decrypted = decrypt(dek.as_bytes(), &encrypted_content)?;
+/// Full path of a vault-relative path from the frontend, refusing anything
+/// that would land outside the notes folder
+///
+/// On top of `normalize_note_path`, symlinks along the part of the path that
+/// exists are resolved, so a link inside the vault can't lead out of it.
+pub(crate) fn resolve_safe(notes_dir: &Path, rel_path: &str) -> Result<PathBuf, GhostError> {
+    let rel_path = normalize_note_path(rel_path)?;
+    let full_path = notes_dir.join(&rel_path);
+    let Ok(base) = notes_dir.canonicalize() else {
+        // Nothing exists yet for a link to redirect
+        return Ok(full_path);
+    };
 
-    String::from_utf8(decrypted)
-        .map_err(|e| format!("Invalid UTF-8 in decrypted content: {}", e))
+    // The deepest existing ancestor, plus the note files the path may name
+    let deepest = full_path
+        .ancestors()
+        .find(|p| p.symlink_metadata().is_ok())
+        .map(Path::to_path_buf);
+    let note_files = [enc_path(&full_path), key_path(&full_path)];
+    let escapes = deepest
+        .iter()
+        .chain(note_files.iter().filter(|p| p.symlink_metadata().is_ok()))
+        .any(|p| {
+            !p.canonicalize()
+                .is_ok_and(|resolved| resolved.starts_with(&base))
+        });
+    if escapes {
+        return Err(GhostError::Invalid(format!(
+            "Path '{}' leads outside the notes folder",
+            rel_path
+        )));
+    }
+    Ok(full_path)
 }
 
-/// Encrypt and save a note's content
-fn write_encrypted_note(
-    notes_dir: &Path,
-    rel_path: &str,
-    content: &str,
-    vault: &VaultState,
-    existing_dek: Option<Dek>,
-) -> Result<(), String> {
-    let base_path = notes_dir.join(rel_path);
-    let enc_file = enc_path(&base_path);
-    let key_file = key_path(&base_path);
-
-    // Ensure parent directory exists
-    if let Some(parent) = base_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+/// Whether a file written at `dest` could end up inside the notes folder
+///
+/// The part of `dest` that exists is resolved, so a symlink (or a dangling
+/// one) can't hide the notes folder behind an outside path. Paths with `..`
+/// in them count as inside, since a folder created along the way could
+/// redirect them.
+pub(crate) fn is_inside_notes_dir(notes_dir: &Path, dest: &Path) -> bool {
+    if dest.starts_with(notes_dir)
+        || dest
+            .components()
+            .any(|c| c == std::path::Component::ParentDir)
+    {
+        return true;
     }
+    let base = notes_dir
+        .canonicalize()
+        .unwrap_or_else(|_| notes_dir.to_path_buf());
+    let Some(existing) = dest.ancestors().find(|p| p.symlink_metadata().is_ok()) else {
+        return false;
+    };
+    let rest = dest.strip_prefix(existing).unwrap_or(Path::new(""));
+    existing
+        .canonicalize()
+        .map_or(true, |resolved| resolved.join(rest).starts_with(&base))
+}
 
-    // Use existing DEK or generate new one
-    let dek = existing_dek.unwrap_or_else(Dek::generate);
+/// Create a file no one else can read, failing if anything is already at
+/// `path` so a planted file or symlink is never written through
+fn create_private_file(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
 
-    // Encrypt content with DEK
-    let encrypted_content = encrypt(dek.as_bytes(), content.as_bytes())?;
+/// Normalize a note or folder id from the frontend, checking with
+/// `resolve_safe` that it stays inside the notes folder
+pub(crate) fn checked_note_path(notes_dir: &Path, path: &str) -> Result<String, GhostError> {
+    let path = normalize_note_path(path)?;
+    resolve_safe(notes_dir, &path)?;
+    Ok(path)
+}
 
-    // Wrap DEK with KEK
-    let wrapped_dek = vault.with_kek(|kek| wrap_dek(kek, &dek))?;
+/// Get the encrypted file path (.enc) from a base path
+pub(crate) fn enc_path(path: &Path) -> PathBuf {
+    with_note_extension(path, "enc")
+}
 
-    // Write both files
-    fs::write(&enc_file, &encrypted_content)
-        .map_err(|e| format!("Failed to write encrypted file: {}", e))?;
-    fs::write(&key_file, &wrapped_dek)
-        .map_err(|e| format!("Failed to write key file: {}", e))?;
+/// Get the key file path (.key) from a base path
+pub(crate) fn key_path(path: &Path) -> PathBuf {
+    with_note_extension(path, "key")
+}
 
-    Ok(())
+/// Add `ext` to a base path, replacing only a legacy note's own `.md`/`.txt`
+/// so a dotted id like `v1.2-notes` keeps its dot
+fn with_note_extension(path: &Path, ext: &str) -> PathBuf {
+    if matches!(path.extension().and_then(|e| e.to_str()), Some("md" | "txt")) {
+        return path.with_extension(ext);
+    }
+    let mut file = path.as_os_str().to_owned();
+    file.push(".");
+    file.push(ext);
+    PathBuf::from(file)
 }
 
-/// Delete encrypted note files
-fn delete_encrypted_note(notes_dir: &Path, rel_path: &str) -> Result<(), String> {
-    let base_path = notes_dir.join(rel_path);
-    let enc_file = enc_path(&base_path);
-    let key_file = key_path(&base_path);
+/// Earliest modification time we consider plausible (2000-01-01 UTC)
+const SANE_EPOCH_UNIX: i64 = 946_684_800;
 
-    // Remove both files (ignore errors if they don't exist)
-    let _ = fs::remove_file(&enc_file);
-    let _ = fs::remove_file(&key_file);
+/// Allowed drift into the future before a timestamp is flagged
+const FUTURE_TOLERANCE_SECS: i64 = 300;
 
-    Ok(())
+/// Convert a SystemTime to signed unix seconds (negative before 1970)
+fn to_unix(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
 }
 
-#[tauri::command]
-pub fn list_folders(state: State<AppState>) -> Result<Vec<FolderInfo>, String> {
-    let notes_dir = state.notes_dir.lock().unwrap().clone();
+/// Creation day of a note, from its `YYYY-MM-DD-` filename prefix
+///
+/// Falls back to the filesystem creation time for notes that don't follow
+/// the naming convention.
+pub(crate) fn note_created_date(file_path: &Path) -> Option<chrono::NaiveDate> {
+    let metadata = fs::metadata(file_path).ok();
+    let created = note_created_time(file_path, metadata.as_ref())?;
+    Some(chrono::DateTime::<chrono::Local>::from(created).date_naive())
+}
 
-    fn build_tree(dir: &Path, base: &Path) -> Vec<FolderInfo> {
-        let mut folders = Vec::new();
+/// When a note was created
+///
+/// The `YYYY-MM-DD-` filename prefix gives the day, which survives copying
+/// the vault between machines. The filesystem creation time gives the time
+/// of day when it falls on that same day, and stands in entirely for notes
+/// named otherwise (or the modification time, where it isn't recorded).
+fn note_created_time(file_path: &Path, metadata: Option<&fs::Metadata>) -> Option<SystemTime> {
+    use chrono::{DateTime, Local, NaiveDate, TimeZone};
 
-        if let Ok(entries) = fs::read_dir(dir) {
-            for entry in entries.filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_dir() && !path.file_name().unwrap_or_default().to_string_lossy().starts_with('.') {
-                    let rel_path = path.strip_prefix(base).unwrap_or(&path);
-                    folders.push(FolderInfo {
-                        name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
-                        path: rel_path.to_string_lossy().to_string(),
-                        children: build_tree(&path, base),
-                    });
-                }
-            }
-        }
+    let fs_created = metadata.and_then(|m| m.created().ok());
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    let Some(day) = stem
+        .get(..10)
+        .and_then(|p| NaiveDate::parse_from_str(p, "%Y-%m-%d").ok())
+    else {
+        return fs_created.or_else(|| metadata?.modified().ok());
+    };
 
-        folders.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        folders
+    if let Some(time) = fs_created.filter(|t| DateTime::<Local>::from(*t).date_naive() == day) {
+        return Some(time);
     }
-
-    Ok(build_tree(&notes_dir, &notes_dir))
+    Local
+        .from_local_datetime(&day.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(SystemTime::from)
 }
 
-#[tauri::command]
-pub fn list_notes(
-    folder: String,
-    state: State<AppState>,
-    vault: State<VaultState>,
-) -> Result<Vec<NoteMeta>, String> {
-    let notes_dir = state.notes_dir.lock().unwrap().clone();
-    let folder_path = notes_dir.join(&folder);
+/// Pick a base path in `folder_path` that no existing note uses
+///
+/// Duplicates get a numeric suffix: `<base>-1`, `<base>-2`, ...
+pub(crate) fn unique_note_path(folder_path: &Path, base_name: &str) -> PathBuf {
+    let mut base_path = folder_path.join(base_name);
 
-    if !folder_path.exists() {
-        return Ok(Vec::new());
+    // Handle duplicates (check for .enc file)
+    let mut counter = 1;
+    while enc_path(&base_path).exists() {
+        base_path = folder_path.join(format!("{}-{}", base_name, counter));
+        counter += 1;
     }
 
-    let mut notes = Vec::new();
+    base_path
+}
 
-    if let Ok(entries) = fs::read_dir(&folder_path) {
-        for entry in entries.filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-
-                // Handle encrypted files (.enc)
-                if ext == "enc" {
-                    // Get the base path (without .enc extension)
-                    let base_path = path.with_extension("");
-                    let rel_base = base_path
-                        .strip_prefix(&notes_dir)
-                        .unwrap_or(&base_path)
-                        .to_string_lossy()
-                        .to_string();
-
-                    // Try to decrypt and read content
-                    if let Ok(content) = read_encrypted_note(&notes_dir, &rel_base, &vault) {
-                        let metadata = fs::metadata(&path).ok();
-                        let modified = metadata
-                            .and_then(|m| m.modified().ok())
-                            .map(format_date)
-                            .unwrap_or_else(|| "Unknown".to_string());
-
-                        notes.push(NoteMeta {
-                            id: rel_base.clone(),
-                            path: rel_base,
-                            title: extract_title(&content, &path),
-                            preview: extract_preview(&content),
-                            modified,
-                            word_count: content.split_whitespace().count(),
-                        });
-                    }
-                }
-                // Also handle legacy unencrypted files (.md, .txt)
-                else if ext == "md" || ext == "txt" {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        let metadata = fs::metadata(&path).ok();
-                        let modified = metadata
-                            .and_then(|m| m.modified().ok())
-                            .map(format_date)
-                            .unwrap_or_else(|| "Unknown".to_string());
-
-                        let rel_path = path.strip_prefix(&notes_dir).unwrap_or(&path);
-
-                        notes.push(NoteMeta {
-                            id: rel_path.to_string_lossy().to_string(),
-                            path: rel_path.to_string_lossy().to_string(),
-                            title: extract_title(&content, &path),
-                            preview: extract_preview(&content),
-                            modified,
-                            word_count: content.split_whitespace().count(),
-                        });
-                    }
-                }
-            }
-        }
-    }
+/// Get the sidecar file path (.meta) from a base path
+fn meta_path(path: &Path) -> PathBuf {
+    with_note_extension(path, "meta")
+}
 
-    // Sort by modified date (newest first)
-    notes.sort_by(|a, b| b.modified.cmp(&a.modified));
+/// Load a note's sidecar, defaulting when it has none
+fn read_sidecar(notes_dir: &Path, rel_path: &str) -> NoteSidecar {
+    read_sidecar_at(&notes_dir.join(rel_path))
+}
 
-    Ok(notes)
+/// Load the sidecar of the note files at `base_path`
+fn read_sidecar_at(base_path: &Path) -> NoteSidecar {
+    fs::read(meta_path(base_path))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
 }
 
-#[tauri::command]
-pub fn read_note(
-    path: String,
-    state: State<AppState>,
-    vault: State<VaultState>,
-) -> Result<NoteContent, String> {
-    let notes_dir = state.notes_dir.lock().unwrap().clone();
+fn write_sidecar(
+    notes_dir: &Path,
+    rel_path: &str,
+    sidecar: &NoteSidecar,
+) -> Result<(), GhostError> {
+    let json = serde_json::to_vec_pretty(sidecar)?;
+    fs::write(meta_path(&notes_dir.join(rel_path)), json)
+        .map_err(|e| GhostError::Io(format!("Failed to write note metadata: {}", e)))
+}
 
-    // Check if encrypted version exists
-    if is_encrypted(&notes_dir, &path) {
-        let content = read_encrypted_note(&notes_dir, &path, &vault)?;
-        Ok(NoteContent { path, content })
-    } else {
-        // Fall back to legacy unencrypted read
-        let full_path = notes_dir.join(&path);
-        let content = fs::read_to_string(&full_path).map_err(|e| e.to_string())?;
-        Ok(NoteContent { path, content })
+/// Refuse to modify a note the user marked read-only
+pub(crate) fn ensure_writable(notes_dir: &Path, rel_path: &str) -> Result<(), GhostError> {
+    if read_sidecar(notes_dir, rel_path).readonly {
+        return Err(GhostError::NoteReadOnly(rel_path.to_string()));
     }
+    Ok(())
 }
 
-#[tauri::command]
-pub fn save_note(
-    path: String,
-    content: String,
-    state: State<AppState>,
-    vault: State<VaultState>,
-) -> Result<(), String> {
-    let notes_dir = state.notes_dir.lock().unwrap().clone();
+/// Folder under the notes folder holding deleted notes
+pub(crate) const TRASH_DIR: &str = ".trash";
 
-    // Always save as encrypted
-    write_encrypted_note(&notes_dir, &path, &content, &vault, None)
-}
+/// Folder under the notes folder holding earlier versions of notes,
+/// as `.history/<note id>/<version>.enc` plus its `.key`
+pub(crate) const HISTORY_DIR: &str = ".history";
 
-#[tauri::command]
-pub fn create_note(
-    folder: String,
-    title: Option<String>,
-    state: State<AppState>,
-    vault: State<VaultState>,
-) -> Result<String, String> {
-    let notes_dir = state.notes_dir.lock().unwrap().clone();
-    let folder_path = notes_dir.join(&folder);
+/// Folder under the notes folder holding encrypted attachments, as
+/// `.attachments/<id>.enc` plus its `.key`
+pub(crate) const ATTACHMENTS_DIR: &str = ".attachments";
 
-    // Ensure folder exists
-    fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+/// Folder under the notes folder holding note templates, which are
+/// encrypted like any other note
+pub(crate) const TEMPLATES_DIR: &str = ".templates";
 
-    let now = chrono::Local::now();
-    let date_str = now.format("%Y-%m-%d").to_string();
-    let slug = title
-        .as_ref()
-        .map(|t| slugify(t))
-        .unwrap_or_else(|| "untitled".to_string());
+/// Folder under the notes folder a vault backup is written out to before it
+/// replaces the vault
+pub(crate) const RESTORE_DIR: &str = ".restore";
 
-    // Use base name without extension (we'll add .enc and .key)
-    let base_name = format!("{}-{}", date_str, slug);
-    let mut base_path = folder_path.join(&base_name);
+/// Folders the app keeps its own data in, which never hold regular notes
+const RESERVED_DIRS: [&str; 6] = [
+    ".vault",
+    TRASH_DIR,
+    HISTORY_DIR,
+    ATTACHMENTS_DIR,
+    TEMPLATES_DIR,
+    RESTORE_DIR,
+];
 
-    // Handle duplicates (check for .enc file)
-    let mut counter = 1;
-    while enc_path(&base_path).exists() {
-        let new_base_name = format!("{}-{}-{}", date_str, slug, counter);
-        base_path = folder_path.join(&new_base_name);
-        counter += 1;
-    }
+/// Whether `path` is in one of the reserved folders at the top of the notes
+/// folder
+///
+/// `path` may be relative to `notes_dir` or a full path under it.
+pub(crate) fn is_reserved_dir(notes_dir: &Path, path: &Path) -> bool {
+    let rel_path = path.strip_prefix(notes_dir).unwrap_or(path);
+    RESERVED_DIRS.iter().any(|dir| rel_path.starts_with(dir))
+}
 
-    // Create with initial content (encrypted)
-    let initial_content = title
+/// File at the top of the notes folder listing, gitignore-style, notes to
+/// leave out of searches and folder listings
+const IGNORE_FILE: &str = ".ghostignore";
+
+/// Patterns from `.ghostignore`, read on every call so edits apply at once
+///
+/// A missing file or bad pattern ignores nothing rather than failing.
+pub(crate) fn load_ignore(notes_dir: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(notes_dir);
+    let _ = builder.add(notes_dir.join(IGNORE_FILE));
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// Whether `.ghostignore` leaves out the note or folder at `path`
+///
+/// Patterns are matched against note ids, i.e. paths relative to the notes
+/// folder without `.enc`, so `archive/` or `*-generated` work as expected.
+/// Reserved folders are skipped before this is asked, so a `!pattern` can't
+/// bring the trash or history into results.
+pub(crate) fn is_ignored(ignore: &Gitignore, notes_dir: &Path, path: &Path, is_dir: bool) -> bool {
+    let base = if !is_dir && path.extension().is_some_and(|ext| ext == "enc") {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    };
+    let rel_path = base.strip_prefix(notes_dir).unwrap_or(&base);
+    !rel_path.as_os_str().is_empty()
+        && ignore
+            .matched_path_or_any_parents(rel_path, is_dir)
+            .is_ignore()
+}
+
+/// Every note file under the notes folder (`.enc` plus legacy `.md`/`.txt`)
+///
+/// Reserved folders (trash, note history, attachments and templates) are
+/// not included.
+pub(crate) fn walk_note_files(notes_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_entry(|e| !is_reserved_dir(notes_dir, e.path()))
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext == "enc" || ext == "md" || ext == "txt")
+                .unwrap_or(false)
+        })
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Note id for a file on disk (encrypted notes are addressed without `.enc`)
+pub(crate) fn rel_note_path(notes_dir: &Path, file_path: &Path) -> String {
+    let base_path = if file_path.extension().map(|ext| ext == "enc").unwrap_or(false) {
+        file_path.with_extension("")
+    } else {
+        file_path.to_path_buf()
+    };
+    base_path
+        .strip_prefix(notes_dir)
+        .unwrap_or(&base_path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// File holding a note's content (the `.enc` for encrypted notes)
+pub(crate) fn note_file_path(notes_dir: &Path, rel_path: &str) -> PathBuf {
+    if is_encrypted(notes_dir, rel_path) {
+        enc_path(&notes_dir.join(rel_path))
+    } else {
+        notes_dir.join(rel_path)
+    }
+}
+
+/// Check if a note is encrypted (has .enc file)
+pub(crate) fn is_encrypted(notes_dir: &Path, rel_path: &str) -> bool {
+    let base_path = notes_dir.join(rel_path);
+    enc_path(&base_path).exists()
+}
+
+/// Associated data binding a note's ciphertext to its id
+///
+/// Separators are normalized so the binding survives moving the vault
+/// between platforms.
+fn note_aad(rel_path: &str) -> Vec<u8> {
+    // A legacy note saved under its `.md`/`.txt` id is encrypted next to it
+    // without the extension, and listed by that id afterwards
+    let id = rel_path
+        .strip_suffix(".md")
+        .or_else(|| rel_path.strip_suffix(".txt"))
+        .unwrap_or(rel_path);
+    id.replace('\\', "/").into_bytes()
+}
+
+/// Decrypt the note files at `base_path`, bound to the note id `aad_path`
+///
+/// Also returns the note's DEK. Notes written before path binding carry no
+/// associated data and are still accepted. The plaintext is wiped when the
+/// returned buffer is dropped. Both files are read under the note's lock, so
+/// a concurrent `encrypt_note_files` can't hand us a mismatched pair.
+pub(crate) fn decrypt_note_bytes(
+    base_path: &Path,
+    aad_path: &str,
+    vault: &VaultState,
+) -> Result<(Dek, Zeroizing<Vec<u8>>), GhostError> {
+    vault.with_note_lock(base_path, false, || {
+        // Read wrapped DEK
+        let wrapped_dek = fs::read(key_path(base_path))
+            .map_err(|e| GhostError::Io(format!("Failed to read key file: {}", e)))?;
+
+        // Unwrap DEK with KEK
+        let dek = vault.with_kek(|kek| unwrap_dek(kek, &wrapped_dek))?;
+
+        // Read and decrypt content
+        let decrypted = decrypt_bound_file(&dek, &enc_path(base_path), aad_path)?;
+        Ok((dek, decrypted))
+    })
+}
+
+/// Decrypt a `.enc` file bound to the note id `aad_path`, falling back to
+/// no associated data for files written before path binding
+fn decrypt_bound_file(
+    dek: &Dek,
+    enc_file: &Path,
+    aad_path: &str,
+) -> Result<Zeroizing<Vec<u8>>, GhostError> {
+    match decrypt_file(dek.as_bytes(), enc_file, &note_aad(aad_path)) {
+        Err(GhostError::DecryptionFailed(_)) => decrypt_file(dek.as_bytes(), enc_file, &[]),
+        result => result,
+    }
+    .map_err(|e| match e {
+        GhostError::DecryptionFailed(_) => GhostError::DecryptionFailed(format!(
+            "Failed to decrypt '{}': it may have been moved or tampered with",
+            aad_path
+        )),
+        e => e,
+    })
+}
+
+/// Note id that the encrypted files at `rel_base` are bound to
+///
+/// `rel_base` is relative to the notes folder and has no extension. Versions
+/// in the history are bound to the id of the note they belong to; everything
+/// else to its own path.
+pub(crate) fn bound_note_id(rel_base: &Path) -> String {
+    let id = match rel_base.strip_prefix(HISTORY_DIR) {
+        Ok(version) => version.parent().unwrap_or(version),
+        Err(_) => rel_base,
+    };
+    id.to_string_lossy().replace('\\', "/")
+}
+
+/// Borrow decrypted bytes as text
+fn decrypted_str(bytes: &[u8]) -> Result<&str, GhostError> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        GhostError::DecryptionFailed(format!("Invalid UTF-8 in decrypted content: {}", e))
+    })
+}
+
+/// Decrypt the note files at `base_path` as text, for handing on
+fn decrypt_note_files(
+    base_path: &Path,
+    aad_path: &str,
+    vault: &VaultState,
+) -> Result<(Dek, String), GhostError> {
+    let (dek, decrypted) = decrypt_note_bytes(base_path, aad_path, vault)?;
+    let content = decrypted_str(&decrypted)?.to_string();
+    Ok((dek, content))
+}
+
+/// Read and decrypt a note's content
+fn read_encrypted_note(
+    notes_dir: &Path,
+    rel_path: &str,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    decrypt_note_files(&notes_dir.join(rel_path), rel_path, vault).map(|(_, content)| content)
+}
+
+/// Re-encrypt a note whose files moved from `old_rel` to `new_rel`
+///
+/// Binds the ciphertext to its new id, keeping the DEK.
+fn rebind_note(
+    notes_dir: &Path,
+    old_rel: &str,
+    new_rel: &str,
+    vault: &VaultState,
+) -> Result<(), GhostError> {
+    let (dek, decrypted) = decrypt_note_bytes(&notes_dir.join(new_rel), old_rel, vault)?;
+    let content = decrypted_str(&decrypted)?;
+    write_encrypted_note(notes_dir, new_rel, content, vault, Some(dek))
+}
+
+/// Encrypt and save a note's content
+pub(crate) fn write_encrypted_note(
+    notes_dir: &Path,
+    rel_path: &str,
+    content: &str,
+    vault: &VaultState,
+    existing_dek: Option<Dek>,
+) -> Result<(), GhostError> {
+    encrypt_note_files(
+        &notes_dir.join(rel_path),
+        rel_path,
+        content.as_bytes(),
+        vault,
+        existing_dek,
+    )
+}
+
+/// Encrypt `content` into the note files at `base_path`, bound to the note
+/// id `aad_path`
+///
+/// `content` needn't be text, so attachments are stored the same way. The
+/// note's lock is held exclusively throughout, so saves of the same note
+/// take turns and no read sees one file replaced but not the other. Saves
+/// also wait out a background re-key; see `VaultState::without_rekey`.
+pub(crate) fn encrypt_note_files(
+    base_path: &Path,
+    aad_path: &str,
+    content: &[u8],
+    vault: &VaultState,
+    existing_dek: Option<Dek>,
+) -> Result<(), GhostError> {
+    vault.without_rekey(|| {
+        vault.with_note_lock(base_path, true, || {
+            write_note_files(base_path, aad_path, content, vault, existing_dek)
+        })
+    })
+}
+
+/// Body of `encrypt_note_files`, run under the note's lock
+///
+/// Both files are staged next to their targets first, then the content file
+/// is renamed into place and the key file after it. A crash before the first
+/// rename leaves the previous pair; a crash between them leaves just the
+/// staged key file, which `recover_interrupted_saves` moves into place.
+fn write_note_files(
+    base_path: &Path,
+    aad_path: &str,
+    content: &[u8],
+    vault: &VaultState,
+    existing_dek: Option<Dek>,
+) -> Result<(), GhostError> {
+    stage_note_files(base_path, aad_path, content, vault, existing_dek)?;
+
+    let enc_file = enc_path(base_path);
+    let key_file = key_path(base_path);
+    fs::rename(staged_note_path(&enc_file), &enc_file)
+        .map_err(|e| GhostError::Io(format!("Failed to write note file: {}", e)))?;
+    fs::rename(staged_note_path(&key_file), &key_file)
+        .map_err(|e| GhostError::Io(format!("Failed to write key file: {}", e)))?;
+
+    Ok(())
+}
+
+/// Write a note's content and key files next to their targets, leaving the
+/// live files untouched
+///
+/// The content file is written first, so a staged key file is only ever
+/// left alone once both were complete.
+fn stage_note_files(
+    base_path: &Path,
+    aad_path: &str,
+    content: &[u8],
+    vault: &VaultState,
+    existing_dek: Option<Dek>,
+) -> Result<(), GhostError> {
+    let enc_file = enc_path(base_path);
+    let key_file = key_path(base_path);
+
+    // Ensure parent directory exists
+    if let Some(parent) = base_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| GhostError::Io(format!("Failed to create directory: {}", e)))?;
+    }
+
+    // Use the given DEK, else keep the note's own so the old content file
+    // still opens with the new key file, else generate a new one
+    let dek = match existing_dek {
+        Some(dek) => dek,
+        None => fs::read(&key_file)
+            .ok()
+            .and_then(|wrapped| vault.with_kek(|kek| unwrap_dek(kek, &wrapped)).ok())
+            .unwrap_or_else(Dek::generate),
+    };
+
+    // Wrap DEK with KEK, using the cipher the vault is set to
+    let cipher = vault.config()?.cipher();
+    let wrapped_dek = vault.with_kek(|kek| wrap_dek(kek, &dek, cipher))?;
+
+    let compress = read_sidecar_at(base_path).compression == NoteCompression::Auto
+        && worth_compressing(content);
+
+    let enc_tmp = staged_note_path(&enc_file);
+    let key_tmp = staged_note_path(&key_file);
+    let staged = encrypt_to_file(
+        cipher,
+        dek.as_bytes(),
+        content,
+        &note_aad(aad_path),
+        &enc_tmp,
+        compress,
+    )
+    .and_then(|_| Ok(fs::File::open(&enc_tmp)?.sync_all()?))
+    .and_then(|_| {
+        write_synced(&key_tmp, &wrapped_dek)
+            .map_err(|e| GhostError::Io(format!("Failed to write key file: {}", e)))
+    });
+    if let Err(e) = staged {
+        let _ = fs::remove_file(&key_tmp);
+        let _ = fs::remove_file(&enc_tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Bring notes back to a consistent state after saves a crash interrupted
+///
+/// A staged content file means the save never got as far as its first
+/// rename, so it's discarded along with its key file; a staged key file on
+/// its own belongs to a content file already in place, so it's renamed
+/// after it. Called at startup, before anything else touches the notes.
+pub(crate) fn recover_interrupted_saves(notes_dir: &Path) {
+    let staged: Vec<PathBuf> = WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            name.ends_with(".enc.tmp") || name.ends_with(".key.tmp")
+        })
+        .collect();
+
+    for enc_tmp in staged
+        .iter()
+        .filter(|p| p.to_string_lossy().ends_with(".enc.tmp"))
+    {
+        let base_path = enc_tmp.with_extension("").with_extension("");
+        let _ = fs::remove_file(enc_tmp);
+        let _ = fs::remove_file(staged_note_path(&key_path(&base_path)));
+    }
+    for key_tmp in staged.iter().filter(|p| p.exists()) {
+        let key_file = key_tmp.with_extension("");
+        if let Err(e) = fs::rename(key_tmp, &key_file) {
+            eprintln!(
+                "Warning: Failed to finish saving {}: {}",
+                key_file.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Temporary file a note file is written to before being renamed into place
+fn staged_note_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write a file and flush it to disk
+fn write_synced(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Read a note's content, decrypting if it's encrypted
+pub(crate) fn read_note_content(
+    notes_dir: &Path,
+    rel_path: &str,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    if is_encrypted(notes_dir, rel_path) {
+        read_encrypted_note(notes_dir, rel_path, vault)
+    } else {
+        fs::read_to_string(notes_dir.join(rel_path)).map_err(GhostError::from)
+    }
+}
+
+/// Read a note's content for the editor, optionally recovering what it can
+///
+/// Strictly, invalid UTF-8 fails the read like any other corruption. With
+/// `lossy` it is replaced with U+FFFD instead, and the flag returned says
+/// whether anything was, so a note with one bad byte can still be opened
+/// and saved again. A note whose file hasn't changed since it was last read
+/// comes from the vault's note cache without being decrypted.
+fn read_note_text(
+    notes_dir: &Path,
+    rel_path: &str,
+    vault: &VaultState,
+    lossy: bool,
+) -> Result<(String, bool), GhostError> {
+    // The file as it is now; a cached copy of any other version is stale
+    let stamp = NoteFile::from_path(note_file_path(notes_dir, rel_path))
+        .and_then(|file| Some((file.modified?, file.len)));
+    if let Some((modified, len)) = stamp {
+        if let Some(content) = vault.cached_note(rel_path, modified, len) {
+            return Ok((content.to_string(), false));
+        }
+    }
+
+    let (content, had_invalid_utf8) = if !lossy {
+        (read_note_content(notes_dir, rel_path, vault)?, false)
+    } else {
+        let bytes = if is_encrypted(notes_dir, rel_path) {
+            decrypt_note_bytes(&notes_dir.join(rel_path), rel_path, vault)?.1
+        } else {
+            Zeroizing::new(fs::read(notes_dir.join(rel_path))?)
+        };
+        match std::str::from_utf8(&bytes) {
+            Ok(content) => (content.to_string(), false),
+            Err(_) => (String::from_utf8_lossy(&bytes).into_owned(), true),
+        }
+    };
+
+    // Only content that decoded cleanly is kept, so a hit never hides a
+    // replaced character
+    if let (Some((modified, len)), false) = (stamp, had_invalid_utf8) {
+        vault.cache_note(rel_path, modified, len, &content);
+    }
+    Ok((content, had_invalid_utf8))
+}
+
+/// Hex BLAKE3 of a note's content, as the frontend sees it
+fn content_hash(content: &str) -> String {
+    blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+/// Remember what a note looked like when we last read or wrote it
+fn record_content_hash(state: &AppState, rel_path: &str, content: &str) {
+    state
+        .content_hashes
+        .lock()
+        .unwrap()
+        .insert(rel_path.to_string(), blake3::hash(content.as_bytes()));
+}
+
+/// Render Markdown as plain text, dropping markup but keeping the words
+fn markdown_to_plaintext(content: &str) -> String {
+    use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+    let mut out = String::new();
+    // Next item number per nested list (None for bullet lists)
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::Start(Tag::List(start)) => lists.push(start),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !out.ends_with('\n') => out.push('\n'),
+            Event::End(TagEnd::Paragraph) if lists.is_empty() => out.push_str("\n\n"),
+            Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::CodeBlock) | Event::Rule => {
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    // Collapse runs of blank lines left behind by nested blocks
+    let mut collapsed = String::with_capacity(out.len());
+    for line in out.trim().lines() {
+        let line = line.trim_end();
+        if line.is_empty() && collapsed.ends_with("\n\n") {
+            continue;
+        }
+        collapsed.push_str(line);
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+/// List the folder tree
+///
+/// With `with_counts`, each folder also carries how many notes it holds.
+/// Counting reads every folder's files, so it's left off by default.
+#[tauri::command]
+pub fn list_folders(
+    with_counts: Option<bool>,
+    state: State<AppState>,
+) -> Result<Vec<FolderInfo>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    Ok(build_folder_tree(
+        &notes_dir,
+        &notes_dir,
+        with_counts.unwrap_or(false),
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlatFolder {
+    pub name: String,
+    pub path: String,
+    /// 0 for folders at the top of the notes folder
+    pub depth: usize,
+}
+
+/// List every folder as a flat list, e.g. for a "move to folder" picker
+///
+/// Folders come in the same order as walking `list_folders`' tree: sorted
+/// by path, each followed by its subfolders.
+#[tauri::command]
+pub fn list_folders_flat(state: State<AppState>) -> Result<Vec<FlatFolder>, GhostError> {
+    fn flatten(folders: Vec<FolderInfo>, depth: usize, flat: &mut Vec<FlatFolder>) {
+        for folder in folders {
+            flat.push(FlatFolder {
+                name: folder.name,
+                path: folder.path,
+                depth,
+            });
+            flatten(folder.children, depth + 1, flat);
+        }
+    }
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let mut flat = Vec::new();
+    flatten(
+        build_folder_tree(&notes_dir, &notes_dir, false),
+        0,
+        &mut flat,
+    );
+    Ok(flat)
+}
+
+/// The folders under `dir`, each with its subfolders, sorted by name
+///
+/// Paths are relative to `base`. Notes are counted only with `counts`.
+fn build_folder_tree(dir: &Path, base: &Path, counts: bool) -> Vec<FolderInfo> {
+    let mut folders = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            // Reserved folders, and any other hidden ones, stay out of the tree
+            let hidden = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .starts_with('.');
+            if path.is_dir() && !hidden && !is_reserved_dir(base, &path) {
+                let rel_path = path.strip_prefix(base).unwrap_or(&path);
+                let children = build_folder_tree(&path, base, counts);
+                let (note_count, total_count) = if counts {
+                    let notes = count_folder_notes(&path);
+                    let below: usize = children.iter().filter_map(|c| c.total_count).sum();
+                    (Some(notes), Some(notes + below))
+                } else {
+                    (None, None)
+                };
+                folders.push(FolderInfo {
+                    name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    path: rel_path.to_string_lossy().to_string(),
+                    children,
+                    note_count,
+                    total_count,
+                });
+            }
+        }
+    }
+
+    folders.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    folders
+}
+
+/// Count the encrypted and legacy notes directly inside `folder_path`
+///
+/// Only file names are looked at, so nothing is decrypted. Each encrypted
+/// note counts once, by its `.enc` file.
+fn count_folder_notes(folder_path: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(folder_path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "enc" || ext == "md" || ext == "txt")
+        })
+        .count()
+}
+
+/// A note file found in a folder listing, before its content is read
+struct NoteFile {
+    path: PathBuf,
+    encrypted: bool,
+    /// Size of the file, which with `modified` tells whether it changed
+    len: u64,
+    modified: Option<SystemTime>,
+    created: Option<SystemTime>,
+}
+
+/// Milliseconds since the epoch, clamped to zero for times before it
+fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Sort note files by a timestamp, keeping files without one at the end
+fn sort_by_time(
+    files: &mut [NoteFile],
+    time: impl Fn(&NoteFile) -> Option<SystemTime>,
+    newest_first: bool,
+) {
+    files.sort_by(|a, b| match (time(a), time(b)) {
+        (Some(a), Some(b)) if newest_first => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    });
+}
+
+impl NoteFile {
+    /// Describe a note file on disk, or `None` if it isn't a note
+    fn from_path(path: PathBuf) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let encrypted = ext == "enc";
+        if !encrypted && ext != "md" && ext != "txt" {
+            return None;
+        }
+        let metadata = fs::metadata(&path).ok();
+        let len = metadata.as_ref().map_or(0, |m| m.len());
+        let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+        let created = note_created_time(&path, metadata.as_ref());
+        Some(NoteFile {
+            path,
+            encrypted,
+            len,
+            modified,
+            created,
+        })
+    }
+
+    /// The note's id: its path relative to `notes_dir`, without `.enc`
+    fn note_id(&self, notes_dir: &Path) -> String {
+        let path = if self.encrypted {
+            self.path.with_extension("")
+        } else {
+            self.path.clone()
+        };
+        path.strip_prefix(notes_dir)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// Find the encrypted and legacy note files directly inside `folder_path`
+///
+/// Notes matched by `.ghostignore` are left out.
+fn collect_note_files(notes_dir: &Path, folder_path: &Path) -> Vec<NoteFile> {
+    let Ok(entries) = fs::read_dir(folder_path) else {
+        return Vec::new();
+    };
+    let ignore = load_ignore(notes_dir);
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|entry| !is_ignored(&ignore, notes_dir, &entry.path(), false))
+        .filter_map(|entry| NoteFile::from_path(entry.path()))
+        .collect()
+}
+
+/// Read a listed note and build its metadata
+///
+/// A note that fails to read is still listed, flagged as corrupted, so it
+/// doesn't silently vanish. `None` only while the vault is locked.
+fn load_note_meta(notes_dir: &Path, file: &NoteFile, vault: &VaultState) -> Option<NoteMeta> {
+    let rel = file.note_id(notes_dir);
+    let content = if file.encrypted {
+        read_encrypted_note(notes_dir, &rel, vault)
+    } else {
+        // Legacy unencrypted files (.md, .txt)
+        fs::read_to_string(&file.path).map_err(GhostError::from)
+    };
+    match content {
+        Ok(content) => Some(build_note_meta(rel, file, &Zeroizing::new(content))),
+        Err(GhostError::VaultLocked) => None,
+        Err(e) => Some(corrupted_note_meta(rel, file, &e)),
+    }
+}
+
+/// Context the metadata cache's key is derived from the KEK with
+const META_CACHE_CONTEXT: &str = "ghostnote 2024-10 note metadata cache";
+
+/// A note's metadata as of one version of its file
+#[derive(Serialize, Deserialize)]
+struct CachedMeta {
+    modified_ms: u64,
+    len: u64,
+    meta: NoteMeta,
+}
+
+/// Metadata of listed notes, kept in `.vault/meta.db` so opening a folder
+/// only decrypts the notes whose files changed since they were last listed
+///
+/// Titles and previews are note content, so the cache is encrypted under a
+/// key derived from the KEK. After a re-key it no longer decrypts and is
+/// rebuilt from scratch.
+#[derive(Default)]
+struct MetaCache {
+    entries: HashMap<String, CachedMeta>,
+    changed: bool,
+}
+
+fn meta_cache_key(kek: &Kek) -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(blake3::derive_key(META_CACHE_CONTEXT, kek.as_bytes()))
+}
+
+impl MetaCache {
+    /// Read the cache, starting empty if it's missing or can't be decrypted
+    fn load(config: &VaultConfig, vault: &VaultState) -> Self {
+        let entries = fs::read(&config.meta_cache_path)
+            .ok()
+            .and_then(|data| {
+                vault
+                    .with_kek(|kek| decrypt(&meta_cache_key(kek), &data))
+                    .ok()
+            })
+            .and_then(|json| serde_json::from_slice(&Zeroizing::new(json)).ok())
+            .unwrap_or_default();
+        MetaCache {
+            entries,
+            changed: false,
+        }
+    }
+
+    /// Write the cache back, dropping notes that no longer exist
+    fn save(&mut self, config: &VaultConfig, vault: &VaultState) -> Result<(), GhostError> {
+        self.entries
+            .retain(|id, _| note_exists_in(&config.notes_dir, id));
+        let json = Zeroizing::new(serde_json::to_vec(&self.entries)?);
+        let data =
+            vault.with_kek(|kek| encrypt_with(config.cipher(), &meta_cache_key(kek), &json))?;
+
+        let staged = staged_note_path(&config.meta_cache_path);
+        write_synced(&staged, &data)?;
+        fs::rename(&staged, &config.meta_cache_path)?;
+        Ok(())
+    }
+
+    /// A listed note's cached metadata, if its file hasn't changed since
+    fn fresh(&self, notes_dir: &Path, file: &NoteFile) -> Option<&NoteMeta> {
+        let cached = self.entries.get(&file.note_id(notes_dir))?;
+        (Some(cached.modified_ms) == file.modified.map(epoch_millis) && cached.len == file.len)
+            .then_some(&cached.meta)
+    }
+
+    /// Remember a note's freshly read metadata
+    ///
+    /// Notes that failed to read aren't cached, so they're tried again on the
+    /// next listing.
+    fn store(&mut self, notes_dir: &Path, file: &NoteFile, meta: &NoteMeta) {
+        if let (Some(modified_ms), false) = (file.modified.map(epoch_millis), meta.corrupted) {
+            self.entries.insert(
+                file.note_id(notes_dir),
+                CachedMeta {
+                    modified_ms,
+                    len: file.len,
+                    meta: meta.clone(),
+                },
+            );
+            self.changed = true;
+        }
+    }
+}
+
+/// Metadata for each of `files`, through the metadata cache
+///
+/// Notes the cache can't answer for are decrypted in parallel. Saving the
+/// cache is best effort: a listing never fails because of it.
+fn load_listed_meta<'a>(
+    notes_dir: &Path,
+    files: impl IntoIterator<Item = &'a NoteFile>,
+    vault: &VaultState,
+) -> Vec<NoteMeta> {
+    let config = vault.config().ok();
+    let mut cache = config
         .as_ref()
-        .map(|t| format!("# {}\n\n", t))
-        .unwrap_or_else(|| "# Untitled\n\n".to_string());
+        .map(|config| MetaCache::load(config, vault))
+        .unwrap_or_default();
+
+    let files: Vec<&NoteFile> = files.into_iter().collect();
+    let loaded: Vec<(Option<NoteMeta>, bool)> = files
+        .par_iter()
+        .map(|file| match cache.fresh(notes_dir, file) {
+            Some(meta) => (Some(meta.clone()), false),
+            None => (load_note_meta(notes_dir, file, vault), true),
+        })
+        .collect();
+
+    let mut notes = Vec::with_capacity(files.len());
+    for (file, (meta, read)) in files.iter().zip(loaded) {
+        let Some(meta) = meta else {
+            continue;
+        };
+        if read {
+            cache.store(notes_dir, file, &meta);
+        }
+        notes.push(meta);
+    }
+    if let (Some(config), true) = (config, cache.changed) {
+        let _ = cache.save(&config, vault);
+    }
+    notes
+}
+
+/// Metadata for a note that couldn't be read, titled after its file
+fn corrupted_note_meta(rel: String, file: &NoteFile, error: &GhostError) -> NoteMeta {
+    NoteMeta {
+        corrupted: true,
+        error: Some(error.to_string()),
+        char_count: None,
+        ..build_note_meta(rel, file, "")
+    }
+}
+
+/// Metadata for the note `rel` from its file and decrypted content
+fn build_note_meta(rel: String, file: &NoteFile, content: &str) -> NoteMeta {
+    let (frontmatter, _) = parse_frontmatter(content);
+
+    NoteMeta {
+        id: rel.clone(),
+        path: rel,
+        title: extract_title(content, &file.path),
+        preview: extract_preview(content),
+        modified: file
+            .modified
+            .map(format_date)
+            .unwrap_or_else(|| "Unknown".to_string()),
+        modified_ms: file.modified.map(epoch_millis),
+        created: file
+            .created
+            .map(format_date)
+            .unwrap_or_else(|| "Unknown".to_string()),
+        created_ms: file.created.map(epoch_millis),
+        word_count: count_words(content),
+        char_count: Some(count_chars(content)),
+        tags: frontmatter.tags,
+        pinned: frontmatter.pinned,
+        corrupted: false,
+        error: None,
+    }
+}
+
+/// Get one note's metadata without its content
+///
+/// The note is still decrypted in full, but only the title, preview, counts
+/// and times cross to the frontend.
+#[tauri::command]
+pub fn get_note_meta(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NoteMeta, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    let file = NoteFile::from_path(note_file_path(&notes_dir, &path))
+        .ok_or_else(|| GhostError::NotFound(format!("Note '{}' not found", path)))?;
+    let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+
+    Ok(build_note_meta(path, &file, &content))
+}
+
+#[tauri::command]
+pub fn list_notes(
+    folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<NoteMeta>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, &folder)?;
+    let folder_path = notes_dir.join(&folder);
+
+    // Reserved folders hold no notes of their own to list
+    if !folder_path.exists() || is_reserved_dir(&notes_dir, &folder_path) {
+        return Ok(Vec::new());
+    }
+
+    // Sort on the raw modified time (newest first, unknown last), not the
+    // formatted date, which loses the year and orders by month name
+    let mut files = collect_note_files(&notes_dir, &folder_path);
+    sort_by_time(&mut files, |f| f.modified, true);
+
+    let mut notes = load_listed_meta(&notes_dir, &files, &vault);
+    // Pinned notes lead, each group keeping its order
+    notes.sort_by_key(|note| !note.pinned);
+    Ok(notes)
+}
+
+/// Totals over the notes in a folder and its subfolders
+#[derive(Debug, Serialize)]
+pub struct FolderStats {
+    pub total_notes: usize,
+    pub total_words: usize,
+    pub total_chars: usize,
+    pub average_words: f64,
+    /// Notes that couldn't be read and so aren't counted
+    pub corrupted: usize,
+}
+
+/// Word and character totals for `folder` and everything below it, or for
+/// the whole vault when `folder` is empty
+///
+/// Counts come from the metadata cache where it's current, so only notes
+/// changed since they were last listed need decrypting.
+#[tauri::command]
+pub fn folder_stats(
+    folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<FolderStats, GhostError> {
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, &folder)?;
+    let folder_path = notes_dir.join(&folder);
+    if !folder_path.is_dir() {
+        return Err(GhostError::NotFound(format!("Folder '{}' not found", folder)));
+    }
+
+    let ignore = load_ignore(&notes_dir);
+    let files: Vec<NoteFile> = walk_note_files(&notes_dir)
+        .into_iter()
+        .filter(|path| path.starts_with(&folder_path))
+        .filter(|path| !is_ignored(&ignore, &notes_dir, path, false))
+        .filter_map(NoteFile::from_path)
+        .collect();
+
+    let mut stats = FolderStats {
+        total_notes: 0,
+        total_words: 0,
+        total_chars: 0,
+        average_words: 0.0,
+        corrupted: 0,
+    };
+    for meta in load_listed_meta(&notes_dir, &files, &vault) {
+        if meta.corrupted {
+            stats.corrupted += 1;
+            continue;
+        }
+        stats.total_notes += 1;
+        stats.total_words += meta.word_count;
+        stats.total_chars += meta.char_count.unwrap_or(0);
+    }
+    if stats.total_notes > 0 {
+        stats.average_words = stats.total_words as f64 / stats.total_notes as f64;
+    }
+    Ok(stats)
+}
+
+/// Order for `list_notes_paged`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum NoteSort {
+    /// Most recently modified first
+    #[default]
+    ModifiedDesc,
+    /// Least recently modified first
+    ModifiedAsc,
+    /// Title, case-insensitive
+    TitleAsc,
+    /// Most recently created first
+    Created,
+    /// Earliest created first
+    CreatedAsc,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotePage {
+    pub notes: Vec<NoteMeta>,
+    pub total: usize,
+}
+
+/// List one page of a folder's notes
+///
+/// Time-based sorts only decrypt the notes inside the requested window.
+/// Sorting by title needs every note's content, so it reads the whole folder.
+/// `total` counts note files, so it can include notes that fail to decrypt.
+#[tauri::command]
+pub fn list_notes_paged(
+    folder: String,
+    offset: usize,
+    limit: usize,
+    sort_by: Option<NoteSort>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NotePage, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, &folder)?;
+    let folder_path = notes_dir.join(&folder);
+
+    if !folder_path.exists() || is_reserved_dir(&notes_dir, &folder_path) {
+        return Ok(NotePage {
+            notes: Vec::new(),
+            total: 0,
+        });
+    }
+
+    let mut files = collect_note_files(&notes_dir, &folder_path);
+    let total = files.len();
+
+    let notes = match sort_by.unwrap_or_default() {
+        NoteSort::TitleAsc => {
+            let mut notes = load_listed_meta(&notes_dir, &files, &vault);
+            notes.sort_by_cached_key(|n| n.title.to_lowercase());
+            notes.into_iter().skip(offset).take(limit).collect()
+        }
+        sort => {
+            match sort {
+                NoteSort::ModifiedAsc => sort_by_time(&mut files, |f| f.modified, false),
+                NoteSort::Created => sort_by_time(&mut files, |f| f.created, true),
+                NoteSort::CreatedAsc => sort_by_time(&mut files, |f| f.created, false),
+                _ => sort_by_time(&mut files, |f| f.modified, true),
+            }
+            load_listed_meta(&notes_dir, files.iter().skip(offset).take(limit), &vault)
+        }
+    };
+
+    Ok(NotePage { notes, total })
+}
+
+/// Note ids for each frontmatter tag, valid for one content version
+pub struct TagCache {
+    content_version: u64,
+    notes: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Drop the cached tags along with the plaintext they came from
+pub(crate) fn clear_tag_cache(state: &AppState) {
+    *state.tag_cache.lock().unwrap() = None;
+}
+
+/// Run `f` over the tag map, rebuilding it first if any note has changed
+///
+/// Every note has to be decrypted to read its frontmatter, so the map is kept
+/// until the vault's content version moves on.
+pub(crate) fn with_tag_map<T>(
+    state: &AppState,
+    vault: &VaultState,
+    f: impl FnOnce(&BTreeMap<String, Vec<String>>) -> T,
+) -> Result<T, GhostError> {
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+
+    let version = vault.content_version();
+    let mut cache = state.tag_cache.lock().unwrap();
+    if let Some(cached) = cache.as_ref().filter(|c| c.content_version == version) {
+        return Ok(f(&cached.notes));
+    }
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let mut notes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file_path in walk_note_files(&notes_dir) {
+        let rel_path = rel_note_path(&notes_dir, &file_path);
+        let Ok(content) = read_note_content(&notes_dir, &rel_path, vault) else {
+            continue;
+        };
+        let content = Zeroizing::new(content);
+        let (frontmatter, _) = parse_frontmatter(&content);
+        for tag in frontmatter.tags {
+            let paths = notes.entry(tag).or_default();
+            if !paths.contains(&rel_path) {
+                paths.push(rel_path.clone());
+            }
+        }
+    }
+
+    let result = f(&notes);
+    *cache = Some(TagCache {
+        content_version: version,
+        notes,
+    });
+    Ok(result)
+}
+
+/// List every frontmatter tag with the number of notes carrying it
+#[tauri::command]
+pub fn list_tags(
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<TagCount>, GhostError> {
+    with_tag_map(&state, &vault, |notes| {
+        notes
+            .iter()
+            .map(|(tag, paths)| TagCount {
+                tag: tag.clone(),
+                count: paths.len(),
+            })
+            .collect()
+    })
+}
+
+/// List the notes tagged `tag`, most recently modified first
+#[tauri::command]
+pub fn list_notes_by_tag(
+    tag: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<NoteMeta>, GhostError> {
+    let paths = with_tag_map(&state, &vault, |notes| {
+        notes.get(tag.trim()).cloned().unwrap_or_default()
+    })?;
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    Ok(load_notes_meta(&notes_dir, &paths, &vault))
+}
+
+/// Metadata for the notes `paths`, most recently modified first
+pub(crate) fn load_notes_meta(
+    notes_dir: &Path,
+    paths: &[String],
+    vault: &VaultState,
+) -> Vec<NoteMeta> {
+    let mut files: Vec<NoteFile> = paths
+        .iter()
+        .filter_map(|rel_path| NoteFile::from_path(note_file_path(notes_dir, rel_path)))
+        .collect();
+    sort_by_time(&mut files, |f| f.modified, true);
+
+    load_listed_meta(notes_dir, &files, vault)
+}
+
+/// Every note's title and `[[wiki links]]`, valid for one content version
+pub struct LinkGraph {
+    content_version: u64,
+    notes: Vec<LinkedNote>,
+}
+
+struct LinkedNote {
+    path: String,
+    title: String,
+    /// Link targets as written, in order of first appearance
+    links: Vec<String>,
+}
+
+impl LinkGraph {
+    /// The note a link target names: by id, else by title, else by slug
+    fn resolve(&self, target: &str) -> Option<&str> {
+        let title = target.to_lowercase();
+        let slug = slugify(target);
+
+        self.notes
+            .iter()
+            .find(|note| note.path == target)
+            .or_else(|| self.notes.iter().find(|note| note.title.to_lowercase() == title))
+            .or_else(|| self.notes.iter().find(|note| note_slug(&note.path) == slug))
+            .map(|note| note.path.as_str())
+    }
+
+    /// Notes a note links to, in link order, without itself or repeats
+    fn outgoing(&self, path: &str) -> Option<Vec<String>> {
+        let note = self.notes.iter().find(|note| note.path == path)?;
+        let mut targets: Vec<String> = Vec::new();
+        for link in &note.links {
+            if let Some(target) = self.resolve(link) {
+                if target != path && !targets.iter().any(|t| t == target) {
+                    targets.push(target.to_string());
+                }
+            }
+        }
+        Some(targets)
+    }
+}
+
+/// A note's file stem without its date prefix, as a slug
+fn note_slug(rel_path: &str) -> String {
+    let stem = Path::new(rel_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match date_prefix(&stem) {
+        Some(prefix) => &stem[prefix.len()..],
+        None => &stem,
+    };
+    slugify(name)
+}
+
+/// Targets of the `[[...]]` links in `content`, without any `|alias` or
+/// `#heading` part
+fn parse_wiki_links(content: &str) -> Vec<String> {
+    let (_, body) = parse_frontmatter(content);
+    let mut links: Vec<String> = Vec::new();
+    let mut rest = body;
+
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let inner = &rest[..end];
+        // A nested opener or line break means this `[[` wasn't a link
+        if inner.contains("[[") || inner.contains('\n') {
+            continue;
+        }
+        rest = &rest[end + 2..];
+
+        let target = inner.split(['|', '#']).next().unwrap_or_default().trim();
+        if !target.is_empty() && !links.iter().any(|l| l == target) {
+            links.push(target.to_string());
+        }
+    }
+    links
+}
+
+/// Drop the cached link graph along with the titles it holds
+pub(crate) fn clear_link_graph(state: &AppState) {
+    *state.link_graph.lock().unwrap() = None;
+}
+
+/// Run `f` over the link graph, rebuilding it first if any note has changed
+///
+/// Links can only be read from decrypted content, so the graph is kept
+/// until the vault's content version moves on.
+fn with_link_graph<T>(
+    state: &AppState,
+    vault: &VaultState,
+    f: impl FnOnce(&LinkGraph) -> T,
+) -> Result<T, GhostError> {
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+
+    let version = vault.content_version();
+    let mut cache = state.link_graph.lock().unwrap();
+    if let Some(cached) = cache.as_ref().filter(|g| g.content_version == version) {
+        return Ok(f(cached));
+    }
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let mut notes = Vec::new();
+    for file_path in walk_note_files(&notes_dir) {
+        let rel_path = rel_note_path(&notes_dir, &file_path);
+        let Ok(content) = read_note_content(&notes_dir, &rel_path, vault) else {
+            continue;
+        };
+        let content = Zeroizing::new(content);
+        notes.push(LinkedNote {
+            title: extract_title(&content, Path::new(&rel_path)),
+            links: parse_wiki_links(&content),
+            path: rel_path,
+        });
+    }
+
+    let graph = LinkGraph {
+        content_version: version,
+        notes,
+    };
+    let result = f(&graph);
+    *cache = Some(graph);
+    Ok(result)
+}
+
+/// Refresh `rel_path`'s links in the cached graph after a save that moved
+/// the content version on from `version`
+///
+/// Only a graph that was current before the save is patched; anything older
+/// is left to be rebuilt on next use.
+fn relink_note(state: &AppState, rel_path: &str, content: &str, version: u64) {
+    let mut cache = state.link_graph.lock().unwrap();
+    let Some(graph) = cache.as_mut().filter(|g| g.content_version == version) else {
+        return;
+    };
+
+    let title = extract_title(content, Path::new(rel_path));
+    let links = parse_wiki_links(content);
+    match graph.notes.iter_mut().find(|note| note.path == rel_path) {
+        Some(note) => {
+            note.title = title;
+            note.links = links;
+        }
+        None => graph.notes.push(LinkedNote {
+            path: rel_path.to_string(),
+            title,
+            links,
+        }),
+    }
+    graph.content_version = version + 1;
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub title: String,
+    /// Set for a link target that doesn't name any note
+    pub dangling: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl LinkGraph {
+    /// Every note as a node and every link as an edge
+    ///
+    /// Links that don't name a note point at a dangling node, one per target
+    /// regardless of case. Self-links and repeated links are left out.
+    fn to_note_graph(&self) -> NoteGraph {
+        let mut nodes: Vec<GraphNode> = self
+            .notes
+            .iter()
+            .map(|note| GraphNode {
+                id: note.path.clone(),
+                title: note.title.clone(),
+                dangling: false,
+            })
+            .collect();
+        let mut dangling: HashMap<String, String> = HashMap::new();
+        let mut edges = Vec::new();
+
+        for note in &self.notes {
+            let mut seen: Vec<String> = Vec::new();
+            for link in &note.links {
+                let to = match self.resolve(link) {
+                    Some(target) => target.to_string(),
+                    None => dangling
+                        .entry(link.to_lowercase())
+                        .or_insert_with(|| {
+                            nodes.push(GraphNode {
+                                id: link.clone(),
+                                title: link.clone(),
+                                dangling: true,
+                            });
+                            link.clone()
+                        })
+                        .clone(),
+                };
+                if to != note.path && !seen.contains(&to) {
+                    seen.push(to.clone());
+                    edges.push(GraphEdge {
+                        from: note.path.clone(),
+                        to,
+                    });
+                }
+            }
+        }
+        NoteGraph { nodes, edges }
+    }
+}
+
+/// The whole `[[wiki link]]` network between notes, for a graph view
+#[tauri::command]
+pub fn get_link_graph(
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NoteGraph, GhostError> {
+    with_link_graph(&state, &vault, LinkGraph::to_note_graph)
+}
+
+/// List the notes whose `[[links]]` point at `path`
+#[tauri::command]
+pub fn get_backlinks(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<NoteMeta>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let sources = with_link_graph(&state, &vault, |graph| {
+        graph
+            .notes
+            .iter()
+            .filter(|note| note.path != path)
+            .filter(|note| {
+                note.links
+                    .iter()
+                    .any(|link| graph.resolve(link) == Some(path.as_str()))
+            })
+            .map(|note| note.path.clone())
+            .collect::<Vec<_>>()
+    })?;
+    Ok(load_notes_meta(&notes_dir, &sources, &vault))
+}
+
+/// List the existing notes that `path` links to
+///
+/// Links that don't name any note are left out.
+#[tauri::command]
+pub fn get_outgoing_links(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<NoteMeta>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let targets = with_link_graph(&state, &vault, |graph| graph.outgoing(&path))?
+        .ok_or_else(|| GhostError::NotFound(format!("Note '{}' not found", path)))?;
+    Ok(load_notes_meta(&notes_dir, &targets, &vault))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelatedNote {
+    pub path: String,
+    pub title: String,
+    /// Jaccard similarity of the two notes' tag sets
+    pub score: f64,
+    pub shared_tags: Vec<String>,
+}
+
+/// Size of the intersection of two sets over the size of their union
+fn jaccard<T: Ord>(a: &BTreeSet<T>, b: &BTreeSet<T>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// The notes most like `path`, best first, for a related notes sidebar
+///
+/// Notes are ranked by how much their frontmatter tags overlap with the
+/// note's. Links to the same notes break ties, and also bring up notes
+/// that share no tags at all. At most `limit` are returned.
+#[tauri::command]
+pub fn find_related_notes(
+    path: String,
+    limit: usize,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<RelatedNote>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    let mut tags: HashMap<String, BTreeSet<String>> = HashMap::new();
+    with_tag_map(&state, &vault, |notes| {
+        for (tag, paths) in notes {
+            for note_path in paths {
+                tags.entry(note_path.clone())
+                    .or_default()
+                    .insert(tag.clone());
+            }
+        }
+    })?;
+
+    let mut related = with_link_graph(&state, &vault, |graph| {
+        let links = |note_path: &str| -> BTreeSet<String> {
+            graph
+                .outgoing(note_path)
+                .unwrap_or_default()
+                .into_iter()
+                .collect()
+        };
+        let own_tags = tags.get(&path).cloned().unwrap_or_default();
+        let own_links = links(&path);
+
+        graph
+            .notes
+            .iter()
+            .filter(|note| note.path != path)
+            .filter_map(|note| {
+                let note_tags = tags.get(&note.path).cloned().unwrap_or_default();
+                let score = jaccard(&own_tags, &note_tags);
+                let link_score = jaccard(&own_links, &links(&note.path));
+                if score == 0.0 && link_score == 0.0 {
+                    return None;
+                }
+                let related = RelatedNote {
+                    path: note.path.clone(),
+                    title: note.title.clone(),
+                    score,
+                    shared_tags: own_tags.intersection(&note_tags).cloned().collect(),
+                };
+                Some((related, link_score))
+            })
+            .collect::<Vec<_>>()
+    })?;
+
+    related.sort_by(|(a, a_links), (b, b_links)| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b_links.total_cmp(a_links))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(related
+        .into_iter()
+        .take(limit)
+        .map(|(note, _)| note)
+        .collect())
+}
+
+/// Pin a note to the top of its folder, or unpin it
+///
+/// The pin is kept in the note's frontmatter, so it follows the note through
+/// renames and moves.
+#[tauri::command]
+pub fn set_pinned(
+    path: String,
+    pinned: bool,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+
+    let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+    let updated = Zeroizing::new(set_frontmatter_pinned(&content, pinned));
+    if *updated == *content {
+        return Ok(());
+    }
+    if parse_frontmatter(&updated).0.pinned != pinned {
+        return Err(GhostError::Invalid(format!(
+            "Couldn't update the frontmatter of '{}'",
+            path
+        )));
+    }
+
+    record_own_write(&state, &path);
+    if is_encrypted(&notes_dir, &path) {
+        write_encrypted_note(&notes_dir, &path, &updated, &vault, None)?;
+    } else {
+        fs::write(notes_dir.join(&path), updated.as_bytes())?;
+    }
+    record_content_hash(&state, &path, &updated);
+    index_note(&state, &path, &updated);
+    vault.bump_content_version();
+    Ok(())
+}
+
+/// Read a note's content for the editor
+///
+/// The content is handed to the frontend, so it can't be wiped afterwards.
+/// Invalid UTF-8 fails the read unless `allow_invalid_utf8` is set.
+#[tauri::command]
+pub fn read_note(
+    path: String,
+    allow_invalid_utf8: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NoteContent, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    let (content, had_invalid_utf8) = read_note_text(
+        &notes_dir,
+        &path,
+        &vault,
+        allow_invalid_utf8.unwrap_or(false),
+    )?;
+
+    record_content_hash(&state, &path, &content);
+    record_recent(&vault, &path);
+    Ok(NoteContent {
+        content_hash: content_hash(&content),
+        path,
+        content,
+        had_invalid_utf8,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteRange {
+    pub path: String,
+    /// Line number of the first line in `lines`, counting from 1
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    /// Lines in the whole note
+    pub total_lines: usize,
+}
+
+/// Read a window of a note's lines, e.g. the part of a huge note on screen
+///
+/// The whole note is still decrypted, but only the requested lines cross to
+/// the frontend. A window past the end comes back empty.
+#[tauri::command]
+pub fn read_note_range(
+    path: String,
+    start_line: usize,
+    line_count: usize,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NoteRange, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    if start_line == 0 {
+        return Err(GhostError::Invalid("Line numbers start at 1".to_string()));
+    }
+
+    let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+    Ok(NoteRange {
+        path,
+        start_line,
+        lines: content
+            .lines()
+            .skip(start_line - 1)
+            .take(line_count)
+            .map(str::to_string)
+            .collect(),
+        total_lines: content.lines().count(),
+    })
+}
+
+/// Most notes remembered as recently opened
+const MAX_RECENT_NOTES: usize = 50;
+
+/// Ids of recently opened notes, most recent first
+fn read_recents(config: &VaultConfig) -> Vec<String> {
+    fs::read(&config.recents_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn write_recents(config: &VaultConfig, recents: &[String]) -> Result<(), GhostError> {
+    fs::write(&config.recents_path, serde_json::to_vec(recents)?)
+        .map_err(|e| GhostError::Io(format!("Failed to save recent notes: {}", e)))
+}
+
+/// Move a note to the front of the recently opened list
+///
+/// Best effort: failing to remember it never stops the note opening.
+fn record_recent(vault: &VaultState, rel_path: &str) {
+    let Ok(config) = vault.config() else {
+        return;
+    };
+    let mut recents = read_recents(&config);
+    recents.retain(|p| p != rel_path);
+    recents.insert(0, rel_path.to_string());
+    recents.truncate(MAX_RECENT_NOTES);
+    let _ = write_recents(&config, &recents);
+}
+
+/// List recently opened notes, most recent first
+///
+/// Notes that have since been deleted or moved are dropped from the list.
+#[tauri::command]
+pub fn list_recent_notes(
+    limit: Option<usize>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<NoteMeta>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let config = vault.config()?;
+
+    let mut recents = read_recents(&config);
+    let count = recents.len();
+    recents.retain(|p| !is_reserved_dir(&notes_dir, Path::new(p)) && note_exists_in(&notes_dir, p));
+    if recents.len() != count {
+        write_recents(&config, &recents)?;
+    }
+
+    let files: Vec<NoteFile> = recents
+        .iter()
+        .take(limit.unwrap_or(MAX_RECENT_NOTES))
+        .filter_map(|p| NoteFile::from_path(note_file_path(&notes_dir, p)))
+        .collect();
+    Ok(load_listed_meta(&notes_dir, &files, &vault))
+}
+
+/// Read a note and compute its metadata in one round trip
+///
+/// Invalid UTF-8 fails the read unless `allow_invalid_utf8` is set.
+#[tauri::command]
+pub fn open_note(
+    path: String,
+    allow_invalid_utf8: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<OpenedNote, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    let encrypted = is_encrypted(&notes_dir, &path);
+    let file_path = if encrypted {
+        enc_path(&notes_dir.join(&path))
+    } else {
+        notes_dir.join(&path)
+    };
+    let (content, had_invalid_utf8) = read_note_text(
+        &notes_dir,
+        &path,
+        &vault,
+        allow_invalid_utf8.unwrap_or(false),
+    )?;
+
+    record_content_hash(&state, &path, &content);
+    record_recent(&vault, &path);
+
+    let metadata = fs::metadata(&file_path).ok();
+    let created = note_created_time(&file_path, metadata.as_ref())
+        .map(format_date)
+        .unwrap_or_else(|| "Unknown".to_string());
+    let modified = metadata
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .map(format_date)
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    let (frontmatter, _) = parse_frontmatter(&content);
+    Ok(OpenedNote {
+        title: extract_title(&content, &file_path),
+        word_count: count_words(&content),
+        tags: frontmatter.tags,
+        outline: extract_outline(&content),
+        created,
+        modified,
+        encrypted,
+        readonly: read_sidecar(&notes_dir, &path).readonly,
+        had_invalid_utf8,
+        content_hash: content_hash(&content),
+        path,
+        content,
+    })
+}
+
+/// Export a note as plain text with Markdown syntax stripped
+#[tauri::command]
+pub fn export_note_plaintext(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let content = read_note_content(&notes_dir, &path, &vault)?;
+    Ok(markdown_to_plaintext(&content))
+}
+
+/// Stylesheet embedded in HTML exports
+const EXPORT_CSS: &str = "body { max-width: 42rem; margin: 2rem auto; padding: 0 1rem; \
+font: 16px/1.6 -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; color: #222; }
+pre, code { font-family: ui-monospace, Menlo, Consolas, monospace; background: #f4f4f4; }
+pre { padding: 0.75rem; overflow-x: auto; }
+blockquote { margin-left: 0; padding-left: 1rem; border-left: 3px solid #ccc; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25rem 0.5rem; }
+img { max-width: 100%; }";
+
+/// Render Markdown as a standalone HTML document
+fn markdown_to_html(content: &str, title: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(content, options));
+
+    let title = title
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title, EXPORT_CSS, body
+    )
+}
+
+/// Write a decrypted note to a file for sharing, returning the file's path
+///
+/// Without `dest` the file goes to the system temp folder under a random
+/// name, readable only by the current user. The destination can't be inside
+/// the notes folder, so no plaintext copy is left next to the encrypted
+/// notes.
+#[tauri::command]
+pub fn export_note(
+    path: String,
+    format: ExportFormat,
+    dest: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let content = read_note_content(&notes_dir, &path, &vault)?;
+    let title = extract_title(&content, Path::new(&path));
+
+    let (bytes, ext) = match format {
+        ExportFormat::Markdown => (content.into_bytes(), "md"),
+        ExportFormat::Html => (markdown_to_html(&content, &title).into_bytes(), "html"),
+        ExportFormat::Pdf => (markdown_to_pdf(&content), "pdf"),
+    };
+
+    let dest = match dest {
+        Some(dest) => {
+            let dest = PathBuf::from(dest);
+            if is_inside_notes_dir(&notes_dir, &dest) {
+                return Err(GhostError::Invalid(
+                    "Export destination must be outside the notes folder".to_string(),
+                ));
+            }
+            fs::write(&dest, bytes)
+                .map_err(|e| GhostError::Io(format!("Failed to write export: {}", e)))?;
+            dest
+        }
+        None => {
+            use std::io::Write;
+
+            let stem = Path::new(&path)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let dest = std::env::temp_dir().join(format!(
+                "ghostnote-{:016x}-{}.{}",
+                rand::random::<u64>(),
+                stem,
+                ext
+            ));
+            create_private_file(&dest)
+                .and_then(|mut file| file.write_all(&bytes))
+                .map_err(|e| GhostError::Io(format!("Failed to write export: {}", e)))?;
+            dest
+        }
+    };
+    Ok(dest.to_string_lossy().to_string())
+}
+
+/// Lines of `content` holding a `[[link]]` that `graph` resolves to `target`
+fn referencing_lines(graph: &LinkGraph, content: &str, target: &str) -> Vec<String> {
+    let (_, body) = parse_frontmatter(content);
+    body.lines()
+        .filter(|line| {
+            parse_wiki_links(line)
+                .iter()
+                .any(|link| graph.resolve(link) == Some(target))
+        })
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+/// Write a note as Markdown into `dest_dir` with an appendix of its
+/// backlinks, returning the file's path
+///
+/// Each note linking to it is listed by title with the lines holding the
+/// links, so the export reads on its own. The file is named after the note
+/// and `dest_dir` can't be inside the notes folder.
+#[tauri::command]
+pub fn export_note_with_context(
+    path: String,
+    dest_dir: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let dest_dir = PathBuf::from(dest_dir);
+    if is_inside_notes_dir(&notes_dir, &dest_dir) {
+        return Err(GhostError::Invalid(
+            "Export destination must be outside the notes folder".to_string(),
+        ));
+    }
+    let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+
+    let backlinks = with_link_graph(&state, &vault, |graph| {
+        graph
+            .notes
+            .iter()
+            .filter(|note| note.path != path)
+            .filter_map(|note| {
+                let source =
+                    Zeroizing::new(read_note_content(&notes_dir, &note.path, &vault).ok()?);
+                let lines = referencing_lines(graph, &source, &path);
+                (!lines.is_empty()).then(|| (note.title.clone(), note.path.clone(), lines))
+            })
+            .collect::<Vec<_>>()
+    })?;
+
+    let mut out = Zeroizing::new(content.trim_end().to_string());
+    out.push_str("\n\n---\n\n## Backlinks\n");
+    if backlinks.is_empty() {
+        out.push_str("\nNo other notes link here.\n");
+    }
+    for (title, source_path, lines) in &backlinks {
+        out.push_str(&format!("\n### {} (`{}`)\n\n", title, source_path));
+        for line in lines {
+            out.push_str(&format!("> {}\n", line));
+        }
+    }
+
+    fs::create_dir_all(&dest_dir)
+        .map_err(|e| GhostError::Io(format!("Failed to create export folder: {}", e)))?;
+    let stem = Path::new(&path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let dest = dest_dir.join(format!("{}.md", stem));
+    fs::write(&dest, out.as_bytes())
+        .map_err(|e| GhostError::Io(format!("Failed to write export: {}", e)))?;
+    Ok(dest.to_string_lossy().to_string())
+}
+
+/// Decrypt a note to a private temp file for editing in another app,
+/// returning the file's path
+///
+/// The file is readable only by the current user. `checkin_note` saves it
+/// back; until then it's tracked, and shredded when the vault locks or the
+/// app exits.
+#[tauri::command]
+pub fn checkout_note(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    use std::io::Write;
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+
+    // A random name created fresh, so nothing already there is written through
+    let stem = Path::new(&path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let temp_path = std::env::temp_dir().join(format!(
+        "ghostnote-{:016x}-{}.md",
+        rand::random::<u64>(),
+        stem
+    ));
+    let mut file = create_private_file(&temp_path)
+        .map_err(|e| GhostError::Io(format!("Failed to create checkout file: {}", e)))?;
+    state
+        .checkouts
+        .lock()
+        .unwrap()
+        .insert(temp_path.clone(), path);
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| GhostError::Io(format!("Failed to write checkout file: {}", e)))?;
+
+    Ok(temp_path.to_string_lossy().to_string())
+}
+
+/// Save a checked-out note's temp file back into the vault and shred it
+///
+/// Only files handed out by `checkout_note` for this note are accepted. If
+/// the save fails the temp file is kept, so the edits aren't lost.
+#[tauri::command]
+pub fn checkin_note(
+    path: String,
+    temp_path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let temp_path = PathBuf::from(temp_path);
+    if state.checkouts.lock().unwrap().get(&temp_path) != Some(&path) {
+        return Err(GhostError::Invalid(format!(
+            "'{}' is not a checkout of '{}'",
+            temp_path.display(),
+            path
+        )));
+    }
+
+    let content = fs::read_to_string(&temp_path)
+        .map_err(|e| GhostError::Io(format!("Failed to read checkout file: {}", e)))?;
+    save_note(path, content, None, state.clone(), vault)?;
+
+    state.checkouts.lock().unwrap().remove(&temp_path);
+    secure_delete(&temp_path)
+        .map_err(|e| GhostError::Io(format!("Failed to delete checkout file: {}", e)))
+}
+
+/// Shred every checked-out temp file, e.g. as the vault locks
+pub(crate) fn wipe_checkouts(state: &AppState) {
+    for (temp_path, _) in state.checkouts.lock().unwrap().drain() {
+        if let Err(e) = secure_delete(&temp_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Warning: Failed to delete {}: {}", temp_path.display(), e);
+            }
+        }
+    }
+}
+
+/// Save a note's content
+///
+/// With `expected_hash`, the `content_hash` the note was read with, the save
+/// fails with a conflict if the note has changed on disk since.
+#[tauri::command]
+pub fn save_note(
+    path: String,
+    content: String,
+    expected_hash: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+
+    // Refuse to overwrite a change made since the editor read the note.
+    // Read leniently, so a note opened with invalid UTF-8 matches its hash.
+    if let Some(expected_hash) = expected_hash {
+        if note_exists_in(&notes_dir, &path) {
+            let (theirs, _) = read_note_text(&notes_dir, &path, &vault, true)?;
+            let theirs_hash = content_hash(&theirs);
+            if theirs_hash != expected_hash {
+                return Err(GhostError::Conflict(Box::new(NoteConflict {
+                    path,
+                    ours: content,
+                    theirs,
+                    theirs_hash,
+                })));
+            }
+        }
+    }
+
+    record_own_write(&state, &path);
+    snapshot_note(&notes_dir, &path, *state.max_versions.lock().unwrap())?;
+
+    // Always save as encrypted
+    write_encrypted_note(&notes_dir, &path, &content, &vault, None)?;
+    vault.forget_note(&path);
+    record_content_hash(&state, &path, &content);
+    index_note(&state, &path, &content);
+    let version = vault.content_version();
+    vault.bump_content_version();
+    relink_note(&state, &path, &content, version);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_note(
+    folder: String,
+    title: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    // Create with initial content (encrypted)
+    let initial_content = title
+        .as_ref()
+        .map(|t| format!("# {}\n\n", t))
+        .unwrap_or_else(|| "# Untitled\n\n".to_string());
+
+    let scheme = load_settings()?.filename_scheme;
+    create_note_with_content(
+        &folder,
+        title.as_deref(),
+        &initial_content,
+        scheme,
+        &state,
+        &vault,
+    )
+}
+
+/// Create a note named after `title` by `scheme` in `folder`, holding
+/// `content`, and return its path
+fn create_note_with_content(
+    folder: &str,
+    title: Option<&str>,
+    content: &str,
+    scheme: FilenameScheme,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, folder)?;
+
+    // Fall back to the folder the user is currently browsing
+    let folder = if folder.is_empty() {
+        state.current_folder.lock().unwrap().clone()
+    } else {
+        folder
+    };
+    let folder_path = notes_dir.join(&folder);
+
+    // Ensure folder exists
+    fs::create_dir_all(&folder_path)?;
+
+    let slug = match title.map(slugify) {
+        Some(slug) if !slug.is_empty() => slug,
+        _ => "untitled".to_string(),
+    };
+    let base_name = scheme.base_name(&slug, chrono::Local::now());
+
+    // Use base name without extension (we'll add .enc and .key)
+    let base_path = unique_note_path(&folder_path, &base_name);
+
+    let rel_path = base_path
+        .strip_prefix(&notes_dir)
+        .unwrap_or(&base_path)
+        .to_string_lossy()
+        .to_string();
+
+    write_encrypted_note(&notes_dir, &rel_path, content, vault, None)?;
+    index_note(state, &rel_path, content);
+    vault.bump_content_version();
+
+    Ok(rel_path)
+}
+
+/// Name to show for a template: its title, unless that is itself a
+/// placeholder, in which case its file name without the date
+fn template_name(content: &str, base_path: &Path) -> String {
+    let title = extract_title(content, base_path);
+    if !title.contains("{{") {
+        return title;
+    }
+    let stem = base_path.file_name().unwrap_or_default().to_string_lossy();
+    let name = date_prefix(&stem).map_or(&stem[..], |prefix| &stem[prefix.len()..]);
+    name.replace('-', " ")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    /// Path under the templates folder, for `create_note_from_template`
+    pub id: String,
+    pub title: String,
+}
+
+/// List the templates in the templates folder, sorted by title
+///
+/// Templates are written like any other note, under `.templates/`.
+#[tauri::command]
+pub fn list_templates(
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<TemplateInfo>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let templates_dir = notes_dir.join(TEMPLATES_DIR);
+
+    let mut templates = Vec::new();
+    for entry in WalkDir::new(&templates_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "enc"))
+    {
+        let rel_path = rel_note_path(&notes_dir, entry.path());
+        let content = Zeroizing::new(read_note_content(&notes_dir, &rel_path, &vault)?);
+        templates.push(TemplateInfo {
+            id: rel_note_path(&templates_dir, entry.path()),
+            title: template_name(&content, &entry.path().with_extension("")),
+        });
+    }
+
+    templates.sort_by_key(|t| t.title.to_lowercase());
+    Ok(templates)
+}
+
+/// Create a note from a template and return its path
+///
+/// `{{date}}` and `{{title}}` in the template are filled in; without a
+/// title, the note is named and titled after the template.
+#[tauri::command]
+pub fn create_note_from_template(
+    folder: String,
+    template_id: String,
+    title: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let template_path = Path::new(TEMPLATES_DIR).join(normalize_note_path(&template_id)?);
+    let template_path = checked_note_path(&notes_dir, &template_path.to_string_lossy())?;
+    if !is_encrypted(&notes_dir, &template_path) {
+        return Err(GhostError::NotFound(format!(
+            "Template '{}' not found",
+            template_id
+        )));
+    }
+
+    let template = Zeroizing::new(read_note_content(&notes_dir, &template_path, &vault)?);
+    let title = title.unwrap_or_else(|| template_name(&template, Path::new(&template_path)));
+    let date_str = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let content = Zeroizing::new(
+        template
+            .replace("{{date}}", &date_str)
+            .replace("{{title}}", &title),
+    );
+
+    let scheme = load_settings()?.filename_scheme;
+    create_note_with_content(&folder, Some(&title), &content, scheme, &state, &vault)
+}
+
+/// Open today's journal entry, creating it from the daily template if it
+/// doesn't exist yet, and return its path
+///
+/// The entry is named `<today>-daily`, so there is at most one per day in
+/// each folder.
+#[tauri::command]
+pub fn open_daily_note(
+    folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, &folder)?;
+
+    // Fall back to the folder the user is currently browsing
+    let folder = if folder.is_empty() {
+        state.current_folder.lock().unwrap().clone()
+    } else {
+        folder
+    };
+
+    let date_str = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let rel_path = Path::new(&folder)
+        .join(format!("{}-daily", date_str))
+        .to_string_lossy()
+        .to_string();
+    if note_exists_in(&notes_dir, &rel_path) {
+        return Ok(rel_path);
+    }
+
+    fs::create_dir_all(notes_dir.join(&folder))?;
+    let template = load_settings()?.daily_note_template;
+    let initial_content = template.replace("{date}", &date_str);
+
+    write_encrypted_note(&notes_dir, &rel_path, &initial_content, &vault, None)?;
+    index_note(&state, &rel_path, &initial_content);
+    vault.bump_content_version();
+
+    Ok(rel_path)
+}
+
+/// Folder quick captures land in, created along with the notes folder
+pub(crate) const INBOX_DIR: &str = "inbox";
+
+/// Longest title `quick_capture` takes from the first line, in characters
+const MAX_CAPTURE_TITLE_CHARS: usize = 60;
+
+/// Title for captured text: the prose of its first non-empty line, whether
+/// that's a heading or plain text, without emphasis markers and cut back to
+/// a word boundary if it's long
+fn capture_title(text: &str) -> String {
+    let line = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    let prose = prose_line(line).replace(['*', '`'], "").replace("~~", "");
+    let prose = prose.trim();
+    if prose.chars().count() <= MAX_CAPTURE_TITLE_CHARS {
+        return match prose {
+            "" => "Quick note".to_string(),
+            prose => prose.to_string(),
+        };
+    }
+
+    let cut: String = prose.chars().take(MAX_CAPTURE_TITLE_CHARS).collect();
+    match cut.rfind(char::is_whitespace) {
+        Some(end) => cut[..end].trim_end().to_string(),
+        None => cut,
+    }
+}
+
+/// Save `text` as a new timestamped note in the inbox, returning its path
+///
+/// Meant for capturing a thought from anywhere. The title comes from the
+/// first line; unless that's already a `# Title` heading, one is added above
+/// the text.
+#[tauri::command]
+pub fn quick_capture(
+    text: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    if text.trim().is_empty() {
+        return Err(GhostError::Invalid("Nothing to capture".to_string()));
+    }
+
+    let text = text.trim_start_matches(['\r', '\n']).to_string();
+    let title = capture_title(&text);
+    let first_line = text.lines().find(|line| !line.trim().is_empty());
+    let content = if first_line.is_some_and(|line| line.trim_start().starts_with("# ")) {
+        Zeroizing::new(text)
+    } else {
+        Zeroizing::new(format!("# {}\n\n{}", title, text))
+    };
+
+    create_note_with_content(
+        INBOX_DIR,
+        Some(&title),
+        &content,
+        FilenameScheme::Timestamp,
+        &state,
+        &vault,
+    )
+}
+
+/// Copy a note into a new note beside it, returning the copy's path
+///
+/// The copy is named from the source's title with a `-copy` suffix and gets
+/// its own key, so the two notes share nothing on disk.
+#[tauri::command]
+pub fn duplicate_note(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let content = read_note_content(&notes_dir, &path, &vault)?;
+
+    let source = notes_dir.join(&path);
+    let folder_path = source.parent().unwrap_or(&notes_dir);
+
+    let date_str = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let slug = match slugify(&extract_title(&content, &source)) {
+        slug if slug.is_empty() => "untitled".to_string(),
+        slug => slug,
+    };
+    let base_path = unique_note_path(folder_path, &format!("{}-{}-copy", date_str, slug));
+
+    let rel_path = base_path
+        .strip_prefix(&notes_dir)
+        .unwrap_or(&base_path)
+        .to_string_lossy()
+        .to_string();
+
+    write_encrypted_note(&notes_dir, &rel_path, &content, &vault, None)?;
+    index_note(&state, &rel_path, &content);
+    vault.bump_content_version();
+
+    Ok(rel_path)
+}
+
+#[tauri::command]
+pub fn delete_note(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    trash_note(&notes_dir, &path, &state, &vault)?;
+    vault.bump_content_version();
+    Ok(())
+}
+
+/// Delete several notes, reporting how each one went
+///
+/// A failure doesn't stop the rest of the batch, so the frontend gets a
+/// result for every path it sent.
+#[tauri::command]
+pub fn batch_delete_notes(
+    paths: Vec<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Vec<(String, Result<(), GhostError>)> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    let results: Vec<(String, Result<(), GhostError>)> = paths
+        .into_iter()
+        .map(|path| {
+            let result = trash_note(&notes_dir, &path, &state, &vault);
+            (path, result)
+        })
+        .collect();
+
+    if results.iter().any(|(_, result)| result.is_ok()) {
+        vault.bump_content_version();
+    }
+    results
+}
+
+/// Move the note `path` to the trash and drop it from the index
+fn trash_note(
+    notes_dir: &Path,
+    path: &str,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<(), GhostError> {
+    let path = checked_note_path(notes_dir, path)?;
+    ensure_writable(notes_dir, &path)?;
+
+    unindex_note(state, &path);
+    move_to_trash(notes_dir, &path, state, vault)?;
+    Ok(())
+}
+
+/// Move a note into the trash, returning its trash id
+fn move_to_trash(
+    notes_dir: &Path,
+    rel_path: &str,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    let full_path = notes_dir.join(rel_path);
+    let encrypted = is_encrypted(notes_dir, rel_path);
+    if !encrypted && !full_path.is_file() {
+        return Err(GhostError::NotFound(format!(
+            "Note '{}' not found",
+            rel_path
+        )));
+    }
+
+    let trash_dir = notes_dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir)?;
+
+    // Ids sort by deletion time and stay readable
+    let stem = full_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let trash_id = {
+        let base = format!("{}-{}", chrono::Local::now().format("%Y%m%d%H%M%S"), stem);
+        let mut id = base.clone();
+        let mut counter = 1;
+        while trash_dir.join(format!("{}.json", id)).exists() {
+            id = format!("{}-{}", base, counter);
+            counter += 1;
+        }
+        id
+    };
+
+    let record = TrashRecord {
+        original_path: rel_path.to_string(),
+        deleted_at: format_date(SystemTime::now()),
+    };
+    let json = serde_json::to_vec_pretty(&record)?;
+    fs::write(trash_dir.join(format!("{}.json", trash_id)), json)
+        .map_err(|e| GhostError::Io(format!("Failed to write trash record: {}", e)))?;
+
+    let to_base = if encrypted {
+        trash_dir.join(&trash_id)
+    } else {
+        let ext = full_path.extension().unwrap_or_default().to_string_lossy().to_string();
+        trash_dir.join(format!("{}.{}", trash_id, ext))
+    };
+
+    if let Err(e) = relocate_note(notes_dir, rel_path, &to_base, state, vault) {
+        let _ = fs::remove_file(trash_dir.join(format!("{}.json", trash_id)));
+        return Err(e);
+    }
+    state
+        .content_hashes
+        .lock()
+        .unwrap()
+        .retain(|k, _| !k.starts_with(TRASH_DIR));
+
+    Ok(trash_id)
+}
+
+/// Trash id and record for every note in the trash
+fn read_trash(notes_dir: &Path) -> Vec<(String, TrashRecord)> {
+    let Ok(entries) = fs::read_dir(notes_dir.join(TRASH_DIR)) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|ext| ext == "json").unwrap_or(false))
+        .filter_map(|p| {
+            let record: TrashRecord = serde_json::from_slice(&fs::read(&p).ok()?).ok()?;
+            let id = p.file_stem()?.to_string_lossy().to_string();
+            Some((id, record))
+        })
+        .collect()
+}
+
+/// List notes in the trash, most recently deleted first
+#[tauri::command]
+pub fn list_trash(state: State<AppState>) -> Result<Vec<TrashEntry>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+    let mut entries: Vec<TrashEntry> = read_trash(&notes_dir)
+        .into_iter()
+        .map(|(id, record)| TrashEntry {
+            id,
+            original_path: record.original_path,
+            deleted_at: record.deleted_at,
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(entries)
+}
+
+/// Put a trashed note back where it was deleted from
+///
+/// Recreates the original folder if it is gone. If another note has taken
+/// the original name, the restored note gets a numeric suffix. Returns the
+/// restored note's id.
+#[tauri::command]
+pub fn restore_note(
+    trash_id: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let trash_id = checked_note_path(&notes_dir, &trash_id)?;
+    let trash_dir = notes_dir.join(TRASH_DIR);
+    let record_path = trash_dir.join(format!("{}.json", trash_id));
+
+    let record: TrashRecord = fs::read(&record_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .ok_or_else(|| GhostError::NotFound(format!("'{}' is not in the trash", trash_id)))?;
+    let original_path = checked_note_path(&notes_dir, &record.original_path)?;
+    let original = notes_dir.join(&original_path);
+
+    let folder_path = original
+        .parent()
+        .ok_or_else(|| GhostError::Invalid(format!("Invalid note path '{}'", original_path)))?
+        .to_path_buf();
+    fs::create_dir_all(&folder_path)?;
+
+    let trash_rel = Path::new(TRASH_DIR).join(&trash_id).to_string_lossy().to_string();
+    let (trash_rel, to_base) = if is_encrypted(&notes_dir, &trash_rel) {
+        let file_name = original.file_name().unwrap_or_default().to_string_lossy().to_string();
+        (trash_rel, unique_note_path(&folder_path, &file_name))
+    } else {
+        let ext = original.extension().unwrap_or_default().to_string_lossy().to_string();
+        let trash_rel = format!("{}.{}", trash_rel, ext);
+        (trash_rel, unique_legacy_path(&folder_path, &original))
+    };
+
+    let rel_path = relocate_note(&notes_dir, &trash_rel, &to_base, &state, &vault)?;
+    let _ = fs::remove_file(&record_path);
+
+    if let Ok(content) = read_note_content(&notes_dir, &rel_path, &vault) {
+        index_note(&state, &rel_path, &content);
+    }
+
+    vault.bump_content_version();
+    Ok(rel_path)
+}
+
+/// Permanently delete everything in the trash, returning how many notes went
+///
+/// While the vault is unlocked, attachments no remaining note references go
+/// too. With `secure` every file is overwritten before it's unlinked, which
+/// is slower and only best effort (see `secure_delete`); by default files
+/// are just removed.
+#[tauri::command]
+pub fn empty_trash(
+    secure: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<usize, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let trash_dir = notes_dir.join(TRASH_DIR);
+    // History of trashed notes went to the trash with them
+    let history_trash_dir = notes_dir.join(HISTORY_DIR).join(TRASH_DIR);
+    let secure = secure.unwrap_or(false);
+
+    let count = read_trash(&notes_dir).len();
+    if secure {
+        for entry in WalkDir::new(&trash_dir)
+            .into_iter()
+            .chain(WalkDir::new(&history_trash_dir))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            secure_delete(entry.path()).map_err(|e| {
+                GhostError::Io(format!("Failed to delete '{}': {}", entry.path().display(), e))
+            })?;
+        }
+    }
+    if trash_dir.exists() {
+        fs::remove_dir_all(&trash_dir)
+            .map_err(|e| GhostError::Io(format!("Failed to empty trash: {}", e)))?;
+    }
+    let _ = fs::remove_dir_all(&history_trash_dir);
+
+    if vault.is_unlocked() {
+        let _ = remove_unreferenced_attachments(&notes_dir, &vault, secure);
+    }
+
+    Ok(count)
+}
+
+/// What `vacuum_vault` should clean up besides history and orphan keys
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct VacuumOptions {
+    /// Permanently delete notes that have been in the trash at least this
+    /// many days; the trash is left alone without it
+    pub trash_older_than_days: Option<u64>,
+    /// Only report what would be deleted
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VacuumReport {
+    /// Versions beyond the number kept per note
+    pub history_versions: usize,
+    pub trashed_notes: usize,
+    /// `.key` files with no `.enc` beside them
+    pub orphan_keys: usize,
+    /// Total size of the files removed, or that would be on a dry run
+    pub bytes_reclaimed: u64,
+}
+
+/// Shred one file for `vacuum_vault`, or only measure it on a dry run,
+/// returning its size (0 if there is no such file)
+fn vacuum_file(path: &Path, dry_run: bool) -> Result<u64, GhostError> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(0);
+    };
+    if !metadata.is_file() {
+        return Ok(0);
+    }
+    if !dry_run {
+        secure_delete(path)
+            .map_err(|e| GhostError::Io(format!("Failed to delete '{}': {}", path.display(), e)))?;
+    }
+    Ok(metadata.len())
+}
+
+/// Reclaim space from note history, the trash and orphan key files
+///
+/// History is cut back to the number of versions kept per note, and trashed
+/// notes older than `trash_older_than_days` go for good, along with their
+/// history. Removed files are overwritten before being unlinked.
+#[tauri::command]
+pub fn vacuum_vault(
+    options: VacuumOptions,
+    state: State<AppState>,
+) -> Result<VacuumReport, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let limit = *state.max_versions.lock().unwrap();
+    let dry_run = options.dry_run;
+    let mut report = VacuumReport::default();
+
+    // Every folder of versions, including those of trashed notes
+    let history_dirs: Vec<PathBuf> = WalkDir::new(notes_dir.join(HISTORY_DIR))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect();
+    for dir in history_dirs {
+        let versions = history_versions(&dir);
+        for old in &versions[..versions.len().saturating_sub(limit)] {
+            let base = dir.join(old);
+            report.bytes_reclaimed += vacuum_file(&enc_path(&base), dry_run)?;
+            report.bytes_reclaimed += vacuum_file(&key_path(&base), dry_run)?;
+            report.history_versions += 1;
+        }
+    }
+
+    if let Some(days) = options.trash_older_than_days {
+        let cutoff = chrono::Local::now().naive_local() - chrono::Duration::days(days as i64);
+        let trash_dir = notes_dir.join(TRASH_DIR);
+        for (id, _) in read_trash(&notes_dir) {
+            // Trash ids start with the time the note was deleted
+            let deleted = id
+                .get(..14)
+                .and_then(|t| chrono::NaiveDateTime::parse_from_str(t, "%Y%m%d%H%M%S").ok());
+            if deleted.is_none_or(|deleted| deleted > cutoff) {
+                continue;
+            }
+
+            for ext in ["enc", "key", "md", "txt", "meta", "json"] {
+                let file = trash_dir.join(format!("{}.{}", id, ext));
+                report.bytes_reclaimed += vacuum_file(&file, dry_run)?;
+            }
+            let versions_dir = notes_dir.join(HISTORY_DIR).join(TRASH_DIR).join(&id);
+            for version in history_versions(&versions_dir) {
+                let base = versions_dir.join(&version);
+                report.bytes_reclaimed += vacuum_file(&enc_path(&base), dry_run)?;
+                report.bytes_reclaimed += vacuum_file(&key_path(&base), dry_run)?;
+            }
+            if !dry_run {
+                let _ = fs::remove_dir(&versions_dir);
+            }
+            report.trashed_notes += 1;
+        }
+    }
+
+    let orphan_keys: Vec<PathBuf> = WalkDir::new(&notes_dir)
+        .into_iter()
+        .filter_entry(|e| !e.path().starts_with(notes_dir.join(".vault")))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "key") && !enc_path(p).exists())
+        .collect();
+    for key_file in orphan_keys {
+        report.bytes_reclaimed += vacuum_file(&key_file, dry_run)?;
+        report.orphan_keys += 1;
+    }
+
+    Ok(report)
+}
+
+/// Folder holding a note's earlier versions
+fn history_dir(notes_dir: &Path, rel_path: &str) -> PathBuf {
+    notes_dir.join(HISTORY_DIR).join(rel_path)
+}
+
+/// Version ids in a note's history, oldest first
+fn history_versions(history_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(history_dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "enc"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .collect();
+    versions.sort();
+    versions
+}
+
+/// Copy a note's current encrypted files into its history, keeping at most
+/// `limit` versions
+///
+/// The files are copied rather than moved so the note is still intact if the
+/// save that follows fails. Legacy plaintext notes have no history.
+fn snapshot_note(notes_dir: &Path, rel_path: &str, limit: usize) -> Result<(), GhostError> {
+    if limit == 0 || !is_encrypted(notes_dir, rel_path) {
+        return Ok(());
+    }
+
+    let dir = history_dir(notes_dir, rel_path);
+    fs::create_dir_all(&dir)?;
+
+    // Ids sort by save time; millis keep rapid autosaves apart
+    let base = chrono::Local::now().format("%Y%m%d%H%M%S%3f").to_string();
+    let mut version = base.clone();
+    let mut counter = 1;
+    while enc_path(&dir.join(&version)).exists() {
+        version = format!("{}-{}", base, counter);
+        counter += 1;
+    }
+
+    let from_base = notes_dir.join(rel_path);
+    let to_base = dir.join(&version);
+    fs::copy(key_path(&from_base), key_path(&to_base))
+        .map_err(|e| GhostError::Io(format!("Failed to save note history: {}", e)))?;
+    fs::copy(enc_path(&from_base), enc_path(&to_base))
+        .map_err(|e| GhostError::Io(format!("Failed to save note history: {}", e)))?;
+
+    let versions = history_versions(&dir);
+    for old in &versions[..versions.len().saturating_sub(limit)] {
+        let _ = fs::remove_file(enc_path(&dir.join(old)));
+        let _ = fs::remove_file(key_path(&dir.join(old)));
+    }
+
+    Ok(())
+}
+
+/// Move a note's history along with it, binding each version to the new id
+fn relocate_history(
+    notes_dir: &Path,
+    old_rel: &str,
+    new_rel: &str,
+    vault: &VaultState,
+) -> Result<(), GhostError> {
+    let from = history_dir(notes_dir, old_rel);
+    if !from.is_dir() {
+        return Ok(());
+    }
+    let to = history_dir(notes_dir, new_rel);
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&from, &to)?;
+
+    for version in history_versions(&to) {
+        let base = to.join(&version);
+        let (dek, content) = decrypt_note_files(&base, old_rel, vault)?;
+        encrypt_note_files(&base, new_rel, content.as_bytes(), vault, Some(dek))?;
+    }
+    Ok(())
+}
+
+/// List a note's earlier versions, newest first
+#[tauri::command]
+pub fn list_versions(path: String, state: State<AppState>) -> Result<Vec<NoteVersion>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let dir = history_dir(&notes_dir, &path);
+
+    let mut versions: Vec<NoteVersion> = history_versions(&dir)
+        .into_iter()
+        .map(|id| {
+            let saved_at =
+                chrono::NaiveDateTime::parse_from_str(&id[..id.len().min(17)], "%Y%m%d%H%M%S%3f")
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|_| "Unknown".to_string());
+            NoteVersion { id, saved_at }
+        })
+        .collect();
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Bring back an earlier version of a note
+///
+/// The current content goes into the history first, so a restore can itself
+/// be undone.
+#[tauri::command]
+pub fn restore_version(
+    path: String,
+    version: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+
+    let dir = history_dir(&notes_dir, &path);
+    let version_base = dir.join(&version);
+    let valid_id = version.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_id || !enc_path(&version_base).is_file() {
+        return Err(GhostError::NotFound(format!(
+            "Version '{}' of '{}' not found",
+            version, path
+        )));
+    }
+    let (_, content) = decrypt_note_files(&version_base, &path, &vault)?;
+
+    record_own_write(&state, &path);
+    snapshot_note(&notes_dir, &path, *state.max_versions.lock().unwrap())?;
+    // Re-encrypted under the note's own DEK, not the version's, so the key
+    // and content files always match while they are swapped in
+    write_encrypted_note(&notes_dir, &path, &content, &vault, None)?;
+
+    // The restored version is the current note now
+    let _ = fs::remove_file(enc_path(&version_base));
+    let _ = fs::remove_file(key_path(&version_base));
+
+    record_content_hash(&state, &path, &content);
+    index_note(&state, &path, &content);
+    vault.bump_content_version();
+    Ok(())
+}
+
+/// Re-encrypt a note under a freshly generated DEK
+///
+/// For when a single note's key may have leaked; `change_master_password`
+/// rotates the KEK instead, which leaves every DEK as it was. Versions in
+/// the note's history keep the DEK they were saved with.
+#[tauri::command]
+pub fn rekey_note(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+    if !is_encrypted(&notes_dir, &path) {
+        return Err(if note_file_path(&notes_dir, &path).is_file() {
+            GhostError::Invalid(format!("Note '{}' is not encrypted", path))
+        } else {
+            GhostError::NotFound(format!("Note '{}' not found", path))
+        });
+    }
+
+    let base_path = notes_dir.join(&path);
+    let (_, content) = decrypt_note_files(&base_path, &path, &vault)?;
+
+    record_own_write(&state, &path);
+    write_encrypted_note(&notes_dir, &path, &content, &vault, Some(Dek::generate()))?;
+    Ok(())
+}
+
+/// Overwrite a file's contents with random bytes before unlinking it
+///
+/// The file is overwritten `secure_delete_passes` times (see settings).
+/// Best effort: journaling and copy-on-write filesystems or SSD wear
+/// levelling may still keep old blocks around.
+pub(crate) fn secure_delete(path: &Path) -> std::io::Result<()> {
+    let passes = load_settings()
+        .map(|settings| settings.secure_delete_passes)
+        .unwrap_or(1);
+    shred_file(path, passes, |_| {})
+}
+
+/// Body of `secure_delete`, calling `after_pass` once each overwrite is
+/// flushed to disk
+fn shred_file(path: &Path, passes: u32, mut after_pass: impl FnMut(u32)) -> std::io::Result<()> {
+    use rand::RngCore;
+    use std::io::{Seek, Write};
+
+    let len = fs::metadata(path)?.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let mut noise = [0u8; 8192];
+    for pass in 1..=passes.max(1) {
+        file.rewind()?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = remaining.min(noise.len() as u64) as usize;
+            rand::thread_rng().fill_bytes(&mut noise[..chunk]);
+            file.write_all(&noise[..chunk])?;
+            remaining -= chunk as u64;
+        }
+        file.sync_all()?;
+        after_pass(pass);
+    }
+    drop(file);
+
+    fs::remove_file(path)
+}
+
+/// Encrypt one legacy `.md`/`.txt` note and shred the plaintext, returning
+/// the encrypted note's id
+fn migrate_legacy_note(
+    notes_dir: &Path,
+    rel_path: &str,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    let file_path = notes_dir.join(rel_path);
+    let is_legacy = matches!(
+        file_path.extension().and_then(|e| e.to_str()),
+        Some("md" | "txt")
+    );
+    if !is_legacy || !file_path.is_file() {
+        return Err(GhostError::Invalid(format!(
+            "'{}' is not a plaintext note",
+            rel_path
+        )));
+    }
+
+    let content = fs::read_to_string(&file_path)?;
+
+    // An encrypted note with the same name shadows this one, so pick a free name
+    let folder_path = file_path.parent().unwrap_or(notes_dir);
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    let base_path = unique_note_path(folder_path, &stem);
+    let new_rel_path = rel_note_path(notes_dir, &base_path);
+
+    record_own_write(state, &new_rel_path);
+    write_encrypted_note(notes_dir, &new_rel_path, &content, vault, None)?;
+
+    // Only shred the plaintext once the encrypted copy reads back intact
+    if read_encrypted_note(notes_dir, &new_rel_path, vault)
+        .ok()
+        .as_deref()
+        != Some(&content)
+    {
+        let _ = fs::remove_file(enc_path(&base_path));
+        let _ = fs::remove_file(key_path(&base_path));
+        return Err(GhostError::DecryptionFailed(format!(
+            "Encrypted copy of '{}' didn't verify",
+            rel_path
+        )));
+    }
+    secure_delete(&file_path)
+        .map_err(|e| GhostError::Io(format!("Failed to delete plaintext: {}", e)))?;
+
+    // Sidecar metadata travels with the note
+    if meta_path(&file_path) != meta_path(&base_path) {
+        let _ = fs::rename(meta_path(&file_path), meta_path(&base_path));
+    }
+
+    let mut hashes = state.content_hashes.lock().unwrap();
+    if let Some(hash) = hashes.remove(rel_path) {
+        hashes.insert(new_rel_path.clone(), hash);
+    }
+    drop(hashes);
+    reindex_moved_note(state, rel_path, &new_rel_path);
+
+    Ok(new_rel_path)
+}
+
+/// Encrypt a legacy plaintext note under a fresh DEK, returning its new id
+///
+/// The plaintext file is overwritten before it's removed.
+#[tauri::command]
+pub fn migrate_note_to_encrypted(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    let new_path = migrate_legacy_note(&notes_dir, &path, &state, &vault)?;
+    vault.bump_content_version();
+    Ok(new_path)
+}
+
+/// Encrypt every legacy plaintext note in the vault
+///
+/// Notes that fail are left as they are and listed in the report.
+#[tauri::command]
+pub fn migrate_all_to_encrypted(
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<MigrationReport, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    // Fail up front rather than once per note
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+
+    let mut report = MigrationReport {
+        migrated: 0,
+        failed: Vec::new(),
+    };
+    for file in walk_note_files(&notes_dir) {
+        if file.extension().is_some_and(|ext| ext == "enc") {
+            continue;
+        }
+        let rel_path = rel_note_path(&notes_dir, &file);
+        match migrate_legacy_note(&notes_dir, &rel_path, &state, &vault) {
+            Ok(_) => report.migrated += 1,
+            Err(e) => report.failed.push(MigrationFailure {
+                path: rel_path,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    if report.migrated > 0 {
+        vault.bump_content_version();
+    }
+    Ok(report)
+}
+
+/// Move a note into another folder, keeping its file name
+///
+/// Returns the note's new id. A name already taken in the destination gets a
+/// numeric suffix, the same way `create_note` handles duplicates. Read-only
+/// notes stay where they are.
+#[tauri::command]
+pub fn move_note(
+    from_path: String,
+    to_folder: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let from_path = checked_note_path(&notes_dir, &from_path)?;
+    ensure_writable(&notes_dir, &from_path)?;
+    // Checking also keeps the destination inside the notes folder
+    let to_folder = checked_note_path(&notes_dir, &to_folder)?;
+    if is_reserved_dir(&notes_dir, Path::new(&to_folder)) {
+        return Err(GhostError::Invalid(
+            "Cannot move notes into the vault, trash, history, attachments or templates folder"
+                .to_string(),
+        ));
+    }
+    let from_base = notes_dir.join(&from_path);
+
+    let file_name = from_base
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| GhostError::Invalid(format!("Invalid note path '{}'", from_path)))?;
+
+    let folder_path = notes_dir.join(&to_folder);
+    fs::create_dir_all(&folder_path)?;
+
+    let to_base = if is_encrypted(&notes_dir, &from_path) {
+        unique_note_path(&folder_path, &file_name)
+    } else if from_base.is_file() {
+        unique_legacy_path(&folder_path, &from_base)
+    } else {
+        return Err(GhostError::NotFound(format!(
+            "Note '{}' not found",
+            from_path
+        )));
+    };
+
+    let rel_path = relocate_note(&notes_dir, &from_path, &to_base, &state, &vault)?;
+    vault.bump_content_version();
+    Ok(rel_path)
+}
+
+/// Where a note or folder ended up after a rename
+#[derive(Debug, Serialize)]
+pub struct Renamed {
+    pub path: String,
+    /// Notes whose `[[links]]` were rewritten to follow the rename
+    pub relinked: Vec<String>,
+}
+
+/// Rewrite the `[[link]]` targets in `content` that `retarget` gives a new
+/// target for, keeping any `|alias` or `#heading` part
+///
+/// Links are found as `parse_wiki_links` finds them. `None` if nothing
+/// changed.
+fn retarget_wiki_links(content: &str, retarget: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let (_, body) = parse_frontmatter(content);
+    let mut out = String::with_capacity(content.len());
+    out.push_str(&content[..content.len() - body.len()]);
+    let mut rest = body;
+    let mut changed = false;
+
+    while let Some(start) = rest.find("[[") {
+        out.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let inner = &rest[..end];
+        if inner.contains("[[") || inner.contains('\n') {
+            continue;
+        }
+        rest = &rest[end + 2..];
+
+        let target_end = inner.find(['|', '#']).unwrap_or(inner.len());
+        match retarget(inner[..target_end].trim()).filter(|t| t != inner[..target_end].trim()) {
+            Some(target) => {
+                out.push_str(&target);
+                out.push_str(&inner[target_end..]);
+                changed = true;
+            }
+            None => out.push_str(inner),
+        }
+        out.push_str("]]");
+    }
+    out.push_str(rest);
+    changed.then_some(out)
+}
+
+/// Write new content for an existing note, under its existing DEK
+fn rewrite_note(
+    notes_dir: &Path,
+    rel_path: &str,
+    content: &str,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<(), GhostError> {
+    if is_encrypted(notes_dir, rel_path) {
+        let wrapped_dek = fs::read(key_path(&notes_dir.join(rel_path)))
+            .map_err(|e| GhostError::Io(format!("Failed to read key file: {}", e)))?;
+        let dek = vault.with_kek(|kek| unwrap_dek(kek, &wrapped_dek))?;
+        write_encrypted_note(notes_dir, rel_path, content, vault, Some(dek))?;
+    } else {
+        fs::write(notes_dir.join(rel_path), content)?;
+    }
+    vault.forget_note(rel_path);
+    record_content_hash(state, rel_path, content);
+    index_note(state, rel_path, content);
+    Ok(())
+}
+
+/// Rewrite the links in every note that `retarget` gives a new target for,
+/// returning the notes changed
+///
+/// Read-only notes and notes that can't be read are left alone.
+fn relink_references(
+    notes_dir: &Path,
+    state: &AppState,
+    vault: &VaultState,
+    retarget: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>, GhostError> {
+    let mut relinked = Vec::new();
+    for file_path in walk_note_files(notes_dir) {
+        let rel_path = rel_note_path(notes_dir, &file_path);
+        if read_sidecar(notes_dir, &rel_path).readonly {
+            continue;
+        }
+        let Ok(content) = read_note_content(notes_dir, &rel_path, vault) else {
+            continue;
+        };
+        let content = Zeroizing::new(content);
+        let Some(updated) = retarget_wiki_links(&content, &retarget) else {
+            continue;
+        };
+        let updated = Zeroizing::new(updated);
+
+        record_own_write(state, &rel_path);
+        rewrite_note(notes_dir, &rel_path, &updated, state, vault)?;
+        relinked.push(rel_path);
+    }
+    relinked.sort();
+    Ok(relinked)
+}
+
+/// Rename a note's files to match a new title
+///
+/// The `YYYY-MM-DD-` prefix is kept and the DEK and ciphertext are left
+/// untouched. A note whose file name is locked (see `set_filename_locked`)
+/// keeps its files. With `update_heading` the note's `# Title` line is rewritten
+/// to the new title as well.
+///
+/// With `update_links`, links elsewhere that named the note by its old id,
+/// title or slug and would no longer find it are rewritten to match. This
+/// re-encrypts every note that links to it, so it is opt-in.
+#[tauri::command]
+pub fn rename_note(
+    path: String,
+    new_title: String,
+    update_heading: Option<bool>,
+    update_links: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Renamed, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+    let update_heading = update_heading.unwrap_or(false);
+
+    // Links resolve by title too, so note it before the heading changes
+    let old_title = if update_links.unwrap_or(false) {
+        let content = Zeroizing::new(read_note_content(&notes_dir, &path, &vault)?);
+        Some(extract_title(&content, Path::new(&path)))
+    } else {
+        None
+    };
+    let old_path = path.clone();
+
+    // A note whose file name was locked keeps it under any title
+    let from_base = notes_dir.join(&path);
+    let to_base = if read_sidecar(&notes_dir, &path).lock_filename {
+        from_base.clone()
+    } else {
+        retitled_base(&notes_dir, &path, &new_title)?
+    };
+
+    let rel_path = if to_base == from_base {
+        path
+    } else {
+        relocate_note(&notes_dir, &path, &to_base, &state, &vault)?
+    };
 
-    let rel_path = base_path
-        .strip_prefix(&notes_dir)
-        .unwrap_or(&base_path)
+    if update_heading {
+        let content = read_note_content(&notes_dir, &rel_path, &vault)?;
+        let updated = replace_heading(&content, &new_title);
+        rewrite_note(&notes_dir, &rel_path, &updated, &state, &vault)?;
+    }
+
+    let relinked = match old_title {
+        Some(old_title) => {
+            let old_title = old_title.to_lowercase();
+            let old_slug = note_slug(&old_path);
+            let new_slug = note_slug(&rel_path);
+            relink_references(&notes_dir, &state, &vault, |target| {
+                if target == old_path {
+                    Some(rel_path.clone())
+                } else if target.to_lowercase() == old_title {
+                    // Still found by its title unless the heading changed
+                    update_heading.then(|| new_title.clone())
+                } else if slugify(target) == old_slug {
+                    Some(new_slug.clone())
+                } else {
+                    None
+                }
+            })?
+        }
+        None => Vec::new(),
+    };
+
+    vault.bump_content_version();
+    Ok(Renamed {
+        path: rel_path,
+        relinked,
+    })
+}
+
+/// Where the note `path` goes when renamed after `title`: its current files
+/// if they already fit, else a free name in the same folder. The creation
+/// date prefix of the old name is kept.
+fn retitled_base(notes_dir: &Path, path: &str, title: &str) -> Result<PathBuf, GhostError> {
+    let from_base = notes_dir.join(path);
+    let folder_path = from_base
+        .parent()
+        .ok_or_else(|| GhostError::Invalid(format!("Invalid note path '{}'", path)))?;
+    let stem = from_base
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Keep the creation date prefix from the old name
+    let base_name = match date_prefix(&stem) {
+        Some(prefix) => format!("{}{}", prefix, slugify(title)),
+        None => slugify(title),
+    };
+
+    if is_encrypted(notes_dir, path) {
+        let candidate = folder_path.join(&base_name);
+        if candidate == from_base {
+            Ok(candidate)
+        } else {
+            Ok(unique_note_path(folder_path, &base_name))
+        }
+    } else if from_base.is_file() {
+        let ext = from_base.extension().unwrap_or_default().to_string_lossy().to_string();
+        let candidate = folder_path.join(format!("{}.{}", base_name, ext));
+        if candidate == from_base {
+            Ok(candidate)
+        } else {
+            Ok(unique_legacy_path(folder_path, &candidate))
+        }
+    } else {
+        Err(GhostError::NotFound(format!("Note '{}' not found", path)))
+    }
+}
+
+/// Pick a free path for a legacy note file in `folder_path`
+///
+/// Keeps the file's extension and suffixes the stem like `unique_note_path`.
+pub(crate) fn unique_legacy_path(folder_path: &Path, file_path: &Path) -> PathBuf {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = file_path.extension().unwrap_or_default().to_string_lossy().to_string();
+
+    let mut candidate = folder_path.join(format!("{}.{}", stem, ext));
+    let mut counter = 1;
+    while candidate.exists() {
+        candidate = folder_path.join(format!("{}-{}.{}", stem, counter, ext));
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// Move a note's files (and sidecar) to `to_base`, returning the new note id
+///
+/// Encrypted notes are re-encrypted so they stay bound to their new id.
+fn relocate_note(
+    notes_dir: &Path,
+    rel_path: &str,
+    to_base: &Path,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    let from_base = notes_dir.join(rel_path);
+    let new_rel_path = to_base
+        .strip_prefix(notes_dir)
+        .unwrap_or(to_base)
         .to_string_lossy()
         .to_string();
 
-    write_encrypted_note(&notes_dir, &rel_path, &initial_content, &vault, None)?;
+    if is_encrypted(notes_dir, rel_path) {
+        // Move the key first so a failed content move can be rolled back
+        fs::rename(key_path(&from_base), key_path(to_base))
+            .map_err(|e| GhostError::Io(format!("Failed to move key file: {}", e)))?;
+        if let Err(e) = fs::rename(enc_path(&from_base), enc_path(to_base)) {
+            let _ = fs::rename(key_path(to_base), key_path(&from_base));
+            return Err(GhostError::Io(format!("Failed to move note: {}", e)));
+        }
+        if let Err(e) = rebind_note(notes_dir, rel_path, &new_rel_path, vault) {
+            let _ = fs::rename(enc_path(to_base), enc_path(&from_base));
+            let _ = fs::rename(key_path(to_base), key_path(&from_base));
+            return Err(e);
+        }
+    } else {
+        fs::rename(&from_base, to_base)
+            .map_err(|e| GhostError::Io(format!("Failed to move note: {}", e)))?;
+    }
+
+    // Sidecar metadata and history travel with the note
+    let _ = fs::rename(meta_path(&from_base), meta_path(to_base));
+    let _ = relocate_history(notes_dir, rel_path, &new_rel_path, vault);
+
+    let mut hashes = state.content_hashes.lock().unwrap();
+    if let Some(hash) = hashes.remove(rel_path) {
+        hashes.insert(new_rel_path.clone(), hash);
+    }
+    drop(hashes);
+    reindex_moved_note(state, rel_path, &new_rel_path);
+
+    Ok(new_rel_path)
+}
+
+/// Replace the first `# ` heading, or add one if the note has none
+fn replace_heading(content: &str, title: &str) -> String {
+    let heading = format!("# {}", title);
+    let mut replaced = false;
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            if !replaced && line.trim().starts_with("# ") {
+                replaced = true;
+                heading.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    if !replaced {
+        lines.insert(0, heading);
+        lines.insert(1, String::new());
+    }
+
+    let mut updated = lines.join("\n");
+    if content.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated
+}
+
+/// Mark a note read-only (or writable again)
+///
+/// Read-only notes can still be read, but saving or deleting them fails
+/// until the flag is cleared.
+#[tauri::command]
+pub fn set_note_readonly(
+    path: String,
+    readonly: bool,
+    state: State<AppState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    if !note_file_path(&notes_dir, &path).exists() {
+        return Err(GhostError::NotFound(format!("Note '{}' not found", path)));
+    }
+
+    let mut sidecar = read_sidecar(&notes_dir, &path);
+    sidecar.readonly = readonly;
+    write_sidecar(&notes_dir, &path, &sidecar)
+}
+
+/// Lock a note's file name so renames and `resync_filenames` leave it be
+/// (or unlock it again)
+#[tauri::command]
+pub fn set_filename_locked(
+    path: String,
+    locked: bool,
+    state: State<AppState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    if !note_file_path(&notes_dir, &path).exists() {
+        return Err(GhostError::NotFound(format!("Note '{}' not found", path)));
+    }
+
+    let mut sidecar = read_sidecar(&notes_dir, &path);
+    sidecar.lock_filename = locked;
+    write_sidecar(&notes_dir, &path, &sidecar)
+}
+
+/// Set whether a note's content is compressed, rewriting it to match
+#[tauri::command]
+pub fn set_note_compression(
+    path: String,
+    compression: NoteCompression,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    ensure_writable(&notes_dir, &path)?;
+    if !is_encrypted(&notes_dir, &path) {
+        return Err(if note_file_path(&notes_dir, &path).is_file() {
+            GhostError::Invalid(format!("Note '{}' is not encrypted", path))
+        } else {
+            GhostError::NotFound(format!("Note '{}' not found", path))
+        });
+    }
+
+    let mut sidecar = read_sidecar(&notes_dir, &path);
+    sidecar.compression = compression;
+    write_sidecar(&notes_dir, &path, &sidecar)?;
+
+    let (dek, decrypted) = decrypt_note_bytes(&notes_dir.join(&path), &path, &vault)?;
+    record_own_write(&state, &path);
+    encrypt_note_files(&notes_dir.join(&path), &path, &decrypted, &vault, Some(dek))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResyncedNote {
+    pub from: String,
+    pub to: String,
+}
+
+/// Whether the note `rel_path` is already named after `slug`, allowing for
+/// the `-N` suffix `unique_note_path` adds on a clash
+fn named_after(rel_path: &str, slug: &str) -> bool {
+    let name = note_slug(rel_path);
+    name == slug
+        || name.strip_prefix(slug).is_some_and(|rest| {
+            rest.strip_prefix('-')
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+/// Rename every note in `folder` and below whose file name no longer
+/// matches its title, returning the renames
+///
+/// Notes with a locked file name or marked read-only are skipped, as are
+/// notes that can't be read. Links to renamed notes aren't rewritten.
+#[tauri::command]
+pub fn resync_filenames(
+    folder: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<ResyncedNote>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder = checked_note_path(&notes_dir, folder.as_deref().unwrap_or(""))?;
+    let folder_path = notes_dir.join(&folder);
+
+    let mut renamed = Vec::new();
+    for file_path in walk_note_files(&folder_path) {
+        let rel_path = rel_note_path(&notes_dir, &file_path);
+        let sidecar = read_sidecar(&notes_dir, &rel_path);
+        if sidecar.lock_filename || sidecar.readonly {
+            continue;
+        }
+        let Ok(content) = read_note_content(&notes_dir, &rel_path, &vault) else {
+            continue;
+        };
+        let content = Zeroizing::new(content);
+        let title = extract_title(&content, &file_path);
+        let slug = slugify(&title);
+        if slug.is_empty() || named_after(&rel_path, &slug) {
+            continue;
+        }
+
+        let to_base = retitled_base(&notes_dir, &rel_path, &title)?;
+        let to = relocate_note(&notes_dir, &rel_path, &to_base, &state, &vault)?;
+        renamed.push(ResyncedNote { from: rel_path, to });
+    }
+
+    if !renamed.is_empty() {
+        vault.bump_content_version();
+    }
+    Ok(renamed)
+}
+
+#[tauri::command]
+pub fn create_folder(
+    name: String,
+    parent: Option<String>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let name = normalize_note_path(&name)?;
+    let parent = parent.map(|p| normalize_note_path(&p)).transpose()?;
+
+    let folder_path = if let Some(parent_path) = parent {
+        resolve_safe(&notes_dir, &format!("{}/{}", parent_path, name))?
+    } else {
+        resolve_safe(&notes_dir, &name)?
+    };
+
+    fs::create_dir_all(&folder_path)?;
+    vault.bump_content_version();
+
+    let rel_path = folder_path.strip_prefix(&notes_dir).unwrap_or(&folder_path);
+    Ok(rel_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn delete_folder(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    if path.is_empty() {
+        return Err(GhostError::Invalid(
+            "Cannot delete the notes folder itself".to_string(),
+        ));
+    }
+    let full_path = notes_dir.join(&path);
+
+    // Recursively delete folder and all contents
+    fs::remove_dir_all(&full_path)?;
+    // Every note below it changed id; let the index rebuild
+    clear_index(&state);
+    vault.bump_content_version();
+    Ok(())
+}
+
+/// Rename a folder in place
+///
+/// With `update_links`, links that name a note inside it by id are rewritten
+/// to its new id. This re-encrypts every note with such a link, so it is
+/// opt-in.
+#[tauri::command]
+pub fn rename_folder(
+    old_path: String,
+    new_name: String,
+    update_links: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Renamed, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let old_path = checked_note_path(&notes_dir, &old_path)?;
+    let new_name = normalize_note_path(&new_name)?;
+
+    // Get parent directory
+    let parent = Path::new(&old_path).parent()
+        .ok_or_else(|| GhostError::Invalid("Invalid folder path".to_string()))?;
+
+    let new_full_path = resolve_safe(&notes_dir, &parent.join(&new_name).to_string_lossy())?;
+
+    // Check if target already exists
+    if new_full_path.exists() {
+        return Err(GhostError::Invalid(format!(
+            "A folder named '{}' already exists",
+            new_name
+        )));
+    }
+
+    let new_rel = relocate_folder(&notes_dir, &old_path, &new_full_path, &state, &vault)?;
+    let relinked = if update_links.unwrap_or(false) {
+        let old_prefix = format!("{}/", old_path);
+        relink_references(&notes_dir, &state, &vault, |target| {
+            target
+                .strip_prefix(&old_prefix)
+                .map(|rest| format!("{}/{}", new_rel, rest))
+        })?
+    } else {
+        Vec::new()
+    };
+
+    vault.bump_content_version();
+    Ok(Renamed {
+        path: new_rel,
+        relinked,
+    })
+}
+
+/// Move a folder under a different parent, returning its new relative path
+///
+/// `new_parent` is relative to the notes folder; an empty string means the
+/// top level. A folder can't be moved into itself or one of its subfolders.
+#[tauri::command]
+pub fn move_folder(
+    path: String,
+    new_parent: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    let new_parent = checked_note_path(&notes_dir, &new_parent)?;
+    if path.is_empty() {
+        return Err(GhostError::Invalid(
+            "Cannot move the notes folder itself".to_string(),
+        ));
+    }
+    if is_reserved_dir(&notes_dir, Path::new(&path))
+        || is_reserved_dir(&notes_dir, Path::new(&new_parent))
+    {
+        return Err(GhostError::Invalid(
+            "Cannot move folders into or out of the vault, trash, history, attachments or \
+             templates folder"
+                .to_string(),
+        ));
+    }
+    if Path::new(&new_parent).starts_with(&path) {
+        return Err(GhostError::Invalid(format!(
+            "Cannot move '{}' into itself or one of its subfolders",
+            path
+        )));
+    }
+
+    let old_full_path = notes_dir.join(&path);
+    if !old_full_path.is_dir() {
+        return Err(GhostError::NotFound(format!("Folder '{}' not found", path)));
+    }
+    let name = old_full_path
+        .file_name()
+        .ok_or_else(|| GhostError::Invalid("Invalid folder path".to_string()))?;
+
+    let parent_path = notes_dir.join(&new_parent);
+    let new_full_path = parent_path.join(name);
+    if new_full_path == old_full_path {
+        return Ok(path);
+    }
+    if new_full_path.exists() {
+        return Err(GhostError::Invalid(format!(
+            "A folder named '{}' already exists in '{}'",
+            name.to_string_lossy(),
+            new_parent
+        )));
+    }
+    fs::create_dir_all(&parent_path)?;
+
+    let new_rel = relocate_folder(&notes_dir, &path, &new_full_path, &state, &vault)?;
+    vault.bump_content_version();
+    Ok(new_rel)
+}
+
+/// Move a directory, copying it when the destination is on another volume
+fn move_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_dir(from, to) {
+                let _ = fs::remove_dir_all(to);
+                return Err(e);
+            }
+            fs::remove_dir_all(from)
+        }
+        result => result,
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+        if entry.file_type().is_dir() {
+            fs::create_dir(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move a folder to `new_full_path` and bind every encrypted note inside to
+/// its new id, putting everything back if any note fails
+fn relocate_folder(
+    notes_dir: &Path,
+    old_path: &str,
+    new_full_path: &Path,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<String, GhostError> {
+    let old_full_path = notes_dir.join(old_path);
+    move_dir(&old_full_path, new_full_path)?;
+    // Every note below it changed id; let the index rebuild
+    clear_index(state);
+
+    let new_rel = rel_note_path(notes_dir, new_full_path);
+    let mut rebound: Vec<(String, String)> = Vec::new();
+    for file_path in walk_note_files(new_full_path) {
+        if file_path.extension().map(|ext| ext != "enc").unwrap_or(true) {
+            continue;
+        }
+        let note_rel = rel_note_path(notes_dir, &file_path);
+        let suffix = Path::new(&note_rel).strip_prefix(&new_rel).unwrap_or(Path::new(&note_rel));
+        let old_note_rel = Path::new(old_path).join(suffix).to_string_lossy().to_string();
+
+        if let Err(e) = rebind_note(notes_dir, &old_note_rel, &note_rel, vault) {
+            // Put everything back the way it was
+            let _ = move_dir(new_full_path, &old_full_path);
+            for (old_rel, new_rel) in &rebound {
+                let _ = rebind_note(notes_dir, new_rel, old_rel, vault);
+            }
+            return Err(e);
+        }
+        rebound.push((old_note_rel, note_rel));
+    }
+    for (old_note_rel, note_rel) in &rebound {
+        let _ = relocate_history(notes_dir, old_note_rel, note_rel, vault);
+    }
+
+    Ok(new_rel)
+}
+
+/// Set the folder new notes are created in when no folder is given
+#[tauri::command]
+pub fn set_current_folder(path: String, state: State<AppState>) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    if !notes_dir.join(&path).is_dir() {
+        return Err(GhostError::NotFound(format!(
+            "Folder '{}' does not exist",
+            path
+        )));
+    }
+
+    *state.current_folder.lock().unwrap() = path;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_current_folder(state: State<AppState>) -> String {
+    state.current_folder.lock().unwrap().clone()
+}
+
+/// Whether a note exists, encrypted or legacy, without reading it
+///
+/// Anything in a reserved folder such as the trash doesn't count.
+#[tauri::command]
+pub fn note_exists(path: String, state: State<AppState>) -> Result<bool, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+    Ok(!is_reserved_dir(&notes_dir, Path::new(&path)) && note_exists_in(&notes_dir, &path))
+}
+
+/// Whether a folder exists; the notes folder itself (`""`) always does
+#[tauri::command]
+pub fn folder_exists(path: String, state: State<AppState>) -> Result<bool, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let folder_path = resolve_safe(&notes_dir, &path)?;
+    Ok(!is_reserved_dir(&notes_dir, &folder_path) && folder_path.is_dir())
+}
+
+/// Score a title match is worth in a search with `boost_title`
+const TITLE_MATCH_BOOST: usize = 10;
+
+/// Search every note for `query` by scanning it line by line
+///
+/// With `folder`, only that folder and its subfolders are searched; result
+/// paths stay relative to the notes folder. Each note's plaintext is wiped
+/// as soon as it has been scanned; only the matched lines and their context
+/// go back to the frontend. Notes are scanned in parallel and the results
+/// sorted by path, or with `ranked` by score and then most recently
+/// modified. `boost_title` raises the score of notes whose title matches.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn search_notes(
+    query: String,
+    context_lines: Option<usize>,
+    mode: Option<SearchMode>,
+    case_sensitive: Option<bool>,
+    folder: Option<String>,
+    ranked: Option<bool>,
+    boost_title: Option<bool>,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<SearchResult>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let search_root = resolve_safe(&notes_dir, folder.as_deref().unwrap_or_default())?;
+    let pattern = build_search_pattern(
+        &query,
+        mode.unwrap_or_default(),
+        case_sensitive.unwrap_or(false),
+    )?;
+    let context_lines = context_lines.unwrap_or(0);
+    let ranked = ranked.unwrap_or(false);
+    let boost_title = boost_title.unwrap_or(false);
+
+    // Gather the files first so they can be decrypted and scanned in parallel
+    let ignore = load_ignore(&notes_dir);
+    let files: Vec<PathBuf> = WalkDir::new(&search_root)
+        .into_iter()
+        .filter_entry(|e| {
+            !is_reserved_dir(&notes_dir, e.path())
+                && !is_ignored(&ignore, &notes_dir, e.path(), e.file_type().is_dir())
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext == "enc" || ext == "md" || ext == "txt")
+                .unwrap_or(false)
+        })
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut results: Vec<(SearchResult, Option<SystemTime>)> = files
+        .par_iter()
+        .filter_map(|path| {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+            // Use base path for encrypted files
+            let rel_path = if ext == "enc" {
+                let base_path = path.with_extension("");
+                base_path
+                    .strip_prefix(&notes_dir)
+                    .unwrap_or(&base_path)
+                    .to_string_lossy()
+                    .to_string()
+            } else {
+                path.strip_prefix(&notes_dir)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string()
+            };
+
+            // Get content (encrypted or plain), wiped once this note is scanned
+            let bytes = if ext == "enc" {
+                decrypt_note_bytes(&path.with_extension(""), &rel_path, &vault)
+                    .map(|(_, bytes)| bytes)
+            } else {
+                fs::read(path).map(Zeroizing::new).map_err(GhostError::from)
+            };
+
+            let content = bytes.and_then(|b| Ok(Zeroizing::new(decrypted_str(&b)?.to_string())));
+
+            let result = match content {
+                Ok(content) => {
+                    let matches = find_matches(&content, &pattern, context_lines);
+                    if matches.is_empty() {
+                        return None;
+                    }
+                    let title = extract_title(&content, path);
+                    let mut score = count_matches(&content, &pattern);
+                    if boost_title && pattern.is_match(&title) {
+                        score += TITLE_MATCH_BOOST;
+                    }
+                    SearchResult {
+                        path: rel_path,
+                        title,
+                        matches,
+                        corrupted: false,
+                        error: None,
+                        score,
+                    }
+                }
+                Err(GhostError::VaultLocked) => return None,
+                // Report notes that can't be read rather than skipping them
+                Err(e) => SearchResult {
+                    path: rel_path,
+                    title: extract_title("", path),
+                    matches: Vec::new(),
+                    corrupted: true,
+                    error: Some(e.to_string()),
+                    score: 0,
+                },
+            };
+            let modified = ranked
+                .then(|| fs::metadata(path).and_then(|m| m.modified()).ok())
+                .flatten();
+            Some((result, modified))
+        })
+        .collect();
+
+    // Threads finish in any order
+    results.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+    if ranked {
+        // Stable, so notes that tie on both stay in path order
+        results.sort_by(|(a, a_modified), (b, b_modified)| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b_modified.cmp(a_modified))
+        });
+    }
+    Ok(results.into_iter().map(|(result, _)| result).collect())
+}
+
+/// Find notes whose modification time is in the future or implausibly old
+#[tauri::command]
+pub fn find_timestamp_anomalies(
+    state: State<AppState>,
+) -> Result<Vec<TimestampAnomaly>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let now = to_unix(SystemTime::now());
+
+    let mut anomalies = Vec::new();
+
+    for path in walk_note_files(&notes_dir) {
+        let modified = match fs::metadata(&path).ok().and_then(|m| m.modified().ok()) {
+            Some(time) => to_unix(time),
+            None => continue,
+        };
+
+        let kind = if modified > now + FUTURE_TOLERANCE_SECS {
+            AnomalyKind::InFuture
+        } else if modified < SANE_EPOCH_UNIX {
+            AnomalyKind::BeforeEpoch
+        } else {
+            continue;
+        };
+
+        anomalies.push(TimestampAnomaly {
+            path: rel_note_path(&notes_dir, &path),
+            modified_unix: modified,
+            kind,
+        });
+    }
+
+    Ok(anomalies)
+}
+
+/// Overwrite a note's modification time (e.g. to correct clock skew)
+#[tauri::command]
+pub fn fix_timestamp(
+    path: String,
+    new_unix: i64,
+    state: State<AppState>,
+) -> Result<(), GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let path = checked_note_path(&notes_dir, &path)?;
+
+    if new_unix < SANE_EPOCH_UNIX {
+        return Err(GhostError::Invalid(
+            "Timestamp is before 2000-01-01".to_string(),
+        ));
+    }
+    let new_time = UNIX_EPOCH + Duration::from_secs(new_unix as u64);
 
-    Ok(rel_path)
+    let file_path = note_file_path(&notes_dir, &path);
+
+    let file = fs::File::options().write(true).open(&file_path)?;
+    file.set_modified(new_time).map_err(GhostError::from)
 }
 
+/// List notes whose on-disk content differs from what we last read or saved
+///
+/// Notes that haven't been read since startup have no baseline and are
+/// skipped; notes that have since disappeared are dropped from tracking.
 #[tauri::command]
-pub fn delete_note(path: String, state: State<AppState>) -> Result<(), String> {
+pub fn notes_changed_externally(
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<String>, GhostError> {
     let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let baselines: Vec<(String, blake3::Hash)> = state
+        .content_hashes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(path, hash)| (path.clone(), *hash))
+        .collect();
 
-    // Check if encrypted
-    if is_encrypted(&notes_dir, &path) {
-        delete_encrypted_note(&notes_dir, &path)
-    } else {
-        // Legacy unencrypted delete
-        let full_path = notes_dir.join(&path);
-        fs::remove_file(full_path).map_err(|e| e.to_string())
+    let mut changed = Vec::new();
+
+    for (path, hash) in baselines {
+        if !note_exists_in(&notes_dir, &path) {
+            state.content_hashes.lock().unwrap().remove(&path);
+            continue;
+        }
+
+        let content = read_note_content(&notes_dir, &path, &vault)?;
+        if blake3::hash(content.as_bytes()) != hash {
+            changed.push(path);
+        }
     }
+
+    changed.sort();
+    Ok(changed)
 }
 
+/// Count notes created and modified per day over the last `days` days
+///
+/// Uses filename dates and filesystem timestamps only, so nothing is decrypted.
 #[tauri::command]
-pub fn create_folder(name: String, parent: Option<String>, state: State<AppState>) -> Result<String, String> {
+pub fn get_activity_heatmap(
+    days: u32,
+    state: State<AppState>,
+) -> Result<Vec<ActivityDay>, GhostError> {
+    use chrono::{DateTime, Days, Local};
+    use std::collections::HashMap;
+
+    if days == 0 {
+        return Ok(Vec::new());
+    }
+
     let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let today = Local::now().date_naive();
+    let first_day = today
+        .checked_sub_days(Days::new(days as u64 - 1))
+        .ok_or_else(|| GhostError::Invalid("Too many days requested".to_string()))?;
 
-    let folder_path = if let Some(parent_path) = parent {
-        notes_dir.join(&parent_path).join(&name)
-    } else {
-        notes_dir.join(&name)
-    };
+    let mut counts: HashMap<chrono::NaiveDate, (usize, usize)> = HashMap::new();
 
-    fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+    for path in walk_note_files(&notes_dir) {
+        if let Some(created) = note_created_date(&path) {
+            counts.entry(created).or_default().0 += 1;
+        }
 
-    let rel_path = folder_path.strip_prefix(&notes_dir).unwrap_or(&folder_path);
-    Ok(rel_path.to_string_lossy().to_string())
+        if let Some(modified) = fs::metadata(&path).ok().and_then(|m| m.modified().ok()) {
+            let modified: DateTime<Local> = modified.into();
+            counts.entry(modified.date_naive()).or_default().1 += 1;
+        }
+    }
+
+    Ok(first_day
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| {
+            let (created, modified) = counts.get(&day).copied().unwrap_or_default();
+            ActivityDay {
+                date: day.format("%Y-%m-%d").to_string(),
+                created,
+                modified,
+            }
+        })
+        .collect())
+}
+
+const REFERENCE_URI_PREFIX: &str = "gn://note/";
+
+/// New id for a note file: its creation time keyed with the vault salt
+///
+/// The salt keeps ids from different vaults from colliding or being
+/// guessable. Saving replaces a note's files, and with them the creation
+/// time, so the id is kept in the sidecar once handed out.
+fn note_reference_id(file_path: &Path, salt: &[u8]) -> Result<String, GhostError> {
+    let created = fs::metadata(file_path)
+        .and_then(|m| m.created())
+        .map_err(|e| GhostError::Io(format!("Creation time unavailable: {}", e)))?;
+    let nanos = created
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(salt);
+    hasher.update(&nanos.to_le_bytes());
+    Ok(hasher.finalize().to_hex()[..32].to_string())
 }
 
+/// Get a stable external reference (id and gn:// URI) for a note
 #[tauri::command]
-pub fn delete_folder(path: String, state: State<AppState>) -> Result<(), String> {
+pub fn get_note_reference(
+    path: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<NoteReference, GhostError> {
     let notes_dir = state.notes_dir.lock().unwrap().clone();
-    let full_path = notes_dir.join(&path);
+    let path = checked_note_path(&notes_dir, &path)?;
+    let salt = read_salt(&vault.config()?)?;
 
-    // Recursively delete folder and all contents
-    fs::remove_dir_all(&full_path).map_err(|e| e.to_string())
+    let file_path = note_file_path(&notes_dir, &path);
+    if !file_path.exists() {
+        return Err(GhostError::NotFound(format!("Note '{}' not found", path)));
+    }
+
+    let mut sidecar = read_sidecar(&notes_dir, &path);
+    let id = match &sidecar.reference {
+        Some(id) => id.clone(),
+        None => {
+            let id = note_reference_id(&file_path, &salt)?;
+            sidecar.reference = Some(id.clone());
+            write_sidecar(&notes_dir, &path, &sidecar)?;
+            id
+        }
+    };
+    Ok(NoteReference {
+        uri: format!("{}{}", REFERENCE_URI_PREFIX, id),
+        id,
+    })
 }
 
+/// Resolve a gn:// URI (or bare id) to the note's current path
 #[tauri::command]
-pub fn rename_folder(old_path: String, new_name: String, state: State<AppState>) -> Result<String, String> {
+pub fn resolve_reference(
+    uri: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<String, GhostError> {
     let notes_dir = state.notes_dir.lock().unwrap().clone();
-    let old_full_path = notes_dir.join(&old_path);
+    let salt = read_salt(&vault.config()?)?;
+    let id = uri.strip_prefix(REFERENCE_URI_PREFIX).unwrap_or(&uri);
 
-    // Get parent directory
-    let parent = old_full_path.parent()
-        .ok_or_else(|| "Invalid folder path".to_string())?;
+    walk_note_files(&notes_dir)
+        .into_iter()
+        .map(|file| (rel_note_path(&notes_dir, &file), file))
+        .find(
+            |(rel_path, file)| match read_sidecar(&notes_dir, rel_path).reference {
+                Some(reference) => reference == id,
+                None => note_reference_id(file, &salt).is_ok_and(|i| i == id),
+            },
+        )
+        .map(|(rel_path, _)| rel_path)
+        .ok_or_else(|| GhostError::NotFound("No note matches this reference".to_string()))
+}
 
-    let new_full_path = parent.join(&new_name);
+/// Whether any form of the note (encrypted or legacy) exists
+fn note_exists_in(notes_dir: &Path, rel_path: &str) -> bool {
+    note_file_path(notes_dir, rel_path).is_file()
+}
 
-    // Check if target already exists
-    if new_full_path.exists() {
-        return Err(format!("A folder named '{}' already exists", new_name));
+/// Collect stored references that point at notes or folders that are gone
+fn find_dangling_references(notes_dir: &Path, state: &AppState) -> Vec<DanglingReference> {
+    let mut dangling = Vec::new();
+
+    let current_folder = state.current_folder.lock().unwrap().clone();
+    if !notes_dir.join(&current_folder).is_dir() {
+        dangling.push(DanglingReference {
+            kind: DerivedStateKind::CurrentFolder,
+            reference: current_folder,
+        });
     }
 
-    fs::rename(&old_full_path, &new_full_path).map_err(|e| e.to_string())?;
+    let mut hashed: Vec<String> = state
+        .content_hashes
+        .lock()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    hashed.sort();
+    for path in hashed {
+        if !note_exists_in(notes_dir, &path) {
+            dangling.push(DanglingReference {
+                kind: DerivedStateKind::ContentHash,
+                reference: path,
+            });
+        }
+    }
 
-    let rel_path = new_full_path.strip_prefix(&notes_dir).unwrap_or(&new_full_path);
-    Ok(rel_path.to_string_lossy().to_string())
+    for entry in WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path().starts_with(notes_dir.join(".vault")))
+        .filter(|e| e.path().extension().map(|ext| ext == "meta").unwrap_or(false))
+    {
+        let base = entry.path().with_extension("");
+        let has_note = ["enc", "md", "txt"]
+            .iter()
+            .any(|ext| with_note_extension(&base, ext).is_file());
+        if !has_note {
+            dangling.push(DanglingReference {
+                kind: DerivedStateKind::Sidecar,
+                reference: entry
+                    .path()
+                    .strip_prefix(notes_dir)
+                    .unwrap_or(entry.path())
+                    .to_string_lossy()
+                    .to_string(),
+            });
+        }
+    }
+
+    let recents = read_recents(&VaultConfig::new(&notes_dir.to_path_buf()));
+    let mut indexed = indexed_note_ids(state);
+    indexed.sort();
+    let linked: Vec<String> = state
+        .link_graph
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|graph| graph.notes.iter().map(|note| note.path.clone()).collect())
+        .unwrap_or_default();
+    for (kind, ids) in [
+        (DerivedStateKind::RecentNote, recents),
+        (DerivedStateKind::IndexedNote, indexed),
+        (DerivedStateKind::LinkedNote, linked),
+    ] {
+        for id in ids {
+            if !note_exists_in(notes_dir, &id) {
+                dangling.push(DanglingReference {
+                    kind,
+                    reference: id,
+                });
+            }
+        }
+    }
+
+    dangling
 }
 
+/// Report derived state (current folder, change-tracking hashes, sidecars,
+/// recent notes, the search index and link graph) that refers to notes or
+/// folders which no longer exist
 #[tauri::command]
-pub fn search_notes(
-    query: String,
+pub fn audit_derived_state(state: State<AppState>) -> Result<Vec<DanglingReference>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    Ok(find_dangling_references(&notes_dir, &state))
+}
+
+/// Remove the dangling references `audit_derived_state` reports
+///
+/// Returns what was pruned.
+#[tauri::command]
+pub fn prune_derived_state(state: State<AppState>) -> Result<Vec<DanglingReference>, GhostError> {
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let dangling = find_dangling_references(&notes_dir, &state);
+
+    for item in &dangling {
+        match item.kind {
+            DerivedStateKind::CurrentFolder => state.current_folder.lock().unwrap().clear(),
+            DerivedStateKind::ContentHash => {
+                state.content_hashes.lock().unwrap().remove(&item.reference);
+            }
+            DerivedStateKind::Sidecar => {
+                fs::remove_file(notes_dir.join(&item.reference)).map_err(|e| {
+                    GhostError::Io(format!("Failed to remove {}: {}", item.reference, e))
+                })?;
+            }
+            DerivedStateKind::RecentNote => {
+                let config = VaultConfig::new(&notes_dir);
+                let mut recents = read_recents(&config);
+                recents.retain(|id| id != &item.reference);
+                write_recents(&config, &recents)?;
+            }
+            DerivedStateKind::IndexedNote => unindex_note(&state, &item.reference),
+            DerivedStateKind::LinkedNote => {
+                if let Some(graph) = state.link_graph.lock().unwrap().as_mut() {
+                    graph.notes.retain(|note| note.path != item.reference);
+                }
+            }
+        }
+    }
+
+    Ok(dangling)
+}
+
+/// Check one `.enc` file's key and content without changing anything
+///
+/// Fails only if the vault locks partway through.
+fn check_encrypted_file(
+    notes_dir: &Path,
+    enc_file: &Path,
+    vault: &VaultState,
+) -> Result<IntegrityEntry, GhostError> {
+    let base_path = enc_file.with_extension("");
+    let rel_base = base_path.strip_prefix(notes_dir).unwrap_or(&base_path);
+    let path = rel_note_path(notes_dir, enc_file);
+    let entry = |status, error: Option<String>| IntegrityEntry {
+        path: path.clone(),
+        status,
+        error,
+    };
+
+    // Under the note's lock, so a save in progress isn't reported as damage
+    vault.with_note_lock(&base_path, false, || {
+        let wrapped_dek = match fs::read(key_path(&base_path)) {
+            Ok(wrapped) => wrapped,
+            Err(e) => {
+                let error = format!("Failed to read key file: {}", e);
+                return Ok(entry(IntegrityStatus::MissingKey, Some(error)));
+            }
+        };
+        let dek = match vault.with_kek(|kek| unwrap_dek(kek, &wrapped_dek)) {
+            Ok(dek) => dek,
+            Err(GhostError::VaultLocked) => return Err(GhostError::VaultLocked),
+            Err(e) => return Ok(entry(IntegrityStatus::UnwrapFailed, Some(e.to_string()))),
+        };
+        let content = match decrypt_bound_file(&dek, enc_file, &bound_note_id(rel_base)) {
+            Ok(content) => content,
+            Err(e) => return Ok(entry(IntegrityStatus::DecryptFailed, Some(e.to_string()))),
+        };
+
+        // Attachments hold arbitrary bytes, not text
+        if !rel_base.starts_with(ATTACHMENTS_DIR) {
+            if let Err(e) = decrypted_str(&content) {
+                return Ok(entry(IntegrityStatus::InvalidUtf8, Some(e.to_string())));
+            }
+        }
+        Ok(entry(IntegrityStatus::Ok, None))
+    })
+}
+
+/// Try to decrypt every encrypted file in the vault and report which can't
+/// be read, and why
+///
+/// Covers notes, the trash, note history and attachments. Nothing is
+/// modified. Files are checked in parallel since decryption is CPU-bound.
+#[tauri::command]
+pub fn verify_vault_integrity(
     state: State<AppState>,
     vault: State<VaultState>,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<IntegrityReport, GhostError> {
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
     let notes_dir = state.notes_dir.lock().unwrap().clone();
-    let query_lower = query.to_lowercase();
-
-    let mut results = Vec::new();
 
+    let mut enc_files = Vec::new();
+    let mut orphan_keys = Vec::new();
     for entry in WalkDir::new(&notes_dir)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| {
-            e.path()
-                .extension()
-                .map(|ext| ext == "enc" || ext == "md" || ext == "txt")
-                .unwrap_or(false)
-        })
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !e.path().starts_with(notes_dir.join(".vault")))
     {
         let path = entry.path();
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("enc") => enc_files.push(path.to_path_buf()),
+            Some("key") if !enc_path(path).is_file() => {
+                orphan_keys.push(rel_note_path(&notes_dir, &path.with_extension("")));
+            }
+            _ => {}
+        }
+    }
 
-        // Get content (encrypted or plain)
-        let content = if ext == "enc" {
-            let base_path = path.with_extension("");
-            let rel_base = base_path
-                .strip_prefix(&notes_dir)
-                .unwrap_or(&base_path)
-                .to_string_lossy()
-                .to_string();
-            read_encrypted_note(&notes_dir, &rel_base, &vault).ok()
-        } else {
-            fs::read_to_string(path).ok()
+    let mut files = enc_files
+        .par_iter()
+        .map(|enc_file| check_encrypted_file(&notes_dir, enc_file, &vault))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    orphan_keys.sort();
+
+    Ok(IntegrityReport { files, orphan_keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::TestVault;
+
+    #[tokio::test]
+    async fn open_note_populates_every_field() {
+        let vault = TestVault::new().await;
+        let content = "---\ntags: [draft, rust]\n---\n# Plan\n\nFirst write the tests.\n\n## Steps\n\n- one\n- two\n";
+        vault.write("projects/plan", content);
+
+        let opened = open_note(
+            "projects/plan".to_string(),
+            None,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+
+        assert_eq!(opened.path, "projects/plan");
+        assert_eq!(opened.content, content);
+        assert_eq!(opened.content_hash, content_hash(content));
+        assert_eq!(opened.title, "Plan");
+        // The frontmatter's words count along with the prose
+        assert_eq!(opened.word_count, 11);
+        assert_eq!(opened.tags, ["draft", "rust"]);
+        let outline: Vec<_> = opened
+            .outline
+            .iter()
+            .map(|entry| (entry.level, entry.text.as_str()))
+            .collect();
+        assert_eq!(outline, [(1, "Plan"), (2, "Steps")]);
+        assert_ne!(opened.created, "Unknown");
+        assert_ne!(opened.modified, "Unknown");
+        assert!(opened.encrypted);
+        assert!(!opened.readonly);
+        assert!(!opened.had_invalid_utf8);
+    }
+
+    #[test]
+    fn secure_delete_overwrites_once_per_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.txt");
+        let original = b"the launch code is 0000".to_vec();
+        fs::write(&path, &original).unwrap();
+
+        let mut overwrites = Vec::new();
+        shred_file(&path, 3, |pass| {
+            overwrites.push((pass, fs::read(&path).unwrap()));
+        })
+        .unwrap();
+
+        assert_eq!(overwrites.len(), 3);
+        for (i, (pass, bytes)) in overwrites.iter().enumerate() {
+            assert_eq!(*pass as usize, i + 1);
+            assert_eq!(bytes.len(), original.len());
+            assert_ne!(*bytes, original);
+        }
+        assert_ne!(overwrites[0].1, overwrites[1].1);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn future_timestamps_are_flagged_and_fixable() {
+        let vault = TestVault::new().await;
+        vault.write("now", "# Now\n");
+        vault.write("later", "# Later\n");
+
+        let next_year = SystemTime::now() + Duration::from_secs(365 * 24 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(vault.path().join("later.enc"))
+            .unwrap()
+            .set_modified(next_year)
+            .unwrap();
+
+        let anomalies = find_timestamp_anomalies(vault.state()).unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].path, "later");
+        assert!(matches!(anomalies[0].kind, AnomalyKind::InFuture));
+        assert_eq!(anomalies[0].modified_unix, to_unix(next_year));
+
+        let now = to_unix(SystemTime::now());
+        fix_timestamp("later".to_string(), now, vault.state()).unwrap();
+        assert!(find_timestamp_anomalies(vault.state()).unwrap().is_empty());
+
+        let modified = fs::metadata(vault.path().join("later.enc"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(to_unix(modified), now);
+    }
+
+    #[tokio::test]
+    async fn fix_timestamp_rejects_times_before_the_sane_epoch() {
+        let vault = TestVault::new().await;
+        vault.write("note", "# Note\n");
+
+        assert!(fix_timestamp("note".to_string(), 0, vault.state()).is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_export_strips_markdown() {
+        let vault = TestVault::new().await;
+        vault.write(
+            "formatted",
+            "# Title\n\nSome **bold** and _italic_ text with a [link](https://example.com).\n\n\
+             - first\n- second\n  1. nested\n\n```rust\nlet x = 1;\n```\n\n## Next\n\nDone.\n",
+        );
+
+        let plain =
+            export_note_plaintext("formatted".to_string(), vault.state(), vault.vault()).unwrap();
+
+        assert_eq!(
+            plain,
+            "Title\n\nSome bold and italic text with a link.\n\n\
+             - first\n- second\n  1. nested\n\nlet x = 1;\n\nNext\n\nDone.\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn current_folder_must_exist() {
+        let vault = TestVault::new().await;
+        fs::create_dir(vault.path().join("projects")).unwrap();
+
+        set_current_folder("projects".to_string(), vault.state()).unwrap();
+        assert_eq!(get_current_folder(vault.state()), "projects");
+
+        let missing = set_current_folder("nowhere".to_string(), vault.state());
+        assert!(matches!(missing, Err(GhostError::NotFound(_))));
+        assert_eq!(get_current_folder(vault.state()), "projects");
+    }
+
+    #[tokio::test]
+    async fn create_note_defaults_to_the_current_folder() {
+        let vault = TestVault::new().await;
+        fs::create_dir(vault.path().join("projects")).unwrap();
+        set_current_folder("projects".to_string(), vault.state()).unwrap();
+
+        let path = create_note(
+            String::new(),
+            Some("Idea".to_string()),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert!(path.starts_with("projects/"), "{}", path);
+        assert!(vault.path().join(format!("{}.enc", path)).is_file());
+
+        // An explicit folder still wins
+        let path = create_note(
+            "inbox".to_string(),
+            Some("Elsewhere".to_string()),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert!(path.starts_with("inbox/"), "{}", path);
+    }
+
+    #[test]
+    fn messy_paths_normalize_to_canonical_ids() {
+        for (messy, canonical) in [
+            ("inbox/note", "inbox/note"),
+            ("./inbox//note", "inbox/note"),
+            ("inbox/note/", "inbox/note"),
+            ("inbox/./sub/./note", "inbox/sub/note"),
+            ("./", ""),
+            ("", ""),
+        ] {
+            assert_eq!(normalize_note_path(messy).unwrap(), canonical, "{:?}", messy);
+        }
+    }
+
+    #[test]
+    fn parent_and_absolute_paths_are_rejected() {
+        for bad in ["../note", "inbox/../../note", "inbox/..", "/etc/passwd"] {
+            assert!(
+                matches!(normalize_note_path(bad), Err(GhostError::Invalid(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn out_of_band_edits_are_reported() {
+        let vault = TestVault::new().await;
+        vault.write("edited", "# Edited\n");
+        vault.write("untouched", "# Untouched\n");
+        // Written behind the app's back and never read, so there's no baseline
+        write_encrypted_note(vault.path(), "unseen", "# Unseen\n", &vault.vault(), None).unwrap();
+        assert!(notes_changed_externally(vault.state(), vault.vault())
+            .unwrap()
+            .is_empty());
+
+        write_encrypted_note(
+            vault.path(),
+            "edited",
+            "# Edited elsewhere\n",
+            &vault.vault(),
+            None,
+        )
+        .unwrap();
+        write_encrypted_note(
+            vault.path(),
+            "unseen",
+            "# Still unseen\n",
+            &vault.vault(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            notes_changed_externally(vault.state(), vault.vault()).unwrap(),
+            ["edited"]
+        );
+    }
+
+    #[tokio::test]
+    async fn resync_skips_notes_with_locked_filenames() {
+        let vault = TestVault::new().await;
+        vault.write("2020-taxes", "# Tax return\n");
+        vault.write("old-name", "# New name\n");
+        vault.write("in-sync", "# In sync\n");
+        set_filename_locked("2020-taxes".to_string(), true, vault.state()).unwrap();
+
+        let renamed = resync_filenames(None, vault.state(), vault.vault()).unwrap();
+
+        let renamed: Vec<_> = renamed
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str()))
+            .collect();
+        assert_eq!(renamed, [("old-name", "new-name")]);
+        assert!(vault.path().join("2020-taxes.enc").is_file());
+        assert!(vault.path().join("new-name.enc").is_file());
+        assert!(!vault.path().join("old-name.enc").exists());
+        assert!(resync_filenames(None, vault.state(), vault.vault())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn rename_keeps_a_locked_filename() {
+        let vault = TestVault::new().await;
+        vault.write("2020-taxes", "# Taxes\n");
+        set_filename_locked("2020-taxes".to_string(), true, vault.state()).unwrap();
+
+        let renamed = rename_note(
+            "2020-taxes".to_string(),
+            "Tax return".to_string(),
+            Some(true),
+            None,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+
+        assert_eq!(renamed.path, "2020-taxes");
+        let content = read_note_content(vault.path(), "2020-taxes", &vault.vault()).unwrap();
+        assert_eq!(content, "# Tax return\n");
+    }
+
+    #[tokio::test]
+    async fn content_version_bumps_on_mutations_only() {
+        use crate::commands::vault::get_content_version;
+
+        let vault = TestVault::new().await;
+        let version = || async { get_content_version(vault.vault()).await.unwrap() };
+
+        let mut last = version().await;
+        let mut bumped = |now: u64| {
+            assert!(now > last, "{} is not above {}", now, last);
+            last = now;
         };
 
-        if let Some(content) = content {
-            let mut matches = Vec::new();
+        let path = create_note(
+            "inbox".into(),
+            Some("Note".into()),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        bumped(version().await);
+        vault.write(&path, "# Note\n\nEdited\n");
+        bumped(version().await);
+        let folder = create_folder("archive".into(), None, vault.state(), vault.vault()).unwrap();
+        bumped(version().await);
+        let path = move_note(path, folder.clone(), vault.state(), vault.vault()).unwrap();
+        bumped(version().await);
 
-            for (line_num, line) in content.lines().enumerate() {
-                if line.to_lowercase().contains(&query_lower) {
-                    matches.push(SearchMatch {
-                        line_number: line_num + 1,
-                        line_content: line.trim().to_string(),
-                    });
-                }
-            }
+        let before_reads = version().await;
+        read_note(path.clone(), None, vault.state(), vault.vault()).unwrap();
+        open_note(path.clone(), None, vault.state(), vault.vault()).unwrap();
+        list_notes(folder.clone(), vault.state(), vault.vault()).unwrap();
+        get_backlinks(path.clone(), vault.state(), vault.vault()).unwrap();
+        assert_eq!(version().await, before_reads);
 
-            if !matches.is_empty() {
-                // Use base path for encrypted files
-                let rel_path = if ext == "enc" {
-                    let base_path = path.with_extension("");
-                    base_path
-                        .strip_prefix(&notes_dir)
-                        .unwrap_or(&base_path)
-                        .to_string_lossy()
-                        .to_string()
-                } else {
-                    path.strip_prefix(&notes_dir)
-                        .unwrap_or(path)
-                        .to_string_lossy()
-                        .to_string()
-                };
+        delete_note(path, vault.state(), vault.vault()).unwrap();
+        bumped(version().await);
+        delete_folder(folder, vault.state(), vault.vault()).unwrap();
+        let latest = version().await;
+        bumped(latest);
 
-                results.push(SearchResult {
-                    path: rel_path,
-                    title: extract_title(&content, path),
-                    matches,
-                });
-            }
+        // A restart carries on from the persisted version
+        let restarted = VaultState::new();
+        restarted.set_config(VaultConfig::new(&vault.path().to_path_buf()));
+        assert!(restarted.content_version() >= latest);
+    }
+
+    #[tokio::test]
+    async fn export_with_context_lists_every_backlink() {
+        let vault = TestVault::new().await;
+        vault.write("target", "# Target\n\nThe note everyone cites.\n");
+        vault.write(
+            "first",
+            "# First\n\nSee [[Target]] for details.\nUnrelated line.\n",
+        );
+        vault.write("second", "# Second\n\n- builds on [[target|the target]]\n");
+        vault.write("loner", "# Loner\n\nLinks to [[first]] only.\n");
+
+        let dest = export_note_with_context(
+            "target".to_string(),
+            vault.outside("export").to_string_lossy().to_string(),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        let exported = fs::read_to_string(dest).unwrap();
+
+        assert!(exported.starts_with("# Target\n\nThe note everyone cites.\n"));
+        assert!(exported.contains("### First (`first`)\n\n> See [[Target]] for details.\n"));
+        assert!(exported.contains("### Second (`second`)\n\n> - builds on [[target|the target]]\n"));
+        assert!(!exported.contains("Unrelated line"));
+        assert!(!exported.contains("Loner"));
+    }
+
+    #[tokio::test]
+    async fn incompressible_content_is_stored_uncompressed() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+        use rand::RngCore;
+
+        let vault = TestVault::new().await;
+        let mut blob = vec![0u8; 48 * 1024];
+        rand::thread_rng().fill_bytes(&mut blob);
+        let embedded = format!(
+            "# Scan\n\n![scan](data:image/png;base64,{})\n",
+            BASE64.encode(&blob)
+        );
+        let prose = "The quick brown fox jumps over the lazy dog.\n".repeat(500);
+        vault.write("embedded", &embedded);
+        vault.write("prose", &prose);
+
+        // Tag byte, nonce and authentication tag around the plaintext as is
+        let stored_len = |id: &str| {
+            fs::metadata(vault.path().join(format!("{}.enc", id)))
+                .unwrap()
+                .len() as usize
+        };
+        assert_eq!(stored_len("embedded"), 1 + 12 + embedded.len() + 16);
+        assert!(stored_len("prose") < prose.len() / 4);
+
+        let read = |id: &str| read_note(id.to_string(), None, vault.state(), vault.vault()).unwrap().content;
+        assert_eq!(read("embedded"), embedded);
+        assert_eq!(read("prose"), prose);
+
+        set_note_compression(
+            "prose".to_string(),
+            NoteCompression::Off,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert_eq!(stored_len("prose"), 1 + 12 + prose.len() + 16);
+        assert_eq!(read("prose"), prose);
+
+        // Like rekey_note, only writable encrypted notes can be rewritten
+        fs::write(vault.path().join("legacy.md"), "# Legacy\n").unwrap();
+        set_note_readonly("embedded".to_string(), true, vault.state()).unwrap();
+        let compress = |id: &str| {
+            set_note_compression(
+                id.to_string(),
+                NoteCompression::Auto,
+                vault.state(),
+                vault.vault(),
+            )
+        };
+        assert!(matches!(compress("legacy.md"), Err(GhostError::Invalid(_))));
+        assert!(matches!(compress("missing"), Err(GhostError::NotFound(_))));
+        assert!(matches!(
+            compress("embedded"),
+            Err(GhostError::NoteReadOnly(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn heatmap_counts_notes_per_day() {
+        use chrono::{Days, Local, NaiveDate, TimeZone};
+
+        let vault = TestVault::new().await;
+        let today = Local::now().date_naive();
+        let day = |back: u64| today.checked_sub_days(Days::new(back)).unwrap();
+        let touch = |id: &str, date: NaiveDate| {
+            let noon = Local
+                .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+                .unwrap();
+            fs::File::options()
+                .write(true)
+                .open(vault.path().join(format!("{}.enc", id)))
+                .unwrap()
+                .set_modified(noon.into())
+                .unwrap();
+        };
+
+        let (a, b, c) = (
+            format!("{}-alpha", day(1)),
+            format!("{}-beta", day(1)),
+            format!("{}-gamma", day(3)),
+        );
+        for id in [&a, &b, &c] {
+            vault.write(id, "# Note\n");
+        }
+        touch(&a, day(1));
+        touch(&b, today);
+        touch(&c, day(3));
+
+        let heatmap = get_activity_heatmap(7, vault.state()).unwrap();
+        let counts: Vec<_> = heatmap
+            .iter()
+            .map(|d| (d.date.as_str(), d.created, d.modified))
+            .collect();
+        let date = |back: u64| day(back).format("%Y-%m-%d").to_string();
+        assert_eq!(
+            counts,
+            [
+                (date(6).as_str(), 0, 0),
+                (date(5).as_str(), 0, 0),
+                (date(4).as_str(), 0, 0),
+                (date(3).as_str(), 1, 1),
+                (date(2).as_str(), 0, 0),
+                (date(1).as_str(), 2, 1),
+                (date(0).as_str(), 0, 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_sharing_tags_rank_above_unrelated_ones() {
+        let vault = TestVault::new().await;
+        vault.write("base", "---\ntags: [rust, async]\n---\n# Base\n\nSee [[target]].\n");
+        vault.write("same", "---\ntags: [async, rust]\n---\n# Same\n");
+        vault.write("partial", "---\ntags: [rust, web]\n---\n# Partial\n");
+        vault.write("unrelated", "---\ntags: [cooking]\n---\n# Unrelated\n");
+        vault.write("linker", "# Linker\n\nAlso about [[target]].\n");
+        vault.write("target", "# Target\n");
+
+        let related =
+            find_related_notes("base".to_string(), 10, vault.state(), vault.vault()).unwrap();
+        let ranked: Vec<_> = related
+            .iter()
+            .map(|note| (note.path.as_str(), note.shared_tags.len()))
+            .collect();
+        assert_eq!(ranked, [("same", 2), ("partial", 1), ("linker", 0)]);
+        assert_eq!(related[0].score, 1.0);
+        assert!((related[1].score - 1.0 / 3.0).abs() < 1e-9);
+
+        let top = find_related_notes("base".to_string(), 1, vault.state(), vault.vault()).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].title, "Same");
+    }
+
+    #[tokio::test]
+    async fn references_follow_a_note_through_saves_and_moves() {
+        let vault = TestVault::new().await;
+        vault.write("inbox/idea", "# Idea\n");
+        vault.write("inbox/other", "# Other\n");
+
+        let reference =
+            get_note_reference("inbox/idea".to_string(), vault.state(), vault.vault()).unwrap();
+        assert_eq!(reference.uri, format!("gn://note/{}", reference.id));
+        let again =
+            get_note_reference("inbox/idea".to_string(), vault.state(), vault.vault()).unwrap();
+        assert_eq!(again.id, reference.id);
+
+        vault.write("inbox/idea", "# Idea\n\nFleshed out.\n");
+        let moved = move_note(
+            "inbox/idea".to_string(),
+            "projects".to_string(),
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        assert_eq!(moved, "projects/idea");
+
+        let resolve = |uri: &str| resolve_reference(uri.to_string(), vault.state(), vault.vault());
+        assert_eq!(resolve(&reference.uri).unwrap(), "projects/idea");
+        assert_eq!(resolve(&reference.id).unwrap(), "projects/idea");
+        assert!(matches!(
+            resolve("gn://note/0123456789abcdef0123456789abcdef"),
+            Err(GhostError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_only_notes_reject_changes_but_open() {
+        let vault = TestVault::new().await;
+        vault.write("reference", "# Reference\n");
+        set_note_readonly("reference".to_string(), true, vault.state()).unwrap();
+
+        let save = save_note(
+            "reference".to_string(),
+            "# Edited\n".to_string(),
+            None,
+            vault.state(),
+            vault.vault(),
+        );
+        assert!(matches!(save, Err(GhostError::NoteReadOnly(_))));
+        let delete = delete_note("reference".to_string(), vault.state(), vault.vault());
+        assert!(matches!(delete, Err(GhostError::NoteReadOnly(_))));
+        let moved = move_note(
+            "reference".to_string(),
+            "elsewhere".to_string(),
+            vault.state(),
+            vault.vault(),
+        );
+        assert!(matches!(moved, Err(GhostError::NoteReadOnly(_))));
+
+        let opened =
+            open_note("reference".to_string(), None, vault.state(), vault.vault()).unwrap();
+        assert_eq!(opened.content, "# Reference\n");
+        assert!(opened.readonly);
+
+        set_note_readonly("reference".to_string(), false, vault.state()).unwrap();
+        vault.write("reference", "# Edited\n");
+        assert_eq!(
+            read_note_content(vault.path(), "reference", &vault.vault()).unwrap(),
+            "# Edited\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_deleted_behind_our_back_are_audited_and_pruned() {
+        let vault = TestVault::new().await;
+        vault.write("projects/plan", "# Plan\n");
+        vault.write("projects/keep", "# Keep\n");
+        set_pinned(
+            "projects/plan".to_string(),
+            true,
+            vault.state(),
+            vault.vault(),
+        )
+        .unwrap();
+        set_note_readonly("projects/plan".to_string(), true, vault.state()).unwrap();
+        fs::create_dir(vault.path().join("archive")).unwrap();
+        set_current_folder("archive".to_string(), vault.state()).unwrap();
+        read_note("projects/plan".to_string(), None, vault.state(), vault.vault()).unwrap();
+        get_backlinks("projects/keep".to_string(), vault.state(), vault.vault()).unwrap();
+        *vault.state().search_index.lock().unwrap() = Some(
+            crate::commands::search::SearchIndex::build(vault.path(), &vault.vault()).unwrap(),
+        );
+
+        // Gone without going through delete_note or delete_folder
+        fs::remove_file(vault.path().join("projects/plan.enc")).unwrap();
+        fs::remove_file(vault.path().join("projects/plan.key")).unwrap();
+        fs::remove_dir(vault.path().join("archive")).unwrap();
+
+        let audit = audit_derived_state(vault.state()).unwrap();
+        let found = |kind: fn(&DerivedStateKind) -> bool, reference: &str| {
+            audit
+                .iter()
+                .any(|item| kind(&item.kind) && item.reference == reference)
+        };
+        assert_eq!(audit.len(), 6, "{:?}", audit);
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::CurrentFolder),
+            "archive"
+        ));
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::ContentHash),
+            "projects/plan"
+        ));
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::Sidecar),
+            "projects/plan.meta"
+        ));
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::RecentNote),
+            "projects/plan"
+        ));
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::IndexedNote),
+            "projects/plan"
+        ));
+        assert!(found(
+            |k| matches!(k, DerivedStateKind::LinkedNote),
+            "projects/plan"
+        ));
+
+        let pruned = prune_derived_state(vault.state()).unwrap();
+        assert_eq!(pruned.len(), 6);
+        assert!(audit_derived_state(vault.state()).unwrap().is_empty());
+        assert!(!vault.path().join("projects/plan.meta").exists());
+        assert_eq!(indexed_note_ids(&vault.state()), ["projects/keep"]);
+        assert_eq!(*vault.state().current_folder.lock().unwrap(), "");
+        assert!(vault
+            .state()
+            .content_hashes
+            .lock()
+            .unwrap()
+            .contains_key("projects/keep"));
+    }
+
+    #[test]
+    fn word_count_skips_markdown_syntax() {
+        let note = "# Title\n\
+                    \n\
+                    Some intro with a [link](https://example.com/a/b).\n\
+                    \n\
+                    ## Steps\n\
+                    \n\
+                    - first item\n\
+                    * second item\n\
+                    1. numbered step\n\
+                    \n\
+                    ---\n\
+                    \n\
+                    ```rust\n\
+                    let x = 1;\n\
+                    ```\n";
+
+        // Title, 5 words of intro, Steps, 2 per list item and the code's
+        // `let x 1;`, without the `=`
+        assert_eq!(count_words(note), 16);
+        assert!(note.split_whitespace().count() > 16);
+        assert_eq!(
+            count_chars("## Steps\n- first item\n"),
+            "Steps".len() + "first item".len()
+        );
+    }
+
+    #[tokio::test]
+    async fn exports_never_land_in_the_notes_folder() {
+        let vault = TestVault::new().await;
+        vault.write("secret", "# Secret\n");
+        let export = |dest: Option<PathBuf>| {
+            export_note(
+                "secret".to_string(),
+                ExportFormat::Markdown,
+                dest.map(|d| d.to_string_lossy().to_string()),
+                vault.state(),
+                vault.vault(),
+            )
+        };
+
+        assert!(export(Some(vault.path().join("secret.md"))).is_err());
+        assert!(export(Some(vault.outside("sub/../secret.md"))).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+
+            symlink(vault.path(), vault.outside("notes")).unwrap();
+            assert!(export(Some(vault.outside("notes").join("secret.md"))).is_err());
+            symlink(vault.path().join("planted.md"), vault.outside("planted.md")).unwrap();
+            assert!(export(Some(vault.outside("planted.md"))).is_err());
+            assert!(!vault.path().join("planted.md").exists());
         }
+
+        let dest = vault.outside("secret.md");
+        assert_eq!(export(Some(dest.clone())).unwrap(), dest.to_string_lossy());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "# Secret\n");
+
+        // Temp exports get a fresh private file each time
+        let first = PathBuf::from(export(None).unwrap());
+        let second = PathBuf::from(export(None).unwrap());
+        assert_ne!(first, second);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "# Secret\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_saves_leave_the_previous_note() {
+        let vault = TestVault::new().await;
+        vault.write("draft", "# Draft\n");
+        let base = vault.path().join("draft");
+        let enc_tmp = staged_note_path(&enc_path(&base));
+        let key_tmp = staged_note_path(&key_path(&base));
+
+        // A crash once both files are staged, before either is renamed
+        stage_note_files(&base, "draft", b"# Lost edit\n", &vault.vault(), None).unwrap();
+        assert!(enc_tmp.exists() && key_tmp.exists());
+        recover_interrupted_saves(vault.path());
+        assert!(!enc_tmp.exists() && !key_tmp.exists());
+        assert_eq!(
+            read_note_content(vault.path(), "draft", &vault.vault()).unwrap(),
+            "# Draft\n"
+        );
+
+        // A crash partway through the first write
+        fs::write(&enc_tmp, b"half a note").unwrap();
+        recover_interrupted_saves(vault.path());
+        assert!(!enc_tmp.exists());
+        assert_eq!(
+            read_note_content(vault.path(), "draft", &vault.vault()).unwrap(),
+            "# Draft\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn new_key_saves_interrupted_between_renames_are_finished() {
+        let vault = TestVault::new().await;
+        vault.write("draft", "# Draft\n");
+        let base = vault.path().join("draft");
+        let key_tmp = staged_note_path(&key_path(&base));
+
+        // A crash right after the content file under a new DEK is in place
+        let content = b"# Rewritten under a new key\n";
+        stage_note_files(
+            &base,
+            "draft",
+            content,
+            &vault.vault(),
+            Some(Dek::generate()),
+        )
+        .unwrap();
+        fs::rename(staged_note_path(&enc_path(&base)), enc_path(&base)).unwrap();
+        assert!(read_note_content(vault.path(), "draft", &vault.vault()).is_err());
+
+        recover_interrupted_saves(vault.path());
+        assert!(!key_tmp.exists());
+        assert_eq!(
+            read_note_content(vault.path(), "draft", &vault.vault()).unwrap(),
+            "# Rewritten under a new key\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn rekeyed_notes_no_longer_open_with_the_old_key() {
+        let vault = TestVault::new().await;
+        vault.write("secret", "# Secret\n");
+        let key_file = key_path(&vault.path().join("secret"));
+        let old_key = fs::read(&key_file).unwrap();
+
+        rekey_note("secret".to_string(), vault.state(), vault.vault()).unwrap();
+        assert_ne!(fs::read(&key_file).unwrap(), old_key);
+        assert_eq!(
+            read_note_content(vault.path(), "secret", &vault.vault()).unwrap(),
+            "# Secret\n"
+        );
+
+        fs::write(&key_file, &old_key).unwrap();
+        assert!(read_note_content(vault.path(), "secret", &vault.vault()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_out_of_the_notes_folder_are_refused() {
+        use std::os::unix::fs::symlink;
+
+        let vault = TestVault::new().await;
+        vault.write("inbox/note", "# Note\n");
+        let outside = vault.outside("elsewhere");
+        fs::create_dir(&outside).unwrap();
+        fs::write(vault.outside("stolen.enc"), b"not a note").unwrap();
+
+        symlink(&outside, vault.path().join("escape")).unwrap();
+        symlink(vault.outside("stolen.enc"), vault.path().join("leak.enc")).unwrap();
+        symlink(vault.path().join("inbox"), vault.path().join("alias")).unwrap();
+
+        for bad in [
+            "escape",
+            "escape/note",
+            "escape/new/deeper",
+            "leak",
+            "../x",
+            "/etc/passwd",
+        ] {
+            assert!(
+                matches!(resolve_safe(vault.path(), bad), Err(GhostError::Invalid(_))),
+                "{:?}",
+                bad
+            );
+        }
+        assert!(resolve_safe(vault.path(), "alias/note").is_ok());
+        assert!(resolve_safe(vault.path(), "inbox/new note").is_ok());
+
+        let save = save_note(
+            "escape/note".to_string(),
+            "# Planted\n".to_string(),
+            None,
+            vault.state(),
+            vault.vault(),
+        );
+        assert!(matches!(save, Err(GhostError::Invalid(_))));
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+        assert!(read_note("leak".to_string(), None, vault.state(), vault.vault()).is_err());
+    }
+
+    #[tokio::test]
+    async fn trashed_notes_never_show_up_in_search() {
+        let vault = TestVault::new().await;
+        vault.write("kept", "# Kept\n\nshared needle\n");
+        vault.write("binned", "# Binned\n\nshared needle\n");
+        delete_note("binned".to_string(), vault.state(), vault.vault()).unwrap();
+        assert_eq!(walk_note_files(&vault.path().join(TRASH_DIR)).len(), 1);
+
+        let search = |folder: Option<&str>| {
+            search_notes(
+                "needle".to_string(),
+                None,
+                None,
+                None,
+                folder.map(str::to_string),
+                None,
+                None,
+                vault.state(),
+                vault.vault(),
+            )
+        };
+        let paths: Vec<_> = search(None).unwrap().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["kept"]);
+        assert!(search(Some(TRASH_DIR)).map_or(true, |results| results.is_empty()));
+
+        let folders = list_folders(None, vault.state()).unwrap();
+        assert!(folders.iter().all(|folder| !folder.path.starts_with('.')));
     }
 
-    Ok(results)
+    #[tokio::test]
+    async fn existence_checks_see_notes_and_folders_but_not_reserved_ones() {
+        let vault = TestVault::new().await;
+        vault.write("projects/plan", "# Plan\n");
+        fs::write(vault.path().join("legacy.md"), "# Legacy\n").unwrap();
+        vault.write("binned", "# Binned\n");
+        delete_note("binned".to_string(), vault.state(), vault.vault()).unwrap();
+
+        let note = |path: &str| note_exists(path.to_string(), vault.state());
+        let folder = |path: &str| folder_exists(path.to_string(), vault.state());
+
+        assert!(note("projects/plan").unwrap());
+        assert!(note("legacy.md").unwrap());
+        assert!(!note("projects/missing").unwrap());
+        assert!(!note("binned").unwrap());
+        assert!(!note("projects").unwrap());
+
+        assert!(folder("").unwrap());
+        assert!(folder("projects").unwrap());
+        assert!(!folder("projects/plan").unwrap());
+        assert!(!folder("missing").unwrap());
+        assert!(!folder(TRASH_DIR).unwrap());
+        assert!(!folder(".vault").unwrap());
+
+        assert!(note("../outside").is_err());
+        assert!(folder("/etc").is_err());
+    }
 }
@@ -0,0 +1,382 @@
+//! Minimal Markdown to PDF rendering
+//!
+//! Lays the note out as text with the PDF base-14 fonts (Helvetica for prose,
+//! Courier for code), so no font files or external renderer are needed. Text
+//! outside Windows-1252 is replaced with `?`.
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+const LINE_SPACING: f32 = 1.4;
+const BLOCK_GAP: f32 = 6.0;
+const INDENT: f32 = 18.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    /// Resource name used in content streams
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+/// Helvetica advance widths for ASCII 32..=126, in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn char_width(c: char, font: Font, size: f32) -> f32 {
+    let units = match font {
+        Font::Mono => 600.0,
+        _ => {
+            let base = match c as u32 {
+                32..=126 => HELVETICA_WIDTHS[c as usize - 32] as f32,
+                _ => 556.0,
+            };
+            // Helvetica-Bold runs a little wider; overestimating keeps lines inside the margin
+            if font == Font::Bold {
+                base * 1.08
+            } else {
+                base
+            }
+        }
+    };
+    units * size / 1000.0
+}
+
+fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    text.chars().map(|c| char_width(c, font, size)).sum()
+}
+
+/// A run of text laid out with one font, size and indent
+#[derive(Debug)]
+enum Block {
+    Text {
+        font: Font,
+        size: f32,
+        indent: f32,
+        /// Code keeps its line breaks and spacing; prose is re-wrapped
+        preformatted: bool,
+        text: String,
+    },
+    Rule,
+}
+
+/// Collects the text of the block being built and pushes it when it ends
+#[derive(Default)]
+struct BlockBuilder {
+    blocks: Vec<Block>,
+    text: String,
+    /// Font, size and whether the text is preformatted; `None` for body text
+    style: Option<(Font, f32, bool)>,
+    quote_depth: usize,
+    /// One entry per open list: the next number for ordered lists
+    lists: Vec<Option<u64>>,
+}
+
+impl BlockBuilder {
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        if text.trim().is_empty() {
+            return;
+        }
+        let (font, size, preformatted) = self.style.unwrap_or((Font::Regular, BODY_SIZE, false));
+        let depth = self.lists.len().saturating_sub(1) + self.quote_depth;
+        self.blocks.push(Block::Text {
+            font,
+            size,
+            indent: depth as f32 * INDENT,
+            preformatted,
+            text,
+        });
+    }
+}
+
+/// Turn Markdown into blocks, dropping markup that has no place in print
+fn markdown_blocks(markdown: &str) -> Vec<Block> {
+    let mut b = BlockBuilder::default();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                b.flush();
+                let size = match level {
+                    HeadingLevel::H1 => 20.0,
+                    HeadingLevel::H2 => 16.0,
+                    HeadingLevel::H3 => 13.0,
+                    _ => BODY_SIZE,
+                };
+                b.style = Some((Font::Bold, size, false));
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                b.flush();
+                b.style = Some((Font::Mono, CODE_SIZE, true));
+            }
+            Event::Start(Tag::Paragraph) => {
+                // A list item's bullet is already waiting in `text`
+                if b.lists.is_empty() {
+                    b.flush();
+                }
+                b.style = None;
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                b.flush();
+                b.quote_depth += 1;
+            }
+            Event::Start(Tag::List(start)) => {
+                b.flush();
+                b.lists.push(start);
+            }
+            Event::Start(Tag::Item) => {
+                b.flush();
+                b.style = None;
+                match b.lists.last_mut() {
+                    Some(Some(n)) => {
+                        b.text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => b.text.push_str("\u{2022} "),
+                }
+            }
+            Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::CodeBlock) => {
+                b.flush();
+                b.style = None;
+            }
+            Event::End(TagEnd::Paragraph) | Event::End(TagEnd::Item) => b.flush(),
+            Event::End(TagEnd::BlockQuote(_)) => {
+                b.flush();
+                b.quote_depth = b.quote_depth.saturating_sub(1);
+            }
+            Event::End(TagEnd::List(_)) => {
+                b.flush();
+                b.lists.pop();
+            }
+            Event::Text(t) | Event::Code(t) | Event::InlineHtml(t) | Event::Html(t) => {
+                b.text.push_str(&t)
+            }
+            Event::SoftBreak => b.text.push(' '),
+            Event::HardBreak => b.text.push('\n'),
+            Event::TaskListMarker(done) => b.text.push_str(if done { "[x] " } else { "[ ] " }),
+            Event::Rule => {
+                b.flush();
+                b.blocks.push(Block::Rule);
+            }
+            _ => {}
+        }
+    }
+    b.flush();
+
+    b.blocks
+}
+
+/// Break `text` into lines no wider than `width`
+fn wrap(text: &str, font: Font, size: f32, width: f32, preformatted: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.trim_end_matches('\n').split('\n') {
+        let mut line = String::new();
+        let words: Vec<&str> = if preformatted {
+            vec![paragraph]
+        } else {
+            paragraph.split_whitespace().collect()
+        };
+        for word in words {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if text_width(&candidate, font, size) <= width || line.is_empty() {
+                line = candidate;
+            } else {
+                lines.push(std::mem::take(&mut line));
+                line = word.to_string();
+            }
+            // Hard-split anything that still doesn't fit (long URLs, code)
+            while text_width(&line, font, size) > width && line.chars().count() > 1 {
+                let mut taken = 0.0;
+                let split = line
+                    .char_indices()
+                    .find(|&(_, c)| {
+                        taken += char_width(c, font, size);
+                        taken > width
+                    })
+                    .map(|(i, _)| i)
+                    .filter(|&i| i > 0)
+                    .unwrap_or(line.len());
+                let rest = line.split_off(split);
+                lines.push(std::mem::replace(&mut line, rest));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Encode a line as a PDF string literal in Windows-1252
+fn pdf_string(text: &str) -> String {
+    let mut out = String::from("(");
+    for c in text.chars() {
+        let byte = match c {
+            '\u{20AC}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201C}' => 0x93,
+            '\u{201D}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\t' => b' ' as u32,
+            c if (c as u32) < 0x20 => continue,
+            c if (c as u32) <= 0x7E || (0xA0..=0xFF).contains(&(c as u32)) => c as u32,
+            _ => b'?' as u32,
+        };
+        match byte {
+            0x28 | 0x29 | 0x5C => {
+                out.push('\\');
+                out.push(byte as u8 as char);
+            }
+            0x20..=0x7E => out.push(byte as u8 as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// Lay the blocks out onto pages, returning each page's content stream
+fn layout(blocks: &[Block]) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut y = PAGE_HEIGHT - MARGIN;
+    let text_width_max = PAGE_WIDTH - 2.0 * MARGIN;
+
+    for block in blocks {
+        match block {
+            Block::Text {
+                font,
+                size,
+                indent,
+                preformatted,
+                text,
+            } => {
+                let leading = size * LINE_SPACING;
+                for line in wrap(text, *font, *size, text_width_max - indent, *preformatted) {
+                    if y - leading < MARGIN {
+                        pages.push(std::mem::take(&mut page));
+                        y = PAGE_HEIGHT - MARGIN;
+                    }
+                    y -= leading;
+                    page.push_str(&format!(
+                        "BT /{} {} Tf {:.2} {:.2} Td {} Tj ET\n",
+                        font.resource(),
+                        size,
+                        MARGIN + indent,
+                        y,
+                        pdf_string(&line)
+                    ));
+                }
+                y -= BLOCK_GAP;
+            }
+            Block::Rule => {
+                if y - BLOCK_GAP * 2.0 < MARGIN {
+                    pages.push(std::mem::take(&mut page));
+                    y = PAGE_HEIGHT - MARGIN;
+                }
+                y -= BLOCK_GAP;
+                page.push_str(&format!(
+                    "0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n",
+                    MARGIN,
+                    y,
+                    PAGE_WIDTH - MARGIN,
+                    y
+                ));
+                y -= BLOCK_GAP;
+            }
+        }
+    }
+    pages.push(page);
+    pages
+}
+
+/// Render a Markdown note as a PDF document
+pub(crate) fn markdown_to_pdf(markdown: &str) -> Vec<u8> {
+    let pages = layout(&markdown_blocks(markdown));
+
+    // Objects 1-5 are fixed; each page then takes a page object and its content
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        String::new(), // page tree, filled in once page ids are known
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    let mut kids = Vec::new();
+    for content in &pages {
+        let page_id = objects.len() + 1;
+        kids.push(format!("{} 0 R", page_id));
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    );
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+
+    let xref_start = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_start
+        )
+        .as_bytes(),
+    );
+    out
+}
@@ -0,0 +1,436 @@
+use crate::commands::notes::{
+    build_search_pattern, count_matches, extract_title, find_matches, is_ignored, load_ignore,
+    read_note_content, rel_note_path, search_notes, walk_note_files, SearchMode, SearchResult,
+};
+use crate::commands::vault::VaultState;
+use crate::error::GhostError;
+use crate::AppState;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tantivy::collector::TopDocs;
+use tantivy::query::{AllQuery, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tauri::{AppHandle, Manager, State};
+use zeroize::Zeroizing;
+
+/// Most notes a single indexed search returns
+const MAX_RESULTS: usize = 200;
+
+/// Memory budget for the index writer
+const WRITER_HEAP_BYTES: usize = 15_000_000;
+
+/// Most notes a single title search returns
+const MAX_TITLE_RESULTS: usize = 50;
+
+/// Set while a background rebuild is running
+static BUILDING: AtomicBool = AtomicBool::new(false);
+
+/// Set when a note changed during a rebuild, which may have read it before
+/// the change and so has to start over
+static MISSED_UPDATE: AtomicBool = AtomicBool::new(false);
+
+/// Full-text index over decrypted note content
+///
+/// The index is held entirely in RAM so note plaintext never reaches disk.
+/// It is dropped when the vault locks and rebuilt in the background after
+/// the next unlock.
+pub struct SearchIndex {
+    index: Index,
+    writer: IndexWriter,
+    reader: IndexReader,
+    path: Field,
+    title: Field,
+    body: Field,
+}
+
+impl SearchIndex {
+    fn new() -> Result<Self, String> {
+        let mut builder = Schema::builder();
+        let path = builder.add_text_field("path", STRING | STORED);
+        let title = builder.add_text_field("title", TEXT | STORED);
+        let body = builder.add_text_field("body", TEXT | STORED);
+
+        let index = Index::create_in_ram(builder.build());
+        let writer = index.writer(WRITER_HEAP_BYTES).map_err(|e| e.to_string())?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e: tantivy::TantivyError| e.to_string())?;
+
+        Ok(Self {
+            index,
+            writer,
+            reader,
+            path,
+            title,
+            body,
+        })
+    }
+
+    /// Index every note in the notes folder
+    pub fn build(notes_dir: &Path, vault: &VaultState) -> Result<Self, String> {
+        let mut index = Self::new()?;
+
+        for file_path in walk_note_files(notes_dir) {
+            let rel_path = rel_note_path(notes_dir, &file_path);
+            if let Ok(content) = read_note_content(notes_dir, &rel_path, vault) {
+                index.add(&rel_path, &Zeroizing::new(content))?;
+            }
+        }
+
+        index.commit()?;
+        Ok(index)
+    }
+
+    fn add(&mut self, rel_path: &str, content: &str) -> Result<(), String> {
+        let title = extract_title(content, Path::new(rel_path));
+        self.writer
+            .add_document(doc!(
+                self.path => rel_path,
+                self.title => title,
+                self.body => content,
+            ))
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), String> {
+        self.writer.commit().map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+
+    /// Replace a note's entry with its current content
+    pub fn upsert(&mut self, rel_path: &str, content: &str) -> Result<(), String> {
+        self.writer.delete_term(Term::from_field_text(self.path, rel_path));
+        self.add(rel_path, content)?;
+        self.commit()
+    }
+
+    /// Drop a note's entry
+    pub fn remove(&mut self, rel_path: &str) -> Result<(), String> {
+        self.writer.delete_term(Term::from_field_text(self.path, rel_path));
+        self.commit()
+    }
+
+    /// Ids of every indexed note
+    pub fn paths(&self) -> Result<Vec<String>, String> {
+        let searcher = self.reader.searcher();
+        let limit = (searcher.num_docs() as usize).max(1);
+        let hits = searcher
+            .search(&AllQuery, &TopDocs::with_limit(limit))
+            .map_err(|e| e.to_string())?;
+        let mut paths = Vec::new();
+        for (_, address) in hits {
+            let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+            if let Some(path) = doc.get_first(self.path).and_then(|v| v.as_str()) {
+                paths.push(path.to_string());
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Re-key a note's entry after it was moved or renamed
+    pub fn relocate(&mut self, old_path: &str, new_path: &str) -> Result<(), String> {
+        let searcher = self.reader.searcher();
+        let query = TermQuery::new(
+            Term::from_field_text(self.path, old_path),
+            IndexRecordOption::Basic,
+        );
+        let hit = searcher
+            .search(&query, &TopDocs::with_limit(1))
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .next();
+
+        let Some((_, address)) = hit else {
+            return Ok(());
+        };
+        let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+        let content = doc
+            .get_first(self.body)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        self.writer.delete_term(Term::from_field_text(self.path, old_path));
+        self.upsert(new_path, &content)
+    }
+
+    /// Run a query, returning results in the same shape as `search_notes`
+    pub fn search(&self, query: &str, context_lines: usize) -> Result<Vec<SearchResult>, String> {
+        let searcher = self.reader.searcher();
+        let parser = QueryParser::for_index(&self.index, vec![self.title, self.body]);
+        let (parsed, _errors) = parser.parse_query_lenient(query);
+
+        let hits = searcher
+            .search(&parsed, &TopDocs::with_limit(MAX_RESULTS))
+            .map_err(|e| e.to_string())?;
+
+        // Lines are matched on the query's words, as the tokenizer would split them
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(regex::escape)
+            .collect();
+        let pattern = if words.is_empty() {
+            None
+        } else {
+            Some(build_search_pattern(
+                &words.join("|"),
+                SearchMode::Regex,
+                false,
+            )?)
+        };
+
+        let mut results = Vec::new();
+        for (_score, address) in hits {
+            let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+            let field_text = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let content = field_text(self.body);
+
+            let matches = pattern
+                .as_ref()
+                .map(|pattern| find_matches(&content, pattern, context_lines))
+                .unwrap_or_default();
+            let score = pattern
+                .as_ref()
+                .map(|pattern| count_matches(&content, pattern))
+                .unwrap_or(0);
+
+            results.push(SearchResult {
+                path: field_text(self.path),
+                title: field_text(self.title),
+                matches,
+                corrupted: false,
+                error: None,
+                score,
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+/// Apply an edit to the index if it has been built
+///
+/// A failed update drops the index so searches fall back to a full scan
+/// instead of returning stale results. An edit made while the index is
+/// still being built makes the build start over.
+fn update_index<F>(state: &AppState, f: F)
+where
+    F: FnOnce(&mut SearchIndex) -> Result<(), String>,
+{
+    let mut guard = state.search_index.lock().unwrap();
+    match guard.as_mut() {
+        Some(index) => {
+            if f(index).is_err() {
+                *guard = None;
+            }
+        }
+        None => {
+            if BUILDING.load(Ordering::SeqCst) {
+                MISSED_UPDATE.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Record a note's new content in the index
+pub(crate) fn index_note(state: &AppState, rel_path: &str, content: &str) {
+    update_index(state, |index| index.upsert(rel_path, content));
+}
+
+/// Remove a deleted note from the index
+pub(crate) fn unindex_note(state: &AppState, rel_path: &str) {
+    update_index(state, |index| index.remove(rel_path));
+}
+
+/// Follow a note to its new id
+pub(crate) fn reindex_moved_note(state: &AppState, old_path: &str, new_path: &str) {
+    update_index(state, |index| index.relocate(old_path, new_path));
+}
+
+/// Ids of the notes in the index, none if it hasn't been built
+pub(crate) fn indexed_note_ids(state: &AppState) -> Vec<String> {
+    let guard = state.search_index.lock().unwrap();
+    guard
+        .as_ref()
+        .and_then(|index| index.paths().ok())
+        .unwrap_or_default()
+}
+
+/// Discard the index and the plaintext it holds
+pub(crate) fn clear_index(state: &AppState) {
+    *state.search_index.lock().unwrap() = None;
+}
+
+/// Build the index on a background thread
+pub(crate) fn spawn_index_build(app: AppHandle) {
+    if BUILDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let vault = app.state::<VaultState>();
+        let notes_dir = state.notes_dir.lock().unwrap().clone();
+
+        loop {
+            MISSED_UPDATE.store(false, Ordering::SeqCst);
+            let Ok(index) = SearchIndex::build(&notes_dir, &vault) else {
+                break;
+            };
+            // Edits check for the index under the same lock, so none can
+            // slip in between this check and the index going live
+            let mut guard = state.search_index.lock().unwrap();
+            if MISSED_UPDATE.load(Ordering::SeqCst) {
+                continue;
+            }
+            // The vault may have locked while we were indexing
+            if vault.is_unlocked() {
+                *guard = Some(index);
+            }
+            break;
+        }
+
+        BUILDING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Search notes using the full-text index
+///
+/// Falls back to the linear scan of `search_notes` while the index is
+/// missing, and starts building it.
+#[tauri::command]
+pub fn search_notes_indexed(
+    query: String,
+    context_lines: Option<usize>,
+    app: AppHandle,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<SearchResult>, GhostError> {
+    let results = match state.search_index.lock().unwrap().as_ref() {
+        Some(index) => Some(index.search(&query, context_lines.unwrap_or(0))?),
+        None => None,
+    };
+
+    match results {
+        // The index covers every note, whatever `.ghostignore` says now
+        Some(mut results) => {
+            let notes_dir = state.notes_dir.lock().unwrap().clone();
+            let ignore = load_ignore(&notes_dir);
+            results.retain(|r| !is_ignored(&ignore, &notes_dir, Path::new(&r.path), false));
+            Ok(results)
+        }
+        None => {
+            if vault.is_unlocked() {
+                spawn_index_build(app);
+            }
+            search_notes(query, context_lines, None, None, None, None, None, state, vault)
+        }
+    }
+}
+
+/// Every note's id and title, valid for one content version
+pub struct TitleCache {
+    content_version: u64,
+    titles: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TitleMatch {
+    pub path: String,
+    pub title: String,
+    /// Higher is a closer match
+    pub score: i64,
+    /// Character positions in `title` that matched the query
+    pub indices: Vec<usize>,
+}
+
+/// Drop the cached titles
+pub(crate) fn clear_title_cache(state: &AppState) {
+    *state.title_cache.lock().unwrap() = None;
+}
+
+/// Run `f` over every note's title, reading them again if any note changed
+///
+/// Titles come from decrypted content, so they're kept until the vault's
+/// content version moves on rather than decrypted on every keystroke.
+fn with_titles<T>(
+    state: &AppState,
+    vault: &VaultState,
+    f: impl FnOnce(&[(String, String)]) -> T,
+) -> Result<T, GhostError> {
+    if !vault.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+
+    let version = vault.content_version();
+    let mut cache = state.title_cache.lock().unwrap();
+    if let Some(cached) = cache.as_ref().filter(|c| c.content_version == version) {
+        return Ok(f(&cached.titles));
+    }
+
+    let notes_dir = state.notes_dir.lock().unwrap().clone();
+    let titles: Vec<(String, String)> = walk_note_files(&notes_dir)
+        .iter()
+        .filter_map(|file_path| {
+            let rel_path = rel_note_path(&notes_dir, file_path);
+            let content = Zeroizing::new(read_note_content(&notes_dir, &rel_path, vault).ok()?);
+            let title = extract_title(&content, Path::new(&rel_path));
+            Some((rel_path, title))
+        })
+        .collect();
+
+    let result = f(&titles);
+    *cache = Some(TitleCache {
+        content_version: version,
+        titles,
+    });
+    Ok(result)
+}
+
+/// Fuzzy-match note titles for a quick "jump to note" palette
+///
+/// Results are ranked best first. An empty query matches nothing.
+#[tauri::command]
+pub fn search_titles(
+    query: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<Vec<TitleMatch>, GhostError> {
+    let query = query.trim();
+    let matcher = SkimMatcherV2::default();
+
+    with_titles(&state, &vault, |titles| {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<TitleMatch> = titles
+            .iter()
+            .filter_map(|(path, title)| {
+                let (score, indices) = matcher.fuzzy_indices(title, query)?;
+                Some(TitleMatch {
+                    path: path.clone(),
+                    title: title.clone(),
+                    score,
+                    indices,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+        matches.truncate(MAX_TITLE_RESULTS);
+        matches
+    })
+}
@@ -1,14 +1,129 @@
+use crate::commands::notes::{normalize_note_path, FilenameScheme};
+use crate::commands::vault::{
+    lock_and_clear, Cipher, VaultConfig, VaultState, DEFAULT_NOTE_CACHE_BYTES,
+};
+use crate::commands::watcher::watch_notes_dir;
+use crate::error::GhostError;
 use crate::AppState;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 use std::fs;
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use tauri::State;
 
+/// Settings kept in `config.json`
+///
+/// Fields missing from an older file take their default, so upgrading
+/// never loses the rest of the file.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     pub notes_dir: String,
     pub model: String,
+    /// Id of the frontend theme
+    pub theme: String,
+    /// Editor font size in pixels
     pub font_size: u32,
+    /// Minutes without vault access before the KEK is dropped
+    pub auto_lock_minutes: u64,
+    /// Earlier versions kept per note (0 turns history off)
+    pub max_versions: usize,
+    /// Folder new notes go in, relative to the notes folder ("" for the top)
+    pub default_folder: String,
+    /// Cipher a newly created vault starts out with
+    pub default_cipher: Cipher,
+    /// How long deriving the key may take on this device when a vault is
+    /// set up, in milliseconds
+    pub argon2_target_ms: u64,
+    /// Content of a new daily note, with `{date}` replaced by today's date
+    pub daily_note_template: String,
+    /// Bytes of decrypted notes kept in memory for quick reopening (0 turns
+    /// the cache off)
+    pub note_cache_bytes: usize,
+    /// How new notes' files are named
+    pub filename_scheme: FilenameScheme,
+    /// Whether the user confirmed saving the vault's current recovery key
+    pub recovery_key_saved: bool,
+    /// Random overwrites before a securely deleted file is unlinked. On SSDs
+    /// wear levelling puts each write somewhere new, so passes beyond the
+    /// first add little there.
+    pub secure_delete_passes: u32,
+    /// Raise the vault's key derivation costs to the recommended ones in
+    /// the background after unlocking, when they're lower. On unless the
+    /// user turned it off.
+    pub auto_upgrade_kdf: bool,
+}
+
+const FONT_SIZE_RANGE: RangeInclusive<u32> = 8..=48;
+const AUTO_LOCK_MINUTES_RANGE: RangeInclusive<u64> = 1..=240;
+const MAX_VERSIONS_LIMIT: usize = 1000;
+const ARGON2_TARGET_MS_RANGE: RangeInclusive<u64> = 250..=10_000;
+const NOTE_CACHE_BYTES_LIMIT: usize = 256 * 1024 * 1024;
+const SECURE_DELETE_PASSES_RANGE: RangeInclusive<u32> = 1..=35;
+
+fn default_auto_lock_minutes() -> u64 {
+    5
+}
+
+pub(crate) fn default_max_versions() -> usize {
+    20
+}
+
+impl AppSettings {
+    /// Reject values outside what the app supports
+    ///
+    /// Nothing is clamped: a bad value is reported so the caller can fix it.
+    pub fn validate(&self) -> Result<(), GhostError> {
+        fn out_of_range<T: Display>(name: &str, range: &RangeInclusive<T>) -> GhostError {
+            GhostError::Invalid(format!(
+                "{} must be between {} and {}",
+                name,
+                range.start(),
+                range.end()
+            ))
+        }
+
+        if !Path::new(&self.notes_dir).is_absolute() {
+            return Err(GhostError::Invalid(
+                "Notes folder must be an absolute path".to_string(),
+            ));
+        }
+        if self.model.trim().is_empty() {
+            return Err(GhostError::Invalid("Model cannot be empty".to_string()));
+        }
+        if self.theme.trim().is_empty() {
+            return Err(GhostError::Invalid("Theme cannot be empty".to_string()));
+        }
+        if !FONT_SIZE_RANGE.contains(&self.font_size) {
+            return Err(out_of_range("Font size", &FONT_SIZE_RANGE));
+        }
+        if !AUTO_LOCK_MINUTES_RANGE.contains(&self.auto_lock_minutes) {
+            return Err(out_of_range("Auto-lock minutes", &AUTO_LOCK_MINUTES_RANGE));
+        }
+        if self.max_versions > MAX_VERSIONS_LIMIT {
+            return Err(out_of_range("Versions kept", &(0..=MAX_VERSIONS_LIMIT)));
+        }
+        if !ARGON2_TARGET_MS_RANGE.contains(&self.argon2_target_ms) {
+            return Err(out_of_range("Key derivation time", &ARGON2_TARGET_MS_RANGE));
+        }
+        if self.note_cache_bytes > NOTE_CACHE_BYTES_LIMIT {
+            return Err(out_of_range(
+                "Note cache size",
+                &(0..=NOTE_CACHE_BYTES_LIMIT),
+            ));
+        }
+        if !SECURE_DELETE_PASSES_RANGE.contains(&self.secure_delete_passes) {
+            return Err(out_of_range(
+                "Secure delete passes",
+                &SECURE_DELETE_PASSES_RANGE,
+            ));
+        }
+        if !self.default_folder.is_empty() {
+            normalize_note_path(&self.default_folder)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for AppSettings {
@@ -20,20 +135,35 @@ impl Default for AppSettings {
         Self {
             notes_dir: default_dir.to_string_lossy().to_string(),
             model: "small.en".to_string(),
+            theme: "covert".to_string(),
             font_size: 16,
+            auto_lock_minutes: default_auto_lock_minutes(),
+            max_versions: default_max_versions(),
+            default_folder: String::new(),
+            default_cipher: Cipher::default(),
+            argon2_target_ms: 1000,
+            daily_note_template: "# {date}\n\n".to_string(),
+            note_cache_bytes: DEFAULT_NOTE_CACHE_BYTES,
+            filename_scheme: FilenameScheme::default(),
+            recovery_key_saved: false,
+            secure_delete_passes: 1,
+            auto_upgrade_kdf: true,
         }
     }
 }
 
 fn config_path() -> PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("ghostnote")
-        .join("config.json")
+    // Tests must never read or overwrite the user's own settings
+    #[cfg(test)]
+    let config_dir = std::env::temp_dir().join(format!("ghostnote-test-{}", std::process::id()));
+    #[cfg(not(test))]
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+
+    config_dir.join("ghostnote").join("config.json")
 }
 
-#[tauri::command]
-pub fn get_settings() -> Result<AppSettings, String> {
+/// Load settings from disk, falling back to defaults if none are saved
+pub fn load_settings() -> Result<AppSettings, String> {
     let path = config_path();
 
     if path.exists() {
@@ -44,8 +174,8 @@ pub fn get_settings() -> Result<AppSettings, String> {
     }
 }
 
-#[tauri::command]
-pub fn save_settings(settings: AppSettings, state: State<AppState>) -> Result<(), String> {
+/// Write settings to disk
+pub fn store_settings(settings: &AppSettings) -> Result<(), String> {
     let path = config_path();
 
     // Ensure config directory exists
@@ -53,15 +183,147 @@ pub fn save_settings(settings: AppSettings, state: State<AppState>) -> Result<()
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
 
+    let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_settings() -> Result<AppSettings, String> {
+    load_settings()
+}
+
+/// Apply and persist settings, rejecting any that fail validation
+#[tauri::command]
+pub fn save_settings(
+    settings: AppSettings,
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), GhostError> {
+    settings.validate()?;
+
     // Update app state with new notes directory
-    {
-        let mut notes_dir = state.notes_dir.lock().unwrap();
-        *notes_dir = PathBuf::from(&settings.notes_dir);
+    let notes_dir = PathBuf::from(&settings.notes_dir);
+    if *state.notes_dir.lock().unwrap() != notes_dir {
+        switch_notes_dir(notes_dir, &app, &state, &vault)?;
+    }
+
+    apply_settings(&settings, &state, &vault);
+    Ok(store_settings(&settings)?)
+}
+
+/// Put settings that take effect immediately into app and vault state
+fn apply_settings(settings: &AppSettings, state: &AppState, vault: &VaultState) {
+    vault.set_timeout(settings.auto_lock_minutes * 60);
+    vault.set_note_cache_bytes(settings.note_cache_bytes);
+    *state.max_versions.lock().unwrap() = settings.max_versions;
+}
+
+/// Fields left out of exported settings, since they only make sense on
+/// this machine
+const MACHINE_FIELDS: &[&str] = &["notes_dir"];
+
+/// Settings as JSON, for carrying to another machine
+///
+/// Settings hold no keys or passwords; the notes folder is left out.
+#[tauri::command]
+pub fn export_settings() -> Result<String, GhostError> {
+    let mut value = serde_json::to_value(load_settings()?)?;
+    if let Some(fields) = value.as_object_mut() {
+        for field in MACHINE_FIELDS {
+            fields.remove(*field);
+        }
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Validate and apply settings from `export_settings`, returning the result
+///
+/// Fields the JSON leaves out, such as ones added in a later version, keep
+/// their current value. The notes folder is never changed.
+#[tauri::command]
+pub fn import_settings(
+    json: String,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<AppSettings, GhostError> {
+    let imported: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| GhostError::Invalid(format!("Not a settings file: {}", e)))?;
+    let Some(imported) = imported.as_object() else {
+        return Err(GhostError::Invalid("Not a settings file".to_string()));
+    };
+
+    let mut merged = serde_json::to_value(load_settings()?)?;
+    if let Some(fields) = merged.as_object_mut() {
+        for (field, value) in imported {
+            if !MACHINE_FIELDS.contains(&field.as_str()) {
+                fields.insert(field.clone(), value.clone());
+            }
+        }
     }
+    let settings: AppSettings = serde_json::from_value(merged)
+        .map_err(|e| GhostError::Invalid(format!("Invalid settings: {}", e)))?;
+    settings.validate()?;
 
+    apply_settings(&settings, &state, &vault);
+    store_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Change where notes (and the vault) live, remembering it across restarts
+///
+/// The vault is locked afterwards: the KEK in memory belongs to the old
+/// folder, so a folder with its own vault must be unlocked with that
+/// vault's password, and one without needs setting up.
+#[tauri::command]
+pub fn set_notes_dir(
+    path: String,
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    vault: State<VaultState>,
+) -> Result<(), String> {
+    let notes_dir = PathBuf::from(path.trim());
+
+    if !notes_dir.is_absolute() {
+        return Err("Notes folder must be an absolute path".to_string());
+    }
+    if notes_dir.exists() && !notes_dir.is_dir() {
+        return Err(format!("'{}' is not a folder", notes_dir.display()));
+    }
+    if notes_dir.components().any(|c| c.as_os_str() == ".vault") {
+        return Err("Notes folder cannot be inside a vault's data folder".to_string());
+    }
+
+    if *state.notes_dir.lock().unwrap() == notes_dir {
+        return Ok(());
+    }
+
+    switch_notes_dir(notes_dir.clone(), &app, &state, &vault)?;
+
+    let mut settings = load_settings()?;
+    settings.notes_dir = notes_dir.to_string_lossy().to_string();
+    store_settings(&settings)
+}
+
+/// Re-point app and vault state at another notes folder
+fn switch_notes_dir(
+    notes_dir: PathBuf,
+    app: &tauri::AppHandle,
+    state: &AppState,
+    vault: &VaultState,
+) -> Result<(), String> {
     // Ensure notes directory exists
-    fs::create_dir_all(&settings.notes_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&notes_dir).map_err(|e| e.to_string())?;
 
-    let content = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-    fs::write(&path, content).map_err(|e| e.to_string())
+    *state.notes_dir.lock().unwrap() = notes_dir.clone();
+    state.current_folder.lock().unwrap().clear();
+    state.content_hashes.lock().unwrap().clear();
+    // The index, tags, titles, links and checkouts belong to the old notes folder
+    lock_and_clear(vault, state);
+    vault.set_config(VaultConfig::new(&notes_dir));
+
+    if let Err(e) = watch_notes_dir(app, &notes_dir) {
+        eprintln!("Warning: {}", e);
+    }
+    Ok(())
 }
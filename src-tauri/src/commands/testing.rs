@@ -0,0 +1,89 @@
+//! Fixtures shared by the command tests
+
+use crate::commands::notes::save_note;
+use crate::commands::vault::{setup_vault, KdfParams, VaultConfig, VaultState};
+use crate::AppState;
+use std::path::{Path, PathBuf};
+use tauri::test::{mock_app, MockRuntime};
+use tauri::{App, Manager, State};
+use tempfile::TempDir;
+
+/// Master password of every test vault
+pub(crate) const PASSWORD: &str = "correct horse battery staple";
+
+/// A notes folder of its own in a temporary directory, with the app and
+/// vault state pointed at it
+pub(crate) struct TestVault {
+    dir: TempDir,
+    /// Somewhere outside the notes folder, for exports and imports
+    outside: TempDir,
+    app: App<MockRuntime>,
+}
+
+impl TestVault {
+    /// A vault set up with `PASSWORD` and left unlocked
+    ///
+    /// It uses the weakest parameters a vault may have, which keeps key
+    /// derivation quick.
+    pub async fn new() -> Self {
+        let test_vault = Self::uninitialized();
+        setup_vault(
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            test_vault.vault(),
+        )
+        .await
+        .unwrap();
+        test_vault
+    }
+
+    /// A notes folder with no vault in it yet
+    pub fn uninitialized() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let app = mock_app();
+
+        let state = AppState::default();
+        *state.notes_dir.lock().unwrap() = dir.path().to_path_buf();
+        app.manage(state);
+
+        let vault = VaultState::new();
+        vault.set_config(VaultConfig::new(&dir.path().to_path_buf()));
+        app.manage(vault);
+
+        Self {
+            dir,
+            outside: tempfile::tempdir().unwrap(),
+            app,
+        }
+    }
+
+    pub fn state(&self) -> State<'_, AppState> {
+        self.app.state::<AppState>()
+    }
+
+    pub fn vault(&self) -> State<'_, VaultState> {
+        self.app.state::<VaultState>()
+    }
+
+    /// The notes folder
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Save `content` as the note `rel_path`, creating it if need be
+    pub fn write(&self, rel_path: &str, content: &str) {
+        save_note(
+            rel_path.to_string(),
+            content.to_string(),
+            None,
+            self.state(),
+            self.vault(),
+        )
+        .unwrap();
+    }
+
+    /// Where a file named `name` would go outside the notes folder
+    pub fn outside(&self, name: &str) -> PathBuf {
+        self.outside.path().join(name)
+    }
+}
@@ -4,17 +4,42 @@
 //! - Master password / KEK derivation
 //! - Per-note DEK generation and wrapping
 //! - Encryption/decryption of note content
+//!
+//! Decrypted note content is held in `Zeroizing` buffers where it stays on
+//! the Rust side (search scans, the tag map, index builds, the note cache)
+//! and is wiped when dropped. Anything returned to the frontend is serialized
+//! out of our control and can't be wiped: note content from
+//! `read_note`/`open_note`, titles and previews in listings, search
+//! snippets, and exported files.
 
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{
+        consts::U5,
+        generic_array::{typenum::Unsigned, ArrayLength, GenericArray},
+        stream::{DecryptorBE32, EncryptorBE32},
+        Aead, AeadInPlace, KeyInit, OsRng, Payload,
+    },
     Aes256Gcm, Nonce,
 };
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
 use argon2::{Algorithm, Argon2, Params, Version};
+use crate::commands::notes::{
+    bound_note_id, clear_link_graph, clear_tag_cache, decrypt_note_bytes, encrypt_note_files,
+    recover_interrupted_saves, rel_note_path, wipe_checkouts,
+};
+use crate::commands::search::{clear_index, clear_title_cache, spawn_index_build};
+use crate::commands::watcher::record_own_write;
+use crate::error::GhostError;
+use crate::AppState;
 use rand::RngCore;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use std::time::{Duration, Instant};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Sub;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// Key Encryption Key - derived from password, wraps DEKs
 #[derive(Zeroize, ZeroizeOnDrop)]
@@ -31,6 +56,12 @@ pub struct VaultConfig {
     pub salt_path: PathBuf,
     pub verify_path: PathBuf,
     pub recovery_path: PathBuf,
+    pub content_version_path: PathBuf,
+    pub cipher_path: PathBuf,
+    pub kdf_path: PathBuf,
+    pub recents_path: PathBuf,
+    pub meta_cache_path: PathBuf,
+    pub reencrypt_progress_path: PathBuf,
 }
 
 impl VaultConfig {
@@ -41,9 +72,39 @@ impl VaultConfig {
             salt_path: vault_dir.join("salt"),
             verify_path: vault_dir.join("verify"),
             recovery_path: vault_dir.join("recovery.key"),
+            content_version_path: vault_dir.join("content_version.json"),
+            cipher_path: vault_dir.join("cipher"),
+            kdf_path: vault_dir.join("kdf.json"),
+            recents_path: vault_dir.join("recents.json"),
+            meta_cache_path: vault_dir.join("meta.db"),
+            reencrypt_progress_path: vault_dir.join("reencrypt.json"),
             vault_dir,
         }
     }
+
+    /// Cipher new notes are written with (AES-256-GCM unless changed)
+    pub fn cipher(&self) -> Cipher {
+        fs::read_to_string(&self.cipher_path)
+            .ok()
+            .and_then(|name| Cipher::from_name(name.trim()))
+            .unwrap_or_default()
+    }
+
+    /// Argon2 parameters the vault's KEK is derived with
+    ///
+    /// Vaults created before the parameters were stored have no file and use
+    /// `KdfParams::V1`.
+    pub fn kdf_params(&self) -> Result<KdfParams, GhostError> {
+        match fs::read(&self.kdf_path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| GhostError::Invalid(format!("Invalid KDF parameters: {}", e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(KdfParams::V1),
+            Err(e) => Err(GhostError::Io(format!(
+                "Failed to read KDF parameters: {}",
+                e
+            ))),
+        }
+    }
 }
 
 /// Check if vault is initialized (has salt file)
@@ -56,18 +117,135 @@ const ARGON2_M_COST: u32 = 65536; // 64 MB memory
 const ARGON2_T_COST: u32 = 3; // 3 iterations
 const ARGON2_P_COST: u32 = 4; // 4 parallel lanes
 
+/// Argon2id cost parameters, stored per vault in `.vault/kdf.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub m_cost: u32,
+    /// Iterations
+    pub t_cost: u32,
+    /// Parallel lanes
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    /// What every vault used before the parameters were stored. Exports
+    /// and backups, whose formats don't record parameters, always use these.
+    pub const V1: Self = Self {
+        m_cost: 65536,
+        t_cost: 3,
+        p_cost: 4,
+    };
+
+    /// What new vaults get when the device wasn't calibrated
+    pub const RECOMMENDED: Self = Self {
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+    };
+
+    /// The weakest parameters a new vault may use (the OWASP minimum for
+    /// Argon2id)
+    pub const MINIMUM: Self = Self {
+        m_cost: 19456,
+        t_cost: 2,
+        p_cost: 1,
+    };
+
+    /// Whether any cost is below the recommended ones
+    pub fn needs_upgrade(&self) -> bool {
+        self.below(&Self::RECOMMENDED)
+    }
+
+    /// These parameters with every cost raised to at least the recommended ones
+    pub fn upgraded(&self) -> Self {
+        self.raised_to(&Self::RECOMMENDED)
+    }
+
+    /// Whether any cost is below `target`'s
+    pub fn below(&self, target: &Self) -> bool {
+        self.m_cost < target.m_cost || self.t_cost < target.t_cost || self.p_cost < target.p_cost
+    }
+
+    /// These parameters with every cost raised to at least `target`'s
+    pub fn raised_to(&self, target: &Self) -> Self {
+        Self {
+            m_cost: self.m_cost.max(target.m_cost),
+            t_cost: self.t_cost.max(target.t_cost),
+            p_cost: self.p_cost.max(target.p_cost),
+        }
+    }
+}
+
+/// Memory sizes (KiB) tried during calibration, weakest first
+const CALIBRATION_M_COSTS: [u32; 6] = [19456, 32768, 65536, 131072, 262144, 524288];
+
+/// Most iterations calibration will pick
+const CALIBRATION_MAX_T_COST: u32 = 8;
+
+/// Time one derivation with `params`
+///
+/// The key is derived from a throwaway password and salt and dropped (and so
+/// zeroed) as soon as the clock stops.
+fn time_derivation(params: &KdfParams) -> Result<Duration, GhostError> {
+    let salt = generate_salt();
+    let start = Instant::now();
+    let kek = Kek::derive("ghostnote-calibration", &salt, params)?;
+    let elapsed = start.elapsed();
+    drop(kek);
+    Ok(elapsed)
+}
+
+/// Find the strongest parameters that derive a key within `target`
+///
+/// Memory is raised first, since it's what makes GPU attacks expensive,
+/// then iterations at the chosen memory size. Parallel lanes stay at the
+/// recommended count. A device too slow for even the minimum gets the
+/// minimum anyway.
+fn calibrate(target: Duration) -> Result<KdfParams, GhostError> {
+    let p_cost = KdfParams::RECOMMENDED.p_cost;
+    let t_cost = KdfParams::MINIMUM.t_cost;
+    let mut best = KdfParams {
+        m_cost: KdfParams::MINIMUM.m_cost,
+        t_cost,
+        p_cost,
+    };
+
+    for m_cost in CALIBRATION_M_COSTS {
+        let candidate = KdfParams {
+            m_cost,
+            t_cost,
+            p_cost,
+        };
+        if time_derivation(&candidate)? > target {
+            break;
+        }
+        best = candidate;
+    }
+
+    for t_cost in (best.t_cost + 1)..=CALIBRATION_MAX_T_COST {
+        let candidate = KdfParams { t_cost, ..best };
+        if time_derivation(&candidate)? > target {
+            break;
+        }
+        best = candidate;
+    }
+
+    Ok(best)
+}
+
 impl Kek {
     /// Derive KEK from password and salt using Argon2id
-    pub fn derive(password: &str, salt: &[u8; 32]) -> Result<Self, String> {
-        let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, Some(32))
-            .map_err(|e| format!("Invalid Argon2 params: {}", e))?;
+    pub fn derive(password: &str, salt: &[u8; 32], kdf: &KdfParams) -> Result<Self, GhostError> {
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+            .map_err(|e| GhostError::Other(format!("Invalid Argon2 params: {}", e)))?;
 
         let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
         let mut kek_bytes = [0u8; 32];
         argon2
             .hash_password_into(password.as_bytes(), salt, &mut kek_bytes)
-            .map_err(|e| format!("Key derivation failed: {}", e))?;
+            .map_err(|e| GhostError::Other(format!("Key derivation failed: {}", e)))?;
 
         Ok(Kek(kek_bytes))
     }
@@ -76,6 +254,14 @@ impl Kek {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    /// A KEK from decrypted key bytes
+    fn from_slice(bytes: &[u8]) -> Result<Self, GhostError> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| GhostError::Invalid("Invalid KEK size".to_string()))?;
+        Ok(Kek(bytes))
+    }
 }
 
 impl Dek {
@@ -105,55 +291,495 @@ pub fn generate_salt() -> [u8; 32] {
 }
 
 const NONCE_SIZE: usize = 12;
+const XNONCE_SIZE: usize = 24;
+
+/// AEAD used for a ciphertext, recorded in its first byte
+///
+/// Files written before the tag existed are bare AES-256-GCM (nonce first)
+/// and are still accepted by `decrypt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cipher {
+    #[default]
+    Aes256Gcm,
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn tag(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 0x01,
+            Cipher::XChaCha20Poly1305 => 0x02,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x01 => Some(Cipher::Aes256Gcm),
+            0x02 => Some(Cipher::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::XChaCha20Poly1305 => "xchacha20-poly1305",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Cipher::Aes256Gcm, Cipher::XChaCha20Poly1305]
+            .into_iter()
+            .find(|c| c.name() == name)
+    }
+}
 
 /// Encrypt data with AES-256-GCM
-pub fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
-    let cipher =
-        Aes256Gcm::new_from_slice(key).map_err(|e| format!("Invalid key: {}", e))?;
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, GhostError> {
+    encrypt_with(Cipher::Aes256Gcm, key, plaintext)
+}
 
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
-    OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+/// Encrypt data with the given cipher, tagging the output with it
+pub fn encrypt_with(
+    cipher: Cipher,
+    key: &[u8; 32],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, GhostError> {
+    encrypt_with_aad(cipher, key, plaintext, &[])
+}
 
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .map_err(|e| format!("Encryption failed: {}", e))?;
+/// Encrypt data, authenticating `aad` alongside it
+///
+/// The same associated data must be passed to `decrypt_with_aad`.
+pub fn encrypt_with_aad(
+    cipher: Cipher,
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, GhostError> {
+    let payload = Payload { msg: plaintext, aad };
+    let (nonce_bytes, ciphertext) = match cipher {
+        Cipher::Aes256Gcm => {
+            let aead = Aes256Gcm::new_from_slice(key)
+                .map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+            let mut nonce_bytes = vec![0u8; NONCE_SIZE];
+            OsRng.fill_bytes(&mut nonce_bytes);
+            let ciphertext = aead
+                .encrypt(Nonce::from_slice(&nonce_bytes), payload)
+                .map_err(|e| GhostError::Other(format!("Encryption failed: {}", e)))?;
+            (nonce_bytes, ciphertext)
+        }
+        Cipher::XChaCha20Poly1305 => {
+            let aead = XChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+            let mut nonce_bytes = vec![0u8; XNONCE_SIZE];
+            OsRng.fill_bytes(&mut nonce_bytes);
+            let ciphertext = aead
+                .encrypt(XNonce::from_slice(&nonce_bytes), payload)
+                .map_err(|e| GhostError::Other(format!("Encryption failed: {}", e)))?;
+            (nonce_bytes, ciphertext)
+        }
+    };
 
-    // Prepend nonce to ciphertext
-    let mut result = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    // Tag, then nonce, then ciphertext
+    let mut result = Vec::with_capacity(1 + nonce_bytes.len() + ciphertext.len());
+    result.push(cipher.tag());
     result.extend_from_slice(&nonce_bytes);
     result.extend_from_slice(&ciphertext);
 
     Ok(result)
 }
 
-/// Decrypt data with AES-256-GCM
-pub fn decrypt(key: &[u8; 32], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
-    if ciphertext.len() < NONCE_SIZE {
-        return Err("Ciphertext too short".to_string());
+/// Decrypt data written by `encrypt_with`, or untagged legacy AES-256-GCM
+pub fn decrypt(key: &[u8; 32], ciphertext: &[u8]) -> Result<Vec<u8>, GhostError> {
+    decrypt_with_aad(key, ciphertext, &[])
+}
+
+/// Decrypt data written by `encrypt_with_aad` with the same associated data
+pub fn decrypt_with_aad(
+    key: &[u8; 32],
+    ciphertext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, GhostError> {
+    // A legacy nonce can start with a valid tag byte, so a failed tagged
+    // decrypt falls through to the legacy layout; the AEAD check makes a
+    // wrong guess fail rather than return garbage.
+    if let Some(cipher) = ciphertext.first().and_then(|&tag| Cipher::from_tag(tag)) {
+        if let Ok(plaintext) = decrypt_tagged(cipher, key, &ciphertext[1..], aad) {
+            return Ok(plaintext);
+        }
+    }
+
+    decrypt_tagged(Cipher::Aes256Gcm, key, ciphertext, aad)
+}
+
+/// Decrypt a nonce-prefixed ciphertext with a known cipher
+fn decrypt_tagged(
+    cipher: Cipher,
+    key: &[u8; 32],
+    data: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, GhostError> {
+    match cipher {
+        Cipher::Aes256Gcm => {
+            if data.len() < NONCE_SIZE {
+                return Err(GhostError::DecryptionFailed(
+                    "Ciphertext too short".to_string(),
+                ));
+            }
+            let aead = Aes256Gcm::new_from_slice(key)
+                .map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+            let payload = Payload {
+                msg: &data[NONCE_SIZE..],
+                aad,
+            };
+            aead.decrypt(Nonce::from_slice(&data[..NONCE_SIZE]), payload)
+                .map_err(|e| GhostError::DecryptionFailed(format!("Decryption failed: {}", e)))
+        }
+        Cipher::XChaCha20Poly1305 => {
+            if data.len() < XNONCE_SIZE {
+                return Err(GhostError::DecryptionFailed(
+                    "Ciphertext too short".to_string(),
+                ));
+            }
+            let aead = XChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+            let payload = Payload { msg: &data[XNONCE_SIZE..], aad };
+            aead.decrypt(XNonce::from_slice(&data[..XNONCE_SIZE]), payload)
+                .map_err(|e| GhostError::DecryptionFailed(format!("Decryption failed: {}", e)))
+        }
+    }
+}
+
+/// Plaintext size from which note files are encrypted in chunks
+const STREAM_THRESHOLD: usize = 1024 * 1024;
+
+/// Plaintext bytes per chunk of a chunked ciphertext
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Authentication tag appended to every chunk
+const STREAM_TAG_SIZE: usize = 16;
+
+/// Set in the tag byte of chunked ciphertexts
+const STREAM_FLAG: u8 = 0x10;
+
+/// Nonce bytes the STREAM construction reserves for its counter and last-chunk flag
+const STREAM_NONCE_OVERHEAD: usize = 5;
+
+/// Set in the tag byte of ciphertexts whose plaintext was deflated first
+const COMPRESSED_FLAG: u8 = 0x20;
+
+/// Bytes from the start of a plaintext that are test-compressed to decide
+/// whether compressing all of it is worthwhile
+const COMPRESSION_SAMPLE_SIZE: usize = 16 * 1024;
+
+/// Plaintexts shorter than this aren't compressed, as the length prefix and
+/// deflate framing outweigh anything saved
+const COMPRESSION_MIN_SIZE: usize = 256;
+
+/// Whether compressing `plaintext` is likely to make it smaller
+///
+/// Deflates a sample from the start and wants it to shrink by at least 30%.
+/// Prose easily does; base64 of an image or archive only loses the quarter
+/// its encoding added, which isn't worth the time.
+pub fn worth_compressing(plaintext: &[u8]) -> bool {
+    if plaintext.len() < COMPRESSION_MIN_SIZE {
+        return false;
+    }
+    let sample = &plaintext[..plaintext.len().min(COMPRESSION_SAMPLE_SIZE)];
+    let mut deflated = Zeroizing::new(Vec::with_capacity(deflate_bound(sample.len())));
+    let mut encoder = DeflateEncoder::new(&mut *deflated, Compression::fast());
+    if encoder.write_all(sample).and_then(|_| encoder.finish()).is_err() {
+        return false;
+    }
+    deflated.len() * 10 <= sample.len() * 7
+}
+
+/// Most bytes deflating `len` bytes can produce: stored blocks add five
+/// bytes per 64 KiB, plus a little framing
+fn deflate_bound(len: usize) -> usize {
+    len + 5 * (len / 65535 + 1) + 64
+}
+
+/// Deflate `plaintext` behind its length as eight little-endian bytes,
+/// or `None` if that doesn't come out smaller
+///
+/// The output is allocated once at its largest possible size, so it never
+/// reallocates and leaves unwiped copies behind.
+fn compress(plaintext: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    let mut out = Zeroizing::new(Vec::with_capacity(8 + deflate_bound(plaintext.len())));
+    out.extend_from_slice(&(plaintext.len() as u64).to_le_bytes());
+    let mut encoder = DeflateEncoder::new(&mut *out, Compression::default());
+    encoder.write_all(plaintext).ok()?;
+    encoder.finish().ok()?;
+    (out.len() < plaintext.len()).then_some(out)
+}
+
+/// Undo `compress`
+fn decompress(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, GhostError> {
+    let corrupt = || GhostError::DecryptionFailed("Compressed content is corrupt".to_string());
+
+    let (len, deflated) = data.split_first_chunk::<8>().ok_or_else(corrupt)?;
+    let len = u64::from_le_bytes(*len) as usize;
+    // Deflate can't shrink anything more than about 1032 to 1
+    if len > deflated.len().saturating_mul(1032) {
+        return Err(corrupt());
+    }
+
+    let mut plaintext = Zeroizing::new(vec![0u8; len]);
+    let mut decoder = DeflateDecoder::new(deflated);
+    decoder.read_exact(&mut plaintext).map_err(|_| corrupt())?;
+    if decoder.read(&mut [0u8; 1]).map_err(|_| corrupt())? != 0 {
+        return Err(corrupt());
+    }
+    Ok(plaintext)
+}
+
+/// Associated data for a compressed ciphertext
+///
+/// Compression is recorded in the tag byte, which the AEAD doesn't cover;
+/// binding it here as well means clearing or setting the flag fails
+/// authentication instead of handing back deflate output or inflating text.
+fn compressed_aad(aad: &[u8]) -> Vec<u8> {
+    let mut bound = aad.to_vec();
+    bound.extend_from_slice(b"\0deflate");
+    bound
+}
+
+/// Encrypt `plaintext` into the file at `path`
+///
+/// Small plaintexts are written in the single-shot layout of
+/// `encrypt_with_aad`. From `STREAM_THRESHOLD` up they use the STREAM
+/// construction: a tag byte with `STREAM_FLAG` set, a nonce prefix, then
+/// chunks that each carry their own authentication tag. Chunks are written
+/// as they're encrypted, so the whole ciphertext is never held in memory.
+///
+/// With `compress`, the plaintext is deflated first and `COMPRESSED_FLAG`
+/// is set in the tag byte, unless deflating doesn't make it smaller.
+pub fn encrypt_to_file(
+    cipher: Cipher,
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+    path: &Path,
+    compress: bool,
+) -> Result<(), GhostError> {
+    let write_err =
+        |e: std::io::Error| GhostError::Io(format!("Failed to write encrypted file: {}", e));
+
+    let compressed = if compress {
+        self::compress(plaintext)
+    } else {
+        None
+    };
+    let (plaintext, bound_aad, flags) = match &compressed {
+        Some(deflated) => (&deflated[..], compressed_aad(aad), COMPRESSED_FLAG),
+        None => (plaintext, aad.to_vec(), 0),
+    };
+
+    if plaintext.len() < STREAM_THRESHOLD {
+        let mut ciphertext = encrypt_with_aad(cipher, key, plaintext, &bound_aad)?;
+        ciphertext[0] |= flags;
+        return fs::write(path, ciphertext).map_err(write_err);
+    }
+
+    let aad = &bound_aad[..];
+    let mut out = BufWriter::new(fs::File::create(path).map_err(write_err)?);
+    out.write_all(&[cipher.tag() | STREAM_FLAG | flags])
+        .map_err(write_err)?;
+    match cipher {
+        Cipher::Aes256Gcm => encrypt_stream::<Aes256Gcm>(key, plaintext, aad, &mut out)?,
+        Cipher::XChaCha20Poly1305 => {
+            encrypt_stream::<XChaCha20Poly1305>(key, plaintext, aad, &mut out)?
+        }
+    }
+    out.flush().map_err(write_err)
+}
+
+/// Decrypt a file written by `encrypt_to_file`, or any layout `decrypt_with_aad` accepts
+///
+/// Compressed plaintext is inflated on the way out. Chunked files are read
+/// a chunk at a time, so only the plaintext is held in full, and it's wiped
+/// when the returned buffer is dropped.
+pub fn decrypt_file(
+    key: &[u8; 32],
+    path: &Path,
+    aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, GhostError> {
+    let read_err =
+        |e: std::io::Error| GhostError::Io(format!("Failed to read encrypted file: {}", e));
+    let mut file = fs::File::open(path).map_err(read_err)?;
+    let file_len = file.metadata().map_err(read_err)?.len() as usize;
+
+    let mut tag = [0u8; 1];
+    let flags = STREAM_FLAG | COMPRESSED_FLAG;
+    if file.read(&mut tag).map_err(read_err)? == 1 && tag[0] & flags != 0 {
+        // As with single-shot tags, a legacy nonce can start with this byte;
+        // if the flagged layout doesn't authenticate, try the others
+        if let Some(cipher) = Cipher::from_tag(tag[0] & !flags) {
+            let compressed = tag[0] & COMPRESSED_FLAG != 0;
+            let aad = if compressed {
+                compressed_aad(aad)
+            } else {
+                aad.to_vec()
+            };
+            let result = if tag[0] & STREAM_FLAG != 0 {
+                let mut reader = BufReader::new(&mut file);
+                match cipher {
+                    Cipher::Aes256Gcm => {
+                        decrypt_stream::<Aes256Gcm>(key, &mut reader, &aad, file_len)
+                    }
+                    Cipher::XChaCha20Poly1305 => {
+                        decrypt_stream::<XChaCha20Poly1305>(key, &mut reader, &aad, file_len)
+                    }
+                }
+            } else {
+                let mut ciphertext = Vec::new();
+                file.read_to_end(&mut ciphertext).map_err(read_err)?;
+                decrypt_tagged(cipher, key, &ciphertext, &aad).map(Zeroizing::new)
+            };
+            if let Ok(plaintext) = result {
+                return if compressed {
+                    decompress(&plaintext)
+                } else {
+                    Ok(plaintext)
+                };
+            }
+        }
+    }
+
+    file.seek(SeekFrom::Start(0)).map_err(read_err)?;
+    let mut ciphertext = Vec::new();
+    file.read_to_end(&mut ciphertext).map_err(read_err)?;
+    decrypt_with_aad(key, &ciphertext, aad).map(Zeroizing::new)
+}
+
+/// Write the nonce prefix and chunks of a STREAM ciphertext
+fn encrypt_stream<A>(
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+    out: &mut impl Write,
+) -> Result<(), GhostError>
+where
+    A: AeadInPlace + KeyInit,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+{
+    let write_err =
+        |e: std::io::Error| GhostError::Io(format!("Failed to write encrypted file: {}", e));
+    let encrypt_err = |_| GhostError::Other("Encryption failed".to_string());
+
+    let aead =
+        A::new_from_slice(key).map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+    let mut nonce = vec![0u8; A::NonceSize::USIZE - STREAM_NONCE_OVERHEAD];
+    OsRng.fill_bytes(&mut nonce);
+    let mut encryptor = EncryptorBE32::from_aead(aead, GenericArray::from_slice(&nonce));
+    out.write_all(&nonce).map_err(write_err)?;
+
+    let mut buffer = Zeroizing::new(Vec::with_capacity(STREAM_CHUNK_SIZE + STREAM_TAG_SIZE));
+    let mut chunks = plaintext.chunks(STREAM_CHUNK_SIZE).peekable();
+    while let Some(chunk) = chunks.next() {
+        buffer.clear();
+        buffer.extend_from_slice(chunk);
+        if chunks.peek().is_some() {
+            encryptor
+                .encrypt_next_in_place(aad, &mut *buffer)
+                .map_err(encrypt_err)?;
+            out.write_all(&buffer).map_err(write_err)?;
+        } else {
+            encryptor
+                .encrypt_last_in_place(aad, &mut *buffer)
+                .map_err(encrypt_err)?;
+            out.write_all(&buffer).map_err(write_err)?;
+            break;
+        }
     }
 
-    let cipher =
-        Aes256Gcm::new_from_slice(key).map_err(|e| format!("Invalid key: {}", e))?;
+    Ok(())
+}
+
+/// Read the nonce prefix and chunks of a STREAM ciphertext, after its tag byte
+///
+/// `capacity` bounds the plaintext size, so the output never reallocates and
+/// leaves unwiped copies behind.
+fn decrypt_stream<A>(
+    key: &[u8; 32],
+    reader: &mut impl Read,
+    aad: &[u8],
+    capacity: usize,
+) -> Result<Zeroizing<Vec<u8>>, GhostError>
+where
+    A: AeadInPlace + KeyInit,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+{
+    let read_err =
+        |e: std::io::Error| GhostError::Io(format!("Failed to read encrypted file: {}", e));
+    let decrypt_err = |_| GhostError::DecryptionFailed("Decryption failed".to_string());
+
+    let mut nonce = vec![0u8; A::NonceSize::USIZE - STREAM_NONCE_OVERHEAD];
+    reader.read_exact(&mut nonce).map_err(read_err)?;
+    let aead =
+        A::new_from_slice(key).map_err(|e| GhostError::Other(format!("Invalid key: {}", e)))?;
+    let mut decryptor = DecryptorBE32::from_aead(aead, GenericArray::from_slice(&nonce));
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(capacity));
+    let mut buffer = Zeroizing::new(vec![0u8; STREAM_CHUNK_SIZE + STREAM_TAG_SIZE]);
+    // A chunk is the last one when it's short or nothing follows it, so
+    // keep one chunk read ahead
+    let mut filled = read_chunk(reader, &mut buffer).map_err(read_err)?;
+    loop {
+        let mut next = Zeroizing::new(vec![0u8; STREAM_CHUNK_SIZE + STREAM_TAG_SIZE]);
+        let next_filled = if filled == buffer.len() {
+            read_chunk(reader, &mut next).map_err(read_err)?
+        } else {
+            0
+        };
+
+        buffer.truncate(filled);
+        if next_filled == 0 {
+            decryptor
+                .decrypt_last_in_place(aad, &mut *buffer)
+                .map_err(decrypt_err)?;
+            plaintext.extend_from_slice(&buffer);
+            return Ok(plaintext);
+        }
+        decryptor
+            .decrypt_next_in_place(aad, &mut *buffer)
+            .map_err(decrypt_err)?;
+        plaintext.extend_from_slice(&buffer);
 
-    let nonce = Nonce::from_slice(&ciphertext[..NONCE_SIZE]);
-    let encrypted_data = &ciphertext[NONCE_SIZE..];
+        buffer = next;
+        filled = next_filled;
+    }
+}
 
-    cipher
-        .decrypt(nonce, encrypted_data)
-        .map_err(|e| format!("Decryption failed: {}", e))
+/// Fill `buffer` from `reader`, stopping early only at end of file
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
 }
 
 /// Wrap a DEK with the KEK (encrypt the DEK)
-pub fn wrap_dek(kek: &Kek, dek: &Dek) -> Result<Vec<u8>, String> {
-    encrypt(kek.as_bytes(), dek.as_bytes())
+pub fn wrap_dek(kek: &Kek, dek: &Dek, cipher: Cipher) -> Result<Vec<u8>, GhostError> {
+    encrypt_with(cipher, kek.as_bytes(), dek.as_bytes())
 }
 
 /// Unwrap a DEK with the KEK (decrypt the DEK)
-pub fn unwrap_dek(kek: &Kek, wrapped_dek: &[u8]) -> Result<Dek, String> {
+pub fn unwrap_dek(kek: &Kek, wrapped_dek: &[u8]) -> Result<Dek, GhostError> {
     let dek_bytes = decrypt(kek.as_bytes(), wrapped_dek)?;
     if dek_bytes.len() != 32 {
-        return Err("Invalid DEK size".to_string());
+        return Err(GhostError::Invalid("Invalid DEK size".to_string()));
     }
     let mut arr = [0u8; 32];
     arr.copy_from_slice(&dek_bytes);
@@ -196,37 +822,87 @@ impl RecoveryKey {
 }
 
 /// Recovery data stored encrypted in vault
+///
+/// The recovery key is derived with a salt and KDF parameters of its own,
+/// so re-keying the vault doesn't change what it derives. Data written
+/// before those were stored has none of the optional fields and derives
+/// with the vault's salt and parameters instead.
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct RecoveryData {
     pub kek_bytes: Vec<u8>, // The actual KEK, encrypted with recovery key
+    #[serde(default)]
+    pub salt: Option<[u8; 32]>,
+    #[serde(default)]
+    pub kdf: Option<KdfParams>,
+    /// The recovery-key-derived key, encrypted with the KEK, so a re-key can
+    /// encrypt the new KEK for the same recovery key
+    #[serde(default)]
+    pub recovery_kek: Option<Vec<u8>>,
 }
 
 impl RecoveryData {
     /// Create recovery data by encrypting the KEK with a recovery-key-derived key
-    pub fn create(kek: &Kek, recovery_key: &RecoveryKey, salt: &[u8; 32]) -> Result<Self, String> {
+    pub fn create(
+        kek: &Kek,
+        recovery_key: &RecoveryKey,
+        kdf: &KdfParams,
+    ) -> Result<Self, GhostError> {
         // Derive a key from the recovery key
-        let recovery_kek = Kek::derive(&recovery_key.0.replace('-', ""), salt)?;
-
-        // Encrypt the original KEK with the recovery-derived key
-        let encrypted_kek = encrypt(recovery_kek.as_bytes(), kek.as_bytes())?;
+        let salt = generate_salt();
+        let recovery_kek = Kek::derive(&recovery_key.0.replace('-', ""), &salt, kdf)?;
+        Self::seal(kek, &recovery_kek, salt, *kdf)
+    }
 
+    /// Encrypt the KEK with the recovery-derived key, and that key with the KEK
+    fn seal(
+        kek: &Kek,
+        recovery_kek: &Kek,
+        salt: [u8; 32],
+        kdf: KdfParams,
+    ) -> Result<Self, GhostError> {
         Ok(RecoveryData {
-            kek_bytes: encrypted_kek,
+            kek_bytes: encrypt(recovery_kek.as_bytes(), kek.as_bytes())?,
+            salt: Some(salt),
+            kdf: Some(kdf),
+            recovery_kek: Some(encrypt(kek.as_bytes(), recovery_kek.as_bytes())?),
         })
     }
 
     /// Recover the KEK using the recovery key
-    pub fn recover_kek(&self, recovery_key: &RecoveryKey, salt: &[u8; 32]) -> Result<Kek, String> {
-        let recovery_kek = Kek::derive(&recovery_key.0.replace('-', ""), salt)?;
-        let kek_bytes = decrypt(recovery_kek.as_bytes(), &self.kek_bytes)?;
+    ///
+    /// `salt` and `kdf` are the vault's, used only by data that predates
+    /// storing its own.
+    pub fn recover_kek(
+        &self,
+        recovery_key: &RecoveryKey,
+        salt: &[u8; 32],
+        kdf: &KdfParams,
+    ) -> Result<Kek, GhostError> {
+        let salt = self.salt.as_ref().unwrap_or(salt);
+        let kdf = self.kdf.as_ref().unwrap_or(kdf);
+        let recovery_kek = Kek::derive(&recovery_key.0.replace('-', ""), salt, kdf)?;
+        let kek_bytes = Zeroizing::new(decrypt(recovery_kek.as_bytes(), &self.kek_bytes)?);
+        Kek::from_slice(&kek_bytes)
+    }
 
-        if kek_bytes.len() != 32 {
-            return Err("Invalid KEK size".to_string());
-        }
+    /// Whether a re-key can carry this data over (see `rewrapped`)
+    pub fn carries_over(&self) -> bool {
+        self.salt.is_some() && self.kdf.is_some() && self.recovery_kek.is_some()
+    }
 
-        let mut arr = [0u8; 32];
-        arr.copy_from_slice(&kek_bytes);
-        Ok(Kek(arr))
+    /// This data carried over to `new_kek`, so the same recovery key
+    /// recovers it
+    ///
+    /// `None` for data that predates storing what that needs; only a new
+    /// recovery key can cover the new KEK then.
+    pub fn rewrapped(&self, old_kek: &Kek, new_kek: &Kek) -> Result<Option<Self>, GhostError> {
+        let (Some(salt), Some(kdf), Some(wrapped)) = (self.salt, self.kdf, &self.recovery_kek)
+        else {
+            return Ok(None);
+        };
+        let recovery_kek = Zeroizing::new(decrypt(old_kek.as_bytes(), wrapped)?);
+        let recovery_kek = Kek::from_slice(&recovery_kek)?;
+        Self::seal(new_kek, &recovery_kek, salt, kdf).map(Some)
     }
 }
 
@@ -239,73 +915,433 @@ impl Clone for VaultConfig {
             salt_path: self.salt_path.clone(),
             verify_path: self.verify_path.clone(),
             recovery_path: self.recovery_path.clone(),
+            content_version_path: self.content_version_path.clone(),
+            cipher_path: self.cipher_path.clone(),
+            kdf_path: self.kdf_path.clone(),
+            recents_path: self.recents_path.clone(),
+            meta_cache_path: self.meta_cache_path.clone(),
+            reencrypt_progress_path: self.reencrypt_progress_path.clone(),
         }
     }
 }
 
-/// Re-wrap all existing DEKs with a new KEK
+/// Suffix for files staged during a re-key, renamed over the originals on commit
+const STAGED_SUFFIX: &str = ".new";
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut staged = path.as_os_str().to_owned();
+    staged.push(STAGED_SUFFIX);
+    PathBuf::from(staged)
+}
+
+fn rekey_journal_path(config: &VaultConfig) -> PathBuf {
+    config.vault_dir.join("rekey.journal")
+}
+
+/// Write `data` next to `target` as a staged file and remember the target
+fn stage_file(
+    notes_dir: &Path,
+    target: &Path,
+    data: &[u8],
+    staged: &mut Vec<String>,
+) -> Result<(), GhostError> {
+    fs::write(staged_path(target), data)
+        .map_err(|e| GhostError::Io(format!("Failed to stage {}: {}", target.display(), e)))?;
+    staged.push(target.strip_prefix(notes_dir).unwrap_or(target).to_string_lossy().to_string());
+    Ok(())
+}
+
+/// Stage every DEK re-wrapped with the new KEK, plus the new salt, KDF
+/// parameters, verify blob and recovery data. Nothing live is modified here.
 ///
-/// This must be called when changing the password or recovering the vault,
-/// otherwise existing notes will become unreadable.
-fn rewrap_all_deks(notes_dir: &PathBuf, old_kek: &Kek, new_kek: &Kek) -> Result<usize, String> {
+/// The recovery data is carried over so the user's recovery key keeps
+/// working. Data too old for that is recreated for `recovery_key` when the
+/// caller has it, else for a new key, which is returned.
+///
+/// Fails, listing them, if any key file can't be read or unwrapped: those
+/// notes would stay wrapped under the old KEK and never open again.
+fn stage_rekey(
+    config: &VaultConfig,
+    old_kek: &Kek,
+    new_kek: &Kek,
+    new_salt: &[u8; 32],
+    kdf: &KdfParams,
+    recovery_key: Option<&RecoveryKey>,
+    staged: &mut Vec<String>,
+) -> Result<(Option<RecoveryKey>, usize), GhostError> {
     use walkdir::WalkDir;
 
     let mut rewrapped_count = 0;
-
-    for entry in WalkDir::new(notes_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
+    let mut unreadable = Vec::new();
+    let rel = |path: &Path| {
+        path.strip_prefix(&config.notes_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    for entry in WalkDir::new(&config.notes_dir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                unreadable.push(rel(e.path().unwrap_or(&config.notes_dir)));
+                continue;
+            }
+        };
         let path = entry.path();
 
         // Skip the .vault directory
-        if path.starts_with(notes_dir.join(".vault")) {
+        if path.starts_with(&config.vault_dir) {
             continue;
         }
 
         // Process .key files
-        if path.extension().map_or(false, |ext| ext == "key") {
-            // Read the wrapped DEK
-            let wrapped_dek = match fs::read(path) {
-                Ok(data) => data,
-                Err(e) => {
-                    eprintln!("Warning: Failed to read {}: {}", path.display(), e);
-                    continue;
-                }
-            };
-
-            // Unwrap with old KEK
-            let dek = match unwrap_dek(old_kek, &wrapped_dek) {
+        if path.extension().is_some_and(|ext| ext == "key") {
+            // Read the wrapped DEK and unwrap it with the old KEK
+            let dek = match fs::read(path)
+                .map_err(|e| GhostError::Io(e.to_string()))
+                .and_then(|wrapped_dek| unwrap_dek(old_kek, &wrapped_dek))
+            {
                 Ok(d) => d,
                 Err(e) => {
                     eprintln!("Warning: Failed to unwrap DEK at {}: {}", path.display(), e);
+                    unreadable.push(rel(path));
                     continue;
                 }
             };
 
             // Re-wrap with new KEK
-            let new_wrapped_dek = match wrap_dek(new_kek, &dek) {
-                Ok(w) => w,
-                Err(e) => {
-                    return Err(format!("Failed to re-wrap DEK at {}: {}", path.display(), e));
-                }
-            };
+            let new_wrapped_dek = wrap_dek(new_kek, &dek, config.cipher()).map_err(|e| {
+                GhostError::Other(format!(
+                    "Failed to re-wrap DEK at {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            stage_file(&config.notes_dir, path, &new_wrapped_dek, staged)?;
+            rewrapped_count += 1;
+        }
+    }
+
+    if !unreadable.is_empty() {
+        return Err(GhostError::Other(format!(
+            "Couldn't re-key the vault, nothing was changed. These can't be read: {}",
+            unreadable.join(", ")
+        )));
+    }
+
+    stage_file(&config.notes_dir, &config.salt_path, new_salt, staged)?;
+
+    let kdf_json = serde_json::to_vec(kdf)
+        .map_err(|e| GhostError::Other(format!("Failed to serialize KDF parameters: {}", e)))?;
+    stage_file(&config.notes_dir, &config.kdf_path, &kdf_json, staged)?;
+
+    let verify_encrypted = encrypt(new_kek.as_bytes(), b"ghostnote-verify")?;
+    stage_file(&config.notes_dir, &config.verify_path, &verify_encrypted, staged)?;
+
+    let carried_over = read_recovery_data(config)
+        .ok()
+        .and_then(|data| data.rewrapped(old_kek, new_kek).ok().flatten());
+    let (recovery_data, new_recovery_key) = match (carried_over, recovery_key) {
+        (Some(data), _) => (data, None),
+        (None, Some(key)) => (RecoveryData::create(new_kek, key, kdf)?, None),
+        (None, None) => {
+            let key = RecoveryKey::generate();
+            (RecoveryData::create(new_kek, &key, kdf)?, Some(key))
+        }
+    };
+    let recovery_json = serde_json::to_vec(&recovery_data)
+        .map_err(|e| GhostError::Other(format!("Failed to serialize recovery data: {}", e)))?;
+    stage_file(
+        &config.notes_dir,
+        &config.recovery_path,
+        &recovery_json,
+        staged,
+    )?;
+
+    Ok((new_recovery_key, rewrapped_count))
+}
+
+/// Move every file listed in a committed re-key journal into place
+///
+/// Safe to run repeatedly: files already renamed are skipped. Called right
+/// after committing, and at startup to finish a re-key a crash interrupted.
+fn finish_rekey(config: &VaultConfig) -> Result<(), GhostError> {
+    let journal = rekey_journal_path(config);
+    let data = match fs::read(&journal) {
+        Ok(data) => data,
+        Err(_) => return Ok(()),
+    };
+    let targets: Vec<String> = serde_json::from_slice(&data)
+        .map_err(|e| GhostError::Other(format!("Invalid re-key journal: {}", e)))?;
+
+    for target in targets {
+        let target = config.notes_dir.join(target);
+        let staged = staged_path(&target);
+        if staged.exists() {
+            fs::rename(&staged, &target).map_err(|e| {
+                GhostError::Io(format!("Failed to replace {}: {}", target.display(), e))
+            })?;
+        }
+    }
+
+    fs::remove_file(&journal)
+        .map_err(|e| GhostError::Io(format!("Failed to remove re-key journal: {}", e)))
+}
+
+/// Whether `path` is a file a re-key could have staged: a `.new` copy of a
+/// note's key file or of one of the vault's own files
+///
+/// Anything else ending in `.new` belongs to the user and is left alone.
+fn is_staged_rekey_file(config: &VaultConfig, path: &Path) -> bool {
+    let Some(target) = path
+        .to_str()
+        .and_then(|p| p.strip_suffix(STAGED_SUFFIX))
+        .map(Path::new)
+    else {
+        return false;
+    };
+
+    let vault_files = [
+        &config.salt_path,
+        &config.kdf_path,
+        &config.verify_path,
+        &config.recovery_path,
+    ];
+    if vault_files.iter().any(|file| file.as_path() == target)
+        || target == rekey_journal_path(config)
+    {
+        return true;
+    }
+    target.extension().is_some_and(|ext| ext == "key") && target.is_file()
+}
+
+/// Bring the vault back to a consistent state after an interrupted re-key
+///
+/// With a journal the re-key had committed and is rolled forward; without
+/// one it never committed, so any files it staged are discarded.
+fn recover_interrupted_rekey(config: &VaultConfig) {
+    use walkdir::WalkDir;
+
+    if rekey_journal_path(config).exists() {
+        if let Err(e) = finish_rekey(config) {
+            eprintln!("Warning: Failed to finish interrupted re-key: {}", e);
+        }
+        return;
+    }
+
+    for entry in WalkDir::new(&config.notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| is_staged_rekey_file(config, e.path()))
+    {
+        let _ = fs::remove_file(entry.path());
+    }
+}
+
+/// Re-key the vault under a new password
+///
+/// Every DEK is re-wrapped with a KEK derived from `new_password`, a fresh
+/// salt and `kdf`; note ciphertext is untouched. All new files are staged
+/// first and committed by atomically writing a journal, so a crash either leaves the
+/// old vault fully intact or is rolled forward on next start - never a mix
+/// of keys wrapped under the old and new KEK. The vault is left unlocked
+/// with the new KEK. Returns the recovery key's replacement if it couldn't
+/// be kept.
+fn rekey_vault(
+    vault: &VaultState,
+    old_kek: Kek,
+    new_password: &str,
+    kdf: &KdfParams,
+    recovery_key: Option<&RecoveryKey>,
+) -> Result<Option<RecoveryKey>, GhostError> {
+    let config = vault.config()?;
+    let new_salt = generate_salt();
+    let new_kek = Kek::derive(new_password, &new_salt, kdf)?;
+    vault.rekey_from(old_kek, |old_kek| {
+        let new_recovery_key =
+            commit_rekey(&config, old_kek, &new_kek, &new_salt, kdf, recovery_key)?;
+        Ok((new_kek, new_recovery_key))
+    })
+}
 
-            // Write back
-            if let Err(e) = fs::write(path, &new_wrapped_dek) {
-                return Err(format!("Failed to write re-wrapped DEK at {}: {}", path.display(), e));
+/// Body of `rekey_vault` once the new KEK is derived
+fn commit_rekey(
+    config: &VaultConfig,
+    old_kek: &Kek,
+    new_kek: &Kek,
+    new_salt: &[u8; 32],
+    kdf: &KdfParams,
+    recovery_key: Option<&RecoveryKey>,
+) -> Result<Option<RecoveryKey>, GhostError> {
+    let mut staged = Vec::new();
+    let staged_result = stage_rekey(
+        config,
+        old_kek,
+        new_kek,
+        new_salt,
+        kdf,
+        recovery_key,
+        &mut staged,
+    );
+    let (new_recovery_key, rewrapped) = match staged_result {
+        Ok(result) => result,
+        Err(e) => {
+            for target in &staged {
+                let _ = fs::remove_file(staged_path(&config.notes_dir.join(target)));
             }
+            return Err(e);
+        }
+    };
 
-            rewrapped_count += 1;
+    // Commit point: once the journal exists the re-key will complete
+    let journal = rekey_journal_path(config);
+    let journal_json = serde_json::to_vec(&staged)
+        .map_err(|e| GhostError::Other(format!("Failed to serialize re-key journal: {}", e)))?;
+    fs::write(staged_path(&journal), journal_json)
+        .and_then(|_| fs::rename(staged_path(&journal), &journal))
+        .map_err(|e| GhostError::Io(format!("Failed to write re-key journal: {}", e)))?;
+
+    finish_rekey(config)?;
+
+    if rewrapped > 0 {
+        eprintln!("Re-wrapped {} note DEKs with new KEK", rewrapped);
+    }
+    if new_recovery_key.is_some() {
+        recovery_key_issued();
+    }
+
+    Ok(new_recovery_key)
+}
+
+/// Content version persisted across restarts
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct ContentVersion {
+    version: u64,
+    /// Structural hash of the notes tree when the version was last reconciled
+    structure: String,
+}
+
+/// Hash the shape of the notes tree (paths, sizes, mtimes) without reading content
+///
+/// Used at startup to notice changes made while the app wasn't running.
+fn structural_hash(notes_dir: &PathBuf) -> String {
+    use walkdir::WalkDir;
+
+    let mut entries: Vec<(String, u64, u128)> = WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path().starts_with(notes_dir.join(".vault")))
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            let rel = e.path().strip_prefix(notes_dir).ok()?;
+            Some((rel.to_string_lossy().to_string(), metadata.len(), modified))
+        })
+        .collect();
+    entries.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    for (path, len, modified) in &entries {
+        hasher.update(path.as_bytes());
+        hasher.update(&len.to_le_bytes());
+        hasher.update(&modified.to_le_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+fn write_content_version(config: &VaultConfig, stored: &ContentVersion) {
+    let result = fs::create_dir_all(&config.vault_dir)
+        .and_then(|_| fs::write(&config.content_version_path, serde_json::to_vec(stored)?));
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to persist content version: {}", e);
+    }
+}
+
+/// Default cap on the decrypted notes kept in memory, in bytes
+pub const DEFAULT_NOTE_CACHE_BYTES: usize = 8 * 1024 * 1024;
+
+/// A decrypted note, as of one version of its file
+struct CachedNote {
+    path: String,
+    modified: SystemTime,
+    len: u64,
+    content: Zeroizing<String>,
+}
+
+/// Recently read notes, so reopening one doesn't decrypt it again
+///
+/// Entries run from least to most recently used, and the oldest go first
+/// once the content held passes `max_bytes`. Each entry also remembers its
+/// file's modification time and size, so a change we weren't told about
+/// still misses.
+struct NoteCache {
+    entries: Vec<CachedNote>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl NoteCache {
+    fn get(&mut self, path: &str, modified: SystemTime, len: u64) -> Option<Zeroizing<String>> {
+        let pos = self.entries.iter().position(|e| e.path == path)?;
+        if self.entries[pos].modified != modified || self.entries[pos].len != len {
+            self.remove(path);
+            return None;
+        }
+        let entry = self.entries.remove(pos);
+        let content = entry.content.clone();
+        self.entries.push(entry);
+        Some(content)
+    }
+
+    fn insert(&mut self, path: &str, modified: SystemTime, len: u64, content: &str) {
+        self.remove(path);
+        if content.len() > self.max_bytes {
+            return;
         }
+        self.entries.push(CachedNote {
+            path: path.to_string(),
+            modified,
+            len,
+            content: Zeroizing::new(content.to_string()),
+        });
+        self.bytes += content.len();
+        self.evict();
     }
 
-    Ok(rewrapped_count)
+    fn remove(&mut self, path: &str) {
+        if let Some(pos) = self.entries.iter().position(|e| e.path == path) {
+            self.bytes -= self.entries.remove(pos).content.len();
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.max_bytes {
+            let oldest = self.entries.remove(0);
+            self.bytes -= oldest.content.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
 }
 
 /// Thread-safe vault state
 pub struct VaultState {
     inner: Mutex<VaultStateInner>,
+    /// One lock per note's `.enc`/`.key` pair, by base path; see
+    /// `with_note_lock`
+    note_locks: Mutex<HashMap<PathBuf, Weak<RwLock<()>>>>,
+    /// Held shared while note files are written and exclusively while
+    /// `rekey_with` swaps the KEK; see `without_rekey`
+    rekey_lock: RwLock<()>,
 }
 
 struct VaultStateInner {
@@ -313,6 +1349,8 @@ struct VaultStateInner {
     config: Option<VaultConfig>,
     last_activity: Instant,
     lock_timeout: Duration,
+    content_version: ContentVersion,
+    note_cache: NoteCache,
 }
 
 impl Default for VaultState {
@@ -323,7 +1361,15 @@ impl Default for VaultState {
                 config: None,
                 last_activity: Instant::now(),
                 lock_timeout: Duration::from_secs(300), // 5 minutes default
+                content_version: ContentVersion::default(),
+                note_cache: NoteCache {
+                    entries: Vec::new(),
+                    bytes: 0,
+                    max_bytes: DEFAULT_NOTE_CACHE_BYTES,
+                },
             }),
+            note_locks: Mutex::new(HashMap::new()),
+            rekey_lock: RwLock::new(()),
         }
     }
 }
@@ -335,8 +1381,46 @@ impl VaultState {
 
     /// Initialize vault config (called on app start)
     pub fn set_config(&self, config: VaultConfig) {
+        recover_interrupted_rekey(&config);
+        recover_interrupted_saves(&config.notes_dir);
+
+        let mut stored: ContentVersion = fs::read(&config.content_version_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+
+        // Anything that changed while we weren't running invalidates caches too
+        let structure = structural_hash(&config.notes_dir);
+        if structure != stored.structure {
+            stored.version += 1;
+            stored.structure = structure;
+            write_content_version(&config, &stored);
+        }
+
         let mut inner = self.inner.lock().unwrap();
+        inner.content_version = stored;
         inner.config = Some(config);
+        // Cached notes are keyed by path within the old folder
+        inner.note_cache.clear();
+    }
+
+    /// Current content version; changes whenever any note or folder does
+    pub fn content_version(&self) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.content_version.version
+    }
+
+    /// Record a mutation of notes or folders
+    ///
+    /// The structural hash is left as-is, so the next startup sees a
+    /// difference and bumps once more; an extra bump only costs a cache
+    /// rebuild, while walking the tree on every save would not be free.
+    pub fn bump_content_version(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.content_version.version += 1;
+        if let Some(config) = &inner.config {
+            write_content_version(config, &inner.content_version);
+        }
     }
 
     /// Check if vault is unlocked
@@ -356,6 +1440,7 @@ impl VaultState {
     pub fn lock(&self) {
         let mut inner = self.inner.lock().unwrap();
         inner.kek = None; // Zeroize will clear memory
+        inner.note_cache.clear();
     }
 
     /// Record user activity (resets auto-lock timer)
@@ -370,14 +1455,51 @@ impl VaultState {
         inner.lock_timeout = Duration::from_secs(seconds);
     }
 
-    /// Get time remaining until auto-lock (in seconds)
-    pub fn time_until_lock(&self) -> u64 {
-        let inner = self.inner.lock().unwrap();
-        let elapsed = inner.last_activity.elapsed();
-        if elapsed >= inner.lock_timeout {
-            0
-        } else {
-            (inner.lock_timeout - elapsed).as_secs()
+    /// Cap the decrypted notes kept in memory at `bytes` (0 turns caching off)
+    pub fn set_note_cache_bytes(&self, bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.note_cache.max_bytes = bytes;
+        inner.note_cache.evict();
+    }
+
+    /// A note's decrypted content, if it was cached from the file as it is
+    /// now, given as its modification time and size
+    pub fn cached_note(
+        &self,
+        rel_path: &str,
+        modified: SystemTime,
+        len: u64,
+    ) -> Option<Zeroizing<String>> {
+        let mut inner = self.inner.lock().unwrap();
+        // Nothing is served while the vault is locked
+        inner.kek.as_ref()?;
+        let content = inner.note_cache.get(rel_path, modified, len)?;
+        inner.last_activity = Instant::now();
+        Some(content)
+    }
+
+    /// Keep a note's decrypted content for the next read of the same file
+    pub fn cache_note(&self, rel_path: &str, modified: SystemTime, len: u64, content: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.kek.is_some() {
+            inner.note_cache.insert(rel_path, modified, len, content);
+        }
+    }
+
+    /// Drop a note from the cache after it changed or went away
+    pub fn forget_note(&self, rel_path: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.note_cache.remove(rel_path);
+    }
+
+    /// Get time remaining until auto-lock (in seconds)
+    pub fn time_until_lock(&self) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        let elapsed = inner.last_activity.elapsed();
+        if elapsed >= inner.lock_timeout {
+            0
+        } else {
+            (inner.lock_timeout - elapsed).as_secs()
         }
     }
 
@@ -387,22 +1509,115 @@ impl VaultState {
         inner.kek.is_some() && inner.last_activity.elapsed() >= inner.lock_timeout
     }
 
+    /// Run `f` holding the lock on the note files at `base_path`, exclusively
+    /// when `exclusive` is set and shared with other readers otherwise
+    ///
+    /// The lock covers a note's `.enc`/`.key` pair as one unit, so writing it
+    /// serializes with every other write and read of the same note, while
+    /// different notes never wait on each other. It's in-process only: it
+    /// keeps our own saves, reads and watcher-triggered reloads apart, not
+    /// other programs touching the files. Moves and deletes rename whole
+    /// pairs and don't take it.
+    pub fn with_note_lock<T>(&self, base_path: &Path, exclusive: bool, f: impl FnOnce() -> T) -> T {
+        let lock = {
+            let mut locks = self.note_locks.lock().unwrap();
+            match locks.get(base_path).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    // Forget locks no one holds any more before adding one
+                    locks.retain(|_, lock| lock.strong_count() > 0);
+                    let lock = Arc::new(RwLock::new(()));
+                    locks.insert(base_path.to_path_buf(), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+
+        // The lock guards no data, so one poisoned by a panic is still sound
+        if exclusive {
+            let _guard = lock.write().unwrap_or_else(PoisonError::into_inner);
+            f()
+        } else {
+            let _guard = lock.read().unwrap_or_else(PoisonError::into_inner);
+            f()
+        }
+    }
+
+    /// Run `f` with no `rekey_with` in progress
+    ///
+    /// Note writes go through this, so none wraps a DEK with the old KEK
+    /// after the re-key has staged its key files.
+    pub fn without_rekey<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self
+            .rekey_lock
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        f()
+    }
+
+    /// Re-key the unlocked vault with `rekey`, which is handed the current
+    /// KEK and returns its replacement
+    ///
+    /// Note writes wait until the new KEK is in place, and nothing can
+    /// unwrap a DEK with the old KEK once the new key files are.
+    pub fn rekey_with<T>(
+        &self,
+        rekey: impl FnOnce(&Kek) -> Result<(Kek, T), GhostError>,
+    ) -> Result<T, GhostError> {
+        let _guard = self
+            .rekey_lock
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut inner = self.inner.lock().unwrap();
+        let old_kek = inner.kek.as_ref().ok_or(GhostError::VaultLocked)?;
+        let (new_kek, result) = rekey(old_kek)?;
+        inner.kek = Some(new_kek);
+        Ok(result)
+    }
+
+    /// Like `rekey_with`, but from `old_kek`, which the caller verified, so
+    /// the vault may be locked; it's unlocked with the new KEK afterwards
+    pub fn rekey_from<T>(
+        &self,
+        old_kek: Kek,
+        rekey: impl FnOnce(&Kek) -> Result<(Kek, T), GhostError>,
+    ) -> Result<T, GhostError> {
+        let _guard = self
+            .rekey_lock
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut inner = self.inner.lock().unwrap();
+        let (new_kek, result) = rekey(&old_kek)?;
+        inner.kek = Some(new_kek);
+        inner.last_activity = Instant::now();
+        Ok(result)
+    }
+
     /// Execute operation with KEK (returns error if locked)
-    pub fn with_kek<F, T>(&self, f: F) -> Result<T, String>
+    ///
+    /// A successful operation counts as activity and resets the auto-lock timer.
+    pub fn with_kek<F, T>(&self, f: F) -> Result<T, GhostError>
     where
-        F: FnOnce(&Kek) -> Result<T, String>,
+        F: FnOnce(&Kek) -> Result<T, GhostError>,
     {
-        let inner = self.inner.lock().unwrap();
-        match &inner.kek {
+        let mut inner = self.inner.lock().unwrap();
+        let result = match &inner.kek {
             Some(kek) => f(kek),
-            None => Err("Vault is locked".to_string()),
+            None => Err(GhostError::VaultLocked),
+        };
+        if result.is_ok() {
+            inner.last_activity = Instant::now();
         }
+        result
     }
 
     /// Get vault config
-    pub fn config(&self) -> Result<VaultConfig, String> {
+    pub fn config(&self) -> Result<VaultConfig, GhostError> {
         let inner = self.inner.lock().unwrap();
-        inner.config.clone().ok_or_else(|| "Vault not configured".to_string())
+        inner
+            .config
+            .clone()
+            .ok_or_else(|| GhostError::Other("Vault not configured".to_string()))
     }
 }
 
@@ -413,39 +1628,146 @@ pub struct SetupResult {
     pub recovery_key: String,
 }
 
-/// Initialize a new vault with password
-#[tauri::command]
-pub async fn setup_vault(
-    password: String,
-    state: tauri::State<'_, VaultState>,
-) -> Result<SetupResult, String> {
-    let config = state.config()?;
+/// Create a fresh vault: salt, verify blob and recovery data
+///
+/// Refuses to run over an existing vault, since replacing the salt would
+/// make every existing note undecryptable.
+fn create_vault(
+    config: &VaultConfig,
+    password: &str,
+    kdf: Option<KdfParams>,
+) -> Result<(Kek, RecoveryKey), GhostError> {
+    if is_vault_initialized(config) {
+        return Err(GhostError::Invalid(
+            "Vault is already initialized".to_string(),
+        ));
+    }
+    let kdf = kdf.unwrap_or(KdfParams::RECOMMENDED);
+    if kdf.below(&KdfParams::MINIMUM) {
+        return Err(GhostError::Invalid(
+            "KDF parameters are below the minimum".to_string(),
+        ));
+    }
 
     // Create vault directory
     fs::create_dir_all(&config.vault_dir)
-        .map_err(|e| format!("Failed to create vault directory: {}", e))?;
+        .map_err(|e| GhostError::Io(format!("Failed to create vault directory: {}", e)))?;
 
     // Generate salt
     let salt = generate_salt();
     fs::write(&config.salt_path, &salt)
-        .map_err(|e| format!("Failed to write salt: {}", e))?;
+        .map_err(|e| GhostError::Io(format!("Failed to write salt: {}", e)))?;
+
+    // Record the KDF parameters so they can be raised later
+    let kdf_json = serde_json::to_vec(&kdf)
+        .map_err(|e| GhostError::Other(format!("Failed to serialize KDF parameters: {}", e)))?;
+    fs::write(&config.kdf_path, kdf_json)
+        .map_err(|e| GhostError::Io(format!("Failed to write KDF parameters: {}", e)))?;
+
+    // Start out with the cipher chosen in settings
+    let cipher = crate::commands::settings::load_settings()
+        .map(|settings| settings.default_cipher)
+        .unwrap_or_default();
+    fs::write(&config.cipher_path, cipher.name())
+        .map_err(|e| GhostError::Io(format!("Failed to save cipher setting: {}", e)))?;
 
     // Derive KEK from password
-    let kek = Kek::derive(&password, &salt)?;
+    let kek = Kek::derive(password, &salt, &kdf)?;
 
     // Create verification blob (encrypt a known string)
     let verify_plaintext = b"ghostnote-verify";
     let verify_encrypted = encrypt(kek.as_bytes(), verify_plaintext)?;
     fs::write(&config.verify_path, &verify_encrypted)
-        .map_err(|e| format!("Failed to write verify blob: {}", e))?;
+        .map_err(|e| GhostError::Io(format!("Failed to write verify blob: {}", e)))?;
 
     // Generate and store recovery key
     let recovery_key = RecoveryKey::generate();
-    let recovery_data = RecoveryData::create(&kek, &recovery_key, &salt)?;
+    let recovery_data = RecoveryData::create(&kek, &recovery_key, &kdf)?;
     let recovery_json = serde_json::to_vec(&recovery_data)
-        .map_err(|e| format!("Failed to serialize recovery data: {}", e))?;
+        .map_err(|e| GhostError::Other(format!("Failed to serialize recovery data: {}", e)))?;
     fs::write(&config.recovery_path, &recovery_json)
-        .map_err(|e| format!("Failed to write recovery key: {}", e))?;
+        .map_err(|e| GhostError::Io(format!("Failed to write recovery key: {}", e)))?;
+    recovery_key_issued();
+
+    Ok((kek, recovery_key))
+}
+
+#[derive(serde::Serialize)]
+pub struct PasswordStrength {
+    /// 0 (guessed almost at once) to 4 (very hard to guess)
+    pub score: u8,
+    /// Rough time to crack offline against a slow hash, e.g. "3 centuries"
+    pub crack_time: String,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Estimate how hard a candidate master password is to guess
+///
+/// Advisory only: setup accepts any password, this just lets the UI warn
+/// about weak ones first. Nothing about the password is logged or stored.
+#[tauri::command]
+pub async fn check_password_strength(password: String) -> Result<PasswordStrength, GhostError> {
+    let estimate = zxcvbn::zxcvbn(&password, &["ghostnote"]);
+    let feedback = estimate.feedback();
+    Ok(PasswordStrength {
+        score: estimate.score().into(),
+        crack_time: estimate
+            .crack_times()
+            .offline_slow_hashing_1e4_per_second()
+            .to_string(),
+        warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+        suggestions: feedback
+            .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+    })
+}
+
+/// The vault's salt, checked to be the right length
+pub(crate) fn read_salt(config: &VaultConfig) -> Result<[u8; 32], GhostError> {
+    let salt_bytes = fs::read(&config.salt_path)
+        .map_err(|e| GhostError::Io(format!("Failed to read salt: {}", e)))?;
+    if salt_bytes.len() != 32 {
+        return Err(GhostError::Invalid("Invalid salt file".to_string()));
+    }
+    let mut salt = [0u8; 32];
+    salt.copy_from_slice(&salt_bytes);
+    Ok(salt)
+}
+
+/// Derive the KEK from `password`, failing with `WrongPassword` unless it
+/// opens the verify blob
+pub(crate) fn derive_verified_kek(config: &VaultConfig, password: &str) -> Result<Kek, GhostError> {
+    let salt = read_salt(config)?;
+    let kek = Kek::derive(password, &salt, &config.kdf_params()?)?;
+    verify_kek(config, &kek)?;
+    Ok(kek)
+}
+
+/// Fail with `WrongPassword` unless `kek` opens the verify blob
+fn verify_kek(config: &VaultConfig, kek: &Kek) -> Result<(), GhostError> {
+    let verify_encrypted = fs::read(&config.verify_path)
+        .map_err(|e| GhostError::Io(format!("Failed to read verify blob: {}", e)))?;
+    let verify_decrypted =
+        decrypt(kek.as_bytes(), &verify_encrypted).map_err(|_| GhostError::WrongPassword)?;
+    if verify_decrypted != b"ghostnote-verify" {
+        return Err(GhostError::WrongPassword);
+    }
+    Ok(())
+}
+
+/// Initialize a new vault with password
+///
+/// `kdf` is usually the result of `calibrate_argon2`; without it the vault
+/// uses the recommended parameters.
+#[tauri::command]
+pub async fn setup_vault(
+    password: String,
+    kdf: Option<KdfParams>,
+    state: tauri::State<'_, VaultState>,
+) -> Result<SetupResult, GhostError> {
+    let config = state.config()?;
+    let (kek, recovery_key) = create_vault(&config, &password, kdf)?;
 
     // Unlock vault
     state.unlock(kek);
@@ -455,219 +1777,1052 @@ pub async fn setup_vault(
     })
 }
 
-/// Check if vault is initialized
+/// Initialize a new vault, requiring the password to be typed twice
 #[tauri::command]
-pub async fn is_vault_setup(
+pub async fn initialize_vault(
+    password: String,
+    confirm: String,
+    kdf: Option<KdfParams>,
     state: tauri::State<'_, VaultState>,
-) -> Result<bool, String> {
+) -> Result<SetupResult, GhostError> {
+    if password != confirm {
+        return Err(GhostError::Invalid("Passwords do not match".to_string()));
+    }
+    if password.is_empty() {
+        return Err(GhostError::Invalid("Password cannot be empty".to_string()));
+    }
+
+    let config = state.config()?;
+    let (kek, recovery_key) = create_vault(&config, &password, kdf)?;
+
+    // Make sure what we wrote actually unlocks before handing out the key
+    verify_kek(&config, &kek).map_err(|e| match e {
+        GhostError::WrongPassword => {
+            GhostError::DecryptionFailed("Vault verification failed".to_string())
+        }
+        e => e,
+    })?;
+
+    state.unlock(kek);
+
+    Ok(SetupResult {
+        recovery_key: recovery_key.as_str().to_string(),
+    })
+}
+
+/// Check if vault is initialized
+#[tauri::command]
+pub async fn is_vault_setup(state: tauri::State<'_, VaultState>) -> Result<bool, GhostError> {
     let config = state.config()?;
     Ok(is_vault_initialized(&config))
 }
 
+/// Check if the current notes folder holds a vault, to pick between the
+/// create and unlock screens
+///
+/// Looks in the live `notes_dir`, so it's right even before the vault's own
+/// config has caught up with a change of folder.
+#[tauri::command]
+pub async fn vault_initialized(app_state: tauri::State<'_, AppState>) -> Result<bool, GhostError> {
+    let notes_dir = app_state.notes_dir.lock().unwrap().clone();
+    Ok(is_vault_initialized(&VaultConfig::new(&notes_dir)))
+}
+
 /// Unlock vault with password
+///
+/// A vault whose KDF parameters are below the recommended ones is re-keyed
+/// under the same password with stronger parameters in the background, so
+/// unlocking never waits for it. If that fails the vault keeps its old key.
 #[tauri::command]
 pub async fn unlock_vault(
     password: String,
+    app: tauri::AppHandle,
     state: tauri::State<'_, VaultState>,
-) -> Result<(), String> {
+) -> Result<(), GhostError> {
     let config = state.config()?;
+    let kek = derive_verified_kek(&config, &password)?;
 
-    // Read salt
-    let salt_bytes = fs::read(&config.salt_path)
-        .map_err(|e| format!("Failed to read salt: {}", e))?;
-    if salt_bytes.len() != 32 {
-        return Err("Invalid salt file".to_string());
+    // Unlock
+    state.unlock(kek);
+    if wants_kdf_upgrade(&config) {
+        spawn_kdf_upgrade(app.clone(), password);
     }
-    let mut salt = [0u8; 32];
-    salt.copy_from_slice(&salt_bytes);
+    spawn_index_build(app);
+    Ok(())
+}
 
-    // Derive KEK
-    let kek = Kek::derive(&password, &salt)?;
+/// Whether upgrading the KDF on unlock wasn't turned off and this vault's
+/// parameters are below the recommended ones
+///
+/// Vaults whose recovery data is too old to carry over are left alone: a
+/// re-key would replace their recovery key behind the user's back. The next
+/// password change upgrades them and shows the new key.
+fn wants_kdf_upgrade(config: &VaultConfig) -> bool {
+    let enabled = crate::commands::settings::load_settings()
+        .map(|settings| settings.auto_upgrade_kdf)
+        .unwrap_or(true);
+    enabled
+        && config.kdf_params().is_ok_and(|kdf| kdf.needs_upgrade())
+        && read_recovery_data(config).is_ok_and(|data| data.carries_over())
+}
 
-    // Verify password by decrypting verify blob
-    let verify_encrypted = fs::read(&config.verify_path)
-        .map_err(|e| format!("Failed to read verify blob: {}", e))?;
-    let verify_decrypted = decrypt(kek.as_bytes(), &verify_encrypted)
-        .map_err(|_| "Wrong password".to_string())?;
+/// Raise the KDF parameters to the recommended ones in the background
+///
+/// The vault is already unlocked, so notes stay usable while the new KEK is
+/// derived. The recovery key is carried over (see `wants_kdf_upgrade`); if a
+/// new one was needed after all, it's emitted with `kdf-upgraded`.
+fn spawn_kdf_upgrade(app: tauri::AppHandle, password: String) {
+    use tauri::{Emitter, Manager};
+
+    std::thread::spawn(move || {
+        let vault = app.state::<VaultState>();
+        match upgrade_kdf(&vault, &password, &KdfParams::RECOMMENDED) {
+            Ok(Some(recovery_key)) => {
+                let result = RekeyResult {
+                    recovery_key: Some(recovery_key.as_str().to_string()),
+                };
+                app.emit("kdf-upgraded", result).ok();
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Warning: Failed to upgrade KDF parameters: {}", e),
+        }
+    });
+}
 
-    if verify_decrypted != b"ghostnote-verify" {
-        return Err("Wrong password".to_string());
+/// Re-key the unlocked vault under `password` with every KDF cost raised
+/// to at least `target`'s
+///
+/// The slow derivation runs before anything is locked. The re-key commits
+/// through its journal, so being interrupted leaves the old or the new
+/// parameters, never a mix. Returns a new recovery key only if the old one
+/// couldn't be kept (see `stage_rekey`).
+fn upgrade_kdf(
+    vault: &VaultState,
+    password: &str,
+    target: &KdfParams,
+) -> Result<Option<RecoveryKey>, GhostError> {
+    let config = vault.config()?;
+    let kdf = config.kdf_params()?;
+    if !kdf.below(target) {
+        return Ok(None);
     }
+    let kdf = kdf.raised_to(target);
 
-    // Unlock
-    state.unlock(kek);
-    Ok(())
+    let new_salt = generate_salt();
+    let new_kek = Kek::derive(password, &new_salt, &kdf)?;
+    vault
+        .rekey_with(|old_kek| {
+            let recovery_key = commit_rekey(&config, old_kek, &new_kek, &new_salt, &kdf, None)?;
+            Ok((new_kek, recovery_key))
+        })
+}
+
+/// Check the master password without touching the unlocked state
+///
+/// Used to re-authenticate before destructive actions. The check is whether
+/// the verify blob decrypts under the derived KEK, so no key bytes are ever
+/// compared directly. Only IO problems are errors; a bad password is `false`.
+#[tauri::command]
+pub async fn verify_password(
+    password: String,
+    state: tauri::State<'_, VaultState>,
+) -> Result<bool, GhostError> {
+    let config = state.config()?;
+    match derive_verified_kek(&config, &password) {
+        Ok(_) => Ok(true),
+        Err(GhostError::WrongPassword) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Lock the vault and forget what was learned from its notes: the search
+/// index, tags, titles, links and checked-out plaintext files
+pub(crate) fn lock_and_clear(vault: &VaultState, state: &AppState) {
+    vault.lock();
+    clear_index(state);
+    clear_tag_cache(state);
+    clear_title_cache(state);
+    clear_link_graph(state);
+    wipe_checkouts(state);
 }
 
 /// Lock vault
 #[tauri::command]
-pub async fn lock_vault(state: tauri::State<'_, VaultState>) -> Result<(), String> {
-    state.lock();
+pub async fn lock_vault(
+    state: tauri::State<'_, VaultState>,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<(), GhostError> {
+    lock_and_clear(&state, &app_state);
     Ok(())
 }
 
 #[derive(serde::Serialize)]
 pub struct VaultStatus {
     pub initialized: bool,
-    pub locked: bool,
-    pub timeout_remaining: u64,
+    pub unlocked: bool,
+    /// Seconds of inactivity left before auto-lock, while unlocked
+    pub seconds_until_lock: Option<u64>,
+    /// Cipher newly written notes use
+    pub cipher: Cipher,
+    /// Argon2 parameters the KEK is derived with
+    pub argon2_params: KdfParams,
 }
 
-/// Get vault lock status
+/// Get the vault's setup, lock and encryption state in one call
 #[tauri::command]
 pub async fn get_vault_status(
     state: tauri::State<'_, VaultState>,
-) -> Result<VaultStatus, String> {
+) -> Result<VaultStatus, GhostError> {
     let config = state.config()?;
+    let unlocked = state.is_unlocked();
     Ok(VaultStatus {
         initialized: is_vault_initialized(&config),
-        locked: !state.is_unlocked(),
-        timeout_remaining: state.time_until_lock(),
+        unlocked,
+        seconds_until_lock: unlocked.then(|| state.time_until_lock()),
+        cipher: config.cipher(),
+        argon2_params: config.kdf_params()?,
     })
 }
 
+/// Get the content version (bumped on every note or folder mutation)
+#[tauri::command]
+pub async fn get_content_version(state: tauri::State<'_, VaultState>) -> Result<u64, GhostError> {
+    Ok(state.content_version())
+}
+
 /// Record activity (reset auto-lock timer)
 #[tauri::command]
-pub async fn vault_activity(state: tauri::State<'_, VaultState>) -> Result<(), String> {
+pub async fn vault_activity(state: tauri::State<'_, VaultState>) -> Result<(), GhostError> {
     state.touch();
     Ok(())
 }
 
-/// Set auto-lock timeout in seconds
+/// Set auto-lock timeout in seconds (persisted in settings as whole minutes)
 #[tauri::command]
-pub async fn set_lock_timeout(seconds: u64, state: tauri::State<'_, VaultState>) -> Result<(), String> {
+pub async fn set_lock_timeout(
+    seconds: u64,
+    state: tauri::State<'_, VaultState>,
+) -> Result<(), GhostError> {
+    let mut settings = crate::commands::settings::load_settings()?;
+    settings.auto_lock_minutes = seconds.div_ceil(60).max(1);
+    crate::commands::settings::store_settings(&settings)?;
+
     state.set_timeout(seconds);
     Ok(())
 }
 
+/// Get the cipher newly written notes use
+#[tauri::command]
+pub async fn get_vault_cipher(state: tauri::State<'_, VaultState>) -> Result<Cipher, GhostError> {
+    Ok(state.config()?.cipher())
+}
+
+#[derive(serde::Serialize)]
+pub struct KdfStatus {
+    pub current: KdfParams,
+    pub recommended: KdfParams,
+    /// The parameters are below the recommended ones, so the vault is
+    /// re-keyed with stronger ones after its next unlock
+    pub upgrade_available: bool,
+}
+
+/// Pick KDF parameters for this device
+///
+/// Returns the strongest parameters tried whose derivation takes at most
+/// `target_ms`, for passing to `setup_vault`.
+#[tauri::command]
+pub async fn calibrate_argon2(target_ms: u64) -> Result<KdfParams, GhostError> {
+    calibrate(Duration::from_millis(target_ms))
+}
+
+/// Get the vault's KDF parameters next to the recommended ones
+#[tauri::command]
+pub async fn get_kdf_status(state: tauri::State<'_, VaultState>) -> Result<KdfStatus, GhostError> {
+    let current = state.config()?.kdf_params()?;
+    Ok(KdfStatus {
+        current,
+        recommended: KdfParams::RECOMMENDED,
+        upgrade_available: current.needs_upgrade(),
+    })
+}
+
+/// Choose the cipher for newly written notes
+///
+/// Existing notes keep their cipher until they are next saved.
+#[tauri::command]
+pub async fn set_vault_cipher(
+    cipher: Cipher,
+    state: tauri::State<'_, VaultState>,
+) -> Result<(), GhostError> {
+    let config = state.config()?;
+    if !is_vault_initialized(&config) {
+        return Err(GhostError::NotFound("Vault not initialized".to_string()));
+    }
+
+    fs::write(&config.cipher_path, cipher.name())
+        .map_err(|e| GhostError::Io(format!("Failed to save cipher setting: {}", e)))
+}
+
+/// Files re-encrypted between saves of `reencrypt_vault`'s progress
+const REENCRYPT_SAVE_EVERY: usize = 25;
+
+/// Files `reencrypt_vault` has finished, so an interrupted run can resume
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ReencryptProgress {
+    target: Cipher,
+    done: BTreeSet<String>,
+}
+
+fn save_reencrypt_progress(
+    config: &VaultConfig,
+    progress: &ReencryptProgress,
+) -> Result<(), GhostError> {
+    let json = serde_json::to_vec(progress)?;
+    let staged = staged_path(&config.reencrypt_progress_path);
+    fs::write(&staged, json)
+        .and_then(|_| fs::rename(&staged, &config.reencrypt_progress_path))
+        .map_err(|e| GhostError::Io(format!("Failed to save re-encryption progress: {}", e)))
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ReencryptFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ReencryptReport {
+    /// Files re-encrypted by this run
+    pub reencrypted: usize,
+    /// Files an earlier, interrupted run had already done
+    pub already_done: usize,
+    /// Files that couldn't be decrypted, left as they were
+    pub failed: Vec<ReencryptFailure>,
+}
+
+/// Move every encrypted file in the vault to `target_cipher`
+///
+/// Notes, the trash, history and attachments are each decrypted and written
+/// again under a new DEK with the target cipher, which also becomes the
+/// vault's cipher for new notes. Progress is saved as it goes: running again
+/// with the same cipher after an interruption, or after fixing files that
+/// failed, skips the files already done.
+#[tauri::command]
+pub async fn reencrypt_vault(
+    target_cipher: Cipher,
+    password: String,
+    state: tauri::State<'_, VaultState>,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<ReencryptReport, GhostError> {
+    if !state.is_unlocked() {
+        return Err(GhostError::VaultLocked);
+    }
+    let config = state.config()?;
+    derive_verified_kek(&config, &password)?;
+
+    // Notes saved while this runs already use the target cipher
+    fs::write(&config.cipher_path, target_cipher.name())
+        .map_err(|e| GhostError::Io(format!("Failed to save cipher setting: {}", e)))?;
+
+    let mut progress = fs::read(&config.reencrypt_progress_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ReencryptProgress>(&data).ok())
+        .filter(|progress| progress.target == target_cipher)
+        .unwrap_or(ReencryptProgress {
+            target: target_cipher,
+            done: BTreeSet::new(),
+        });
+
+    let notes_dir = &config.notes_dir;
+    let enc_files: Vec<PathBuf> = walkdir::WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !e.path().starts_with(&config.vault_dir))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "enc"))
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut report = ReencryptReport::default();
+    for enc_file in enc_files {
+        let rel_path = rel_note_path(notes_dir, &enc_file);
+        if progress.done.contains(&rel_path) {
+            report.already_done += 1;
+            continue;
+        }
+
+        let base_path = enc_file.with_extension("");
+        let aad_path = bound_note_id(base_path.strip_prefix(notes_dir).unwrap_or(&base_path));
+        let result = decrypt_note_bytes(&base_path, &aad_path, &state).and_then(|(_, content)| {
+            record_own_write(&app_state, &rel_path);
+            encrypt_note_files(
+                &base_path,
+                &aad_path,
+                &content,
+                &state,
+                Some(Dek::generate()),
+            )
+        });
+        match result {
+            Ok(()) => {
+                progress.done.insert(rel_path);
+                report.reencrypted += 1;
+                if report.reencrypted % REENCRYPT_SAVE_EVERY == 0 {
+                    save_reencrypt_progress(&config, &progress)?;
+                }
+            }
+            Err(GhostError::VaultLocked) => {
+                save_reencrypt_progress(&config, &progress)?;
+                return Err(GhostError::VaultLocked);
+            }
+            Err(e) => report.failed.push(ReencryptFailure {
+                path: rel_path,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    // Keep the progress while anything is left to do
+    if report.failed.is_empty() {
+        if config.reencrypt_progress_path.exists() {
+            fs::remove_file(&config.reencrypt_progress_path).map_err(|e| {
+                GhostError::Io(format!("Failed to remove re-encryption progress: {}", e))
+            })?;
+        }
+    } else {
+        save_reencrypt_progress(&config, &progress)?;
+    }
+    Ok(report)
+}
+
+/// Read the vault's recovery data
+fn read_recovery_data(config: &VaultConfig) -> Result<RecoveryData, GhostError> {
+    let recovery_json = fs::read(&config.recovery_path)
+        .map_err(|e| GhostError::Io(format!("Failed to read recovery data: {}", e)))?;
+    serde_json::from_slice(&recovery_json)
+        .map_err(|e| GhostError::Other(format!("Invalid recovery data: {}", e)))
+}
+
+/// Result of re-keying the vault under a new password
+#[derive(Clone, serde::Serialize)]
+pub struct RekeyResult {
+    /// Set only when the vault's recovery data was too old to carry over,
+    /// so a new recovery key replaced the old one
+    pub recovery_key: Option<String>,
+}
+
 /// Recover vault with recovery key and set new password
+///
+/// The recovery key used keeps working afterwards.
 #[tauri::command]
 pub async fn recover_vault(
     recovery_key_input: String,
     new_password: String,
     state: tauri::State<'_, VaultState>,
-) -> Result<SetupResult, String> {
+) -> Result<RekeyResult, GhostError> {
     let config = state.config()?;
-
-    // Read salt
-    let salt_bytes = fs::read(&config.salt_path)
-        .map_err(|e| format!("Failed to read salt: {}", e))?;
-    if salt_bytes.len() != 32 {
-        return Err("Invalid salt file".to_string());
-    }
-    let mut salt = [0u8; 32];
-    salt.copy_from_slice(&salt_bytes);
+    let salt = read_salt(&config)?;
 
     // Parse recovery key
     let recovery_key = RecoveryKey::from_input(&recovery_key_input);
 
     // Read and decrypt recovery data
-    let recovery_json = fs::read(&config.recovery_path)
-        .map_err(|e| format!("Failed to read recovery data: {}", e))?;
-    let recovery_data: RecoveryData = serde_json::from_slice(&recovery_json)
-        .map_err(|e| format!("Invalid recovery data: {}", e))?;
+    let recovery_data = read_recovery_data(&config)?;
 
     // Recover the original KEK
     let original_kek = recovery_data
-        .recover_kek(&recovery_key, &salt)
-        .map_err(|_| "Invalid recovery key".to_string())?;
+        .recover_kek(&recovery_key, &salt, &config.kdf_params()?)
+        .map_err(|_| GhostError::Invalid("Invalid recovery key".to_string()))?;
+
+    // Re-key under the new password (must happen before we lose access to original KEK)
+    let kdf = config.kdf_params()?.upgraded();
+    let new_recovery_key = rekey_vault(
+        &state,
+        original_kek,
+        &new_password,
+        &kdf,
+        Some(&recovery_key),
+    )?;
+
+    Ok(RekeyResult {
+        recovery_key: new_recovery_key.map(|key| key.as_str().to_string()),
+    })
+}
 
-    // Generate new salt for new password
-    let new_salt = generate_salt();
-    fs::write(&config.salt_path, &new_salt)
-        .map_err(|e| format!("Failed to write salt: {}", e))?;
+/// Set a new password on a vault whose password was forgotten; another
+/// name for `recover_vault`
+#[tauri::command]
+pub async fn recover_with_key(
+    recovery_key: String,
+    new_password: String,
+    state: tauri::State<'_, VaultState>,
+) -> Result<RekeyResult, GhostError> {
+    recover_vault(recovery_key, new_password, state).await
+}
 
-    // Derive new KEK from new password
-    let new_kek = Kek::derive(&new_password, &new_salt)?;
+/// Change the master password (requires current password)
+///
+/// The recovery key keeps working, unless the vault's recovery data is too
+/// old to carry over; a new key is returned then.
+#[tauri::command]
+pub async fn change_master_password(
+    old_password: String,
+    new_password: String,
+    state: tauri::State<'_, VaultState>,
+) -> Result<RekeyResult, GhostError> {
+    let config = state.config()?;
 
-    // Re-wrap all existing DEKs with new KEK (must happen before we lose access to original KEK)
-    let rewrapped = rewrap_all_deks(&config.notes_dir, &original_kek, &new_kek)?;
-    if rewrapped > 0 {
-        eprintln!("Re-wrapped {} note DEKs with new KEK", rewrapped);
-    }
+    // Verify current password
+    let current_kek = derive_verified_kek(&config, &old_password)?;
 
-    // Re-encrypt verify blob with new KEK
-    let verify_plaintext = b"ghostnote-verify";
-    let verify_encrypted = encrypt(new_kek.as_bytes(), verify_plaintext)?;
-    fs::write(&config.verify_path, &verify_encrypted)
-        .map_err(|e| format!("Failed to write verify blob: {}", e))?;
+    // Re-wrap all existing DEKs, verify blob and recovery data under the new KEK
+    let kdf = config.kdf_params()?.upgraded();
+    let new_recovery_key = rekey_vault(&state, current_kek, &new_password, &kdf, None)?;
 
-    // Generate new recovery key
-    let new_recovery_key = RecoveryKey::generate();
-    let new_recovery_data = RecoveryData::create(&new_kek, &new_recovery_key, &new_salt)?;
-    let new_recovery_json = serde_json::to_vec(&new_recovery_data)
-        .map_err(|e| format!("Failed to serialize recovery data: {}", e))?;
-    fs::write(&config.recovery_path, &new_recovery_json)
-        .map_err(|e| format!("Failed to write recovery key: {}", e))?;
+    Ok(RekeyResult {
+        recovery_key: new_recovery_key.map(|key| key.as_str().to_string()),
+    })
+}
 
-    // Unlock with new KEK
-    state.unlock(new_kek);
+/// Replace the recovery key, e.g. after the old one was exposed or lost
+///
+/// Requires the password. The KEK is unchanged, so notes aren't touched;
+/// only the recovery data is rewritten, and the previous recovery key stops
+/// working. No re-key can run meanwhile, which would leave the new recovery
+/// data wrapping a KEK that's already been replaced.
+#[tauri::command]
+pub async fn regenerate_recovery_key(
+    password: String,
+    state: tauri::State<'_, VaultState>,
+) -> Result<SetupResult, GhostError> {
+    let recovery_key = state.without_rekey(|| -> Result<_, GhostError> {
+        let config = state.config()?;
+        let kek = derive_verified_kek(&config, &password)?;
+
+        let recovery_key = RecoveryKey::generate();
+        let recovery_data = RecoveryData::create(&kek, &recovery_key, &config.kdf_params()?)?;
+        let recovery_json = serde_json::to_vec(&recovery_data).map_err(|e| {
+            GhostError::Other(format!("Failed to serialize recovery data: {}", e))
+        })?;
+
+        // Write beside the old data and rename over it, so a crash never leaves
+        // the vault with no working recovery key
+        let staged = staged_path(&config.recovery_path);
+        fs::write(&staged, &recovery_json)
+            .map_err(|e| GhostError::Io(format!("Failed to write recovery key: {}", e)))?;
+        fs::rename(&staged, &config.recovery_path)
+            .map_err(|e| GhostError::Io(format!("Failed to write recovery key: {}", e)))?;
+        Ok(recovery_key)
+    })?;
+    recovery_key_issued();
 
     Ok(SetupResult {
-        recovery_key: new_recovery_key.as_str().to_string(),
+        recovery_key: recovery_key.as_str().to_string(),
     })
 }
 
-/// Change password (requires current password)
+/// Record whether the user has confirmed saving the current recovery key
+fn set_recovery_key_saved(saved: bool) -> Result<(), GhostError> {
+    let mut settings = crate::commands::settings::load_settings()?;
+    settings.recovery_key_saved = saved;
+    crate::commands::settings::store_settings(&settings)?;
+    Ok(())
+}
+
+/// Forget any earlier confirmation once a new recovery key replaces the old
+///
+/// Failing to persist this only risks the UI not asking about the new key,
+/// so it doesn't fail the operation that issued it.
+fn recovery_key_issued() {
+    if let Err(e) = set_recovery_key_saved(false) {
+        eprintln!("Warning: Failed to reset recovery key confirmation: {}", e);
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct RecoveryKeyStatus {
+    /// Recovery data is on disk
+    pub exists: bool,
+    /// The recovery data parses, so a recovery key could be checked against it
+    pub valid: bool,
+    /// The user confirmed saving the current recovery key somewhere
+    pub confirmed_saved: bool,
+}
+
+/// Whether the vault has usable recovery data and whether the user said
+/// they saved its key
+///
+/// The key itself is never stored, so it can't be shown again; an unsaved
+/// key can only be replaced with `regenerate_recovery_key`.
 #[tauri::command]
-pub async fn change_password(
-    current_password: String,
-    new_password: String,
+pub async fn recovery_key_status(
     state: tauri::State<'_, VaultState>,
-) -> Result<SetupResult, String> {
+) -> Result<RecoveryKeyStatus, GhostError> {
     let config = state.config()?;
+    let data = fs::read(&config.recovery_path).ok();
+    let valid = data
+        .as_deref()
+        .is_some_and(|data| serde_json::from_slice::<RecoveryData>(data).is_ok());
+
+    Ok(RecoveryKeyStatus {
+        exists: data.is_some(),
+        valid,
+        confirmed_saved: crate::commands::settings::load_settings()?.recovery_key_saved,
+    })
+}
 
-    // Read current salt
-    let salt_bytes = fs::read(&config.salt_path)
-        .map_err(|e| format!("Failed to read salt: {}", e))?;
-    if salt_bytes.len() != 32 {
-        return Err("Invalid salt file".to_string());
+/// Record that the user saved the current recovery key
+#[tauri::command]
+pub async fn confirm_recovery_key_saved() -> Result<(), GhostError> {
+    set_recovery_key_saved(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::notes::read_note_content;
+    use crate::commands::testing::{TestVault, PASSWORD};
+
+    #[tokio::test]
+    async fn initialize_rejects_a_mismatched_confirmation() {
+        let vault = TestVault::uninitialized();
+
+        let result = initialize_vault(
+            PASSWORD.to_string(),
+            format!("{} ", PASSWORD),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(GhostError::Invalid(_))));
+        assert!(!is_vault_initialized(&vault.vault().config().unwrap()));
+        assert!(!vault.vault().is_unlocked());
     }
-    let mut salt = [0u8; 32];
-    salt.copy_from_slice(&salt_bytes);
 
-    // Verify current password
-    let current_kek = Kek::derive(&current_password, &salt)?;
-    let verify_encrypted = fs::read(&config.verify_path)
-        .map_err(|e| format!("Failed to read verify blob: {}", e))?;
-    let verify_decrypted = decrypt(current_kek.as_bytes(), &verify_encrypted)
-        .map_err(|_| "Wrong password".to_string())?;
-    if verify_decrypted != b"ghostnote-verify" {
-        return Err("Wrong password".to_string());
+    #[tokio::test]
+    async fn initialize_leaves_a_usable_vault() {
+        let vault = TestVault::uninitialized();
+
+        let setup = initialize_vault(
+            PASSWORD.to_string(),
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert!(!setup.recovery_key.is_empty());
+        assert!(vault.vault().is_unlocked());
+
+        vault.write("first", "# First\n");
+        vault.vault().lock();
+
+        let config = vault.vault().config().unwrap();
+        let kek = derive_verified_kek(&config, PASSWORD).unwrap();
+        vault.vault().unlock(kek);
+        assert_eq!(
+            read_note_content(vault.path(), "first", &vault.vault()).unwrap(),
+            "# First\n"
+        );
+        assert!(matches!(
+            derive_verified_kek(&config, "not the password"),
+            Err(GhostError::WrongPassword)
+        ));
     }
 
-    // Generate new salt
-    let new_salt = generate_salt();
-    fs::write(&config.salt_path, &new_salt)
-        .map_err(|e| format!("Failed to write salt: {}", e))?;
+    #[tokio::test]
+    async fn initialize_refuses_an_existing_vault() {
+        let vault = TestVault::new().await;
+        vault.write("kept", "# Kept\n");
+        let config = vault.vault().config().unwrap();
+        let salt = read_salt(&config).unwrap();
+
+        let result = initialize_vault(
+            "another password".to_string(),
+            "another password".to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(GhostError::Invalid(_))));
+        assert_eq!(read_salt(&config).unwrap(), salt);
+        assert!(verify_password(PASSWORD.to_string(), vault.vault())
+            .await
+            .unwrap());
+        assert_eq!(
+            read_note_content(vault.path(), "kept", &vault.vault()).unwrap(),
+            "# Kept\n"
+        );
+    }
 
-    // Derive new KEK
-    let new_kek = Kek::derive(&new_password, &new_salt)?;
+    #[tokio::test]
+    async fn kdf_upgrade_after_unlock_keeps_notes_readable() {
+        let vault = TestVault::new().await;
+        vault.write("first", "# First\n");
+        vault.write("nested/second", "# Second\n");
+        let config = vault.vault().config().unwrap();
+        let target = KdfParams {
+            t_cost: KdfParams::MINIMUM.t_cost + 1,
+            ..KdfParams::MINIMUM
+        };
+        assert!(KdfParams::MINIMUM.below(&target));
+
+        let recovery_key = upgrade_kdf(&vault.vault(), PASSWORD, &target).unwrap();
+        assert!(recovery_key.is_none());
+        assert_eq!(config.kdf_params().unwrap(), target);
+        let read = |id: &str| read_note_content(vault.path(), id, &vault.vault()).unwrap();
+        assert_eq!(read("first"), "# First\n");
+        assert_eq!(read("nested/second"), "# Second\n");
+
+        // The new parameters are what the password unlocks with from now on
+        vault.vault().lock();
+        let kek = derive_verified_kek(&config, PASSWORD).unwrap();
+        vault.vault().unlock(kek);
+        assert_eq!(read("nested/second"), "# Second\n");
+        assert!(upgrade_kdf(&vault.vault(), PASSWORD, &target)
+            .unwrap()
+            .is_none());
+    }
 
-    // Re-wrap all existing DEKs with new KEK
-    let rewrapped = rewrap_all_deks(&config.notes_dir, &current_kek, &new_kek)?;
-    if rewrapped > 0 {
-        eprintln!("Re-wrapped {} note DEKs with new KEK", rewrapped);
+    #[tokio::test]
+    async fn uncommitted_rekey_discards_only_its_own_staged_files() {
+        let vault = TestVault::new().await;
+        vault.write("note", "# Note\n");
+        let config = vault.vault().config().unwrap();
+
+        let staged_key = staged_path(&vault.path().join("note.key"));
+        let staged_salt = staged_path(&config.salt_path);
+        fs::write(&staged_key, b"half-written key").unwrap();
+        fs::write(&staged_salt, b"half-written salt").unwrap();
+        // The user's own files that merely end in .new
+        let user_file = vault.path().join("ideas.new");
+        let orphan = vault.path().join("gone.key.new");
+        fs::write(&user_file, b"keep me").unwrap();
+        fs::write(&orphan, b"keep me too").unwrap();
+
+        recover_interrupted_rekey(&config);
+
+        assert!(!staged_key.exists());
+        assert!(!staged_salt.exists());
+        assert!(user_file.exists());
+        assert!(orphan.exists());
+        assert_eq!(
+            read_note_content(vault.path(), "note", &vault.vault()).unwrap(),
+            "# Note\n"
+        );
     }
 
-    // Re-encrypt verify blob
-    let verify_plaintext = b"ghostnote-verify";
-    let verify_encrypted = encrypt(new_kek.as_bytes(), verify_plaintext)?;
-    fs::write(&config.verify_path, &verify_encrypted)
-        .map_err(|e| format!("Failed to write verify blob: {}", e))?;
+    #[tokio::test]
+    async fn forgotten_password_is_recovered_with_the_recovery_key() {
+        let vault = TestVault::uninitialized();
+        let setup = initialize_vault(
+            PASSWORD.to_string(),
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        vault.write("diary", "# Diary\n");
+        vault.vault().lock();
+
+        // The password is forgotten
+        let config = vault.vault().config().unwrap();
+        assert!(matches!(
+            derive_verified_kek(&config, "my best guess"),
+            Err(GhostError::WrongPassword)
+        ));
+
+        let wrong = recover_with_key(
+            RecoveryKey::generate().as_str().to_string(),
+            "new password".to_string(),
+            vault.vault(),
+        )
+        .await;
+        assert!(matches!(wrong, Err(GhostError::Invalid(_))));
+        assert!(!vault.vault().is_unlocked());
+
+        let recovered = recover_with_key(
+            setup.recovery_key.clone(),
+            "new password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert!(recovered.recovery_key.is_none());
+        assert!(vault.vault().is_unlocked());
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+
+        vault.vault().lock();
+        assert!(matches!(
+            derive_verified_kek(&config, PASSWORD),
+            Err(GhostError::WrongPassword)
+        ));
+        let kek = derive_verified_kek(&config, "new password").unwrap();
+        vault.vault().unlock(kek);
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+
+        // The recovery key is still good for the next time
+        vault.vault().lock();
+        recover_with_key(
+            setup.recovery_key,
+            "third password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+    }
 
-    // Generate new recovery key
-    let new_recovery_key = RecoveryKey::generate();
-    let new_recovery_data = RecoveryData::create(&new_kek, &new_recovery_key, &new_salt)?;
-    let new_recovery_json = serde_json::to_vec(&new_recovery_data)
-        .map_err(|e| format!("Failed to serialize recovery data: {}", e))?;
-    fs::write(&config.recovery_path, &new_recovery_json)
-        .map_err(|e| format!("Failed to write recovery key: {}", e))?;
+    #[tokio::test]
+    async fn reencrypting_replaces_every_key_and_survives_a_crash() {
+        let vault = TestVault::new().await;
+        vault.write("first", "# First\n");
+        vault.write("second", "# Second\n");
+        let key_file = vault.path().join("first.key");
+        let old_key = fs::read(&key_file).unwrap();
+
+        let report = reencrypt_vault(
+            Cipher::XChaCha20Poly1305,
+            PASSWORD.to_string(),
+            vault.vault(),
+            vault.state(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.reencrypted, 2);
+        assert!(report.failed.is_empty());
+        let new_key = fs::read(&key_file).unwrap();
+
+        // The old wrapped DEK doesn't open the new ciphertext
+        fs::write(&key_file, &old_key).unwrap();
+        assert!(read_note_content(vault.path(), "first", &vault.vault()).is_err());
+
+        // As if the app died after renaming the content file but not the key
+        fs::write(vault.path().join("first.key.tmp"), &new_key).unwrap();
+        vault.vault().set_config(vault.vault().config().unwrap());
+        for (note, content) in [("first", "# First\n"), ("second", "# Second\n")] {
+            assert_eq!(
+                read_note_content(vault.path(), note, &vault.vault()).unwrap(),
+                content
+            );
+        }
+    }
 
-    // Unlock with new KEK
-    state.unlock(new_kek);
+    #[tokio::test]
+    async fn regenerated_recovery_key_replaces_the_old_one() {
+        let vault = TestVault::uninitialized();
+        let setup = initialize_vault(
+            PASSWORD.to_string(),
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        vault.write("diary", "# Diary\n");
+
+        let wrong = regenerate_recovery_key("not the password".to_string(), vault.vault()).await;
+        assert!(matches!(wrong, Err(GhostError::WrongPassword)));
+
+        let regenerated = regenerate_recovery_key(PASSWORD.to_string(), vault.vault())
+            .await
+            .unwrap();
+        assert_ne!(regenerated.recovery_key, setup.recovery_key);
+
+        vault.vault().lock();
+        let old = recover_with_key(
+            setup.recovery_key,
+            "new password".to_string(),
+            vault.vault(),
+        )
+        .await;
+        assert!(matches!(old, Err(GhostError::Invalid(_))));
+        assert!(!vault.vault().is_unlocked());
+
+        recover_with_key(
+            regenerated.recovery_key,
+            "new password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+    }
 
-    Ok(SetupResult {
-        recovery_key: new_recovery_key.as_str().to_string(),
-    })
+    #[tokio::test]
+    async fn password_changes_keep_the_recovery_key() {
+        let vault = TestVault::uninitialized();
+        let setup = initialize_vault(
+            PASSWORD.to_string(),
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        vault.write("diary", "# Diary\n");
+
+        let changed = change_master_password(
+            PASSWORD.to_string(),
+            "second password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert!(changed.recovery_key.is_none());
+
+        vault.vault().lock();
+        recover_with_key(
+            setup.recovery_key,
+            "third password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn old_recovery_data_is_replaced_with_a_new_key_on_rekey() {
+        let vault = TestVault::uninitialized();
+        initialize_vault(
+            PASSWORD.to_string(),
+            PASSWORD.to_string(),
+            Some(KdfParams::MINIMUM),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        vault.write("diary", "# Diary\n");
+
+        // Recovery data as written before it had a salt of its own
+        let config = vault.vault().config().unwrap();
+        let old_key = RecoveryKey::generate();
+        let recovery_kek = Kek::derive(
+            &old_key.as_str().replace('-', ""),
+            &read_salt(&config).unwrap(),
+            &config.kdf_params().unwrap(),
+        )
+        .unwrap();
+        let kek = derive_verified_kek(&config, PASSWORD).unwrap();
+        let legacy = serde_json::json!({
+            "kek_bytes": encrypt(recovery_kek.as_bytes(), kek.as_bytes()).unwrap(),
+        });
+        fs::write(&config.recovery_path, legacy.to_string()).unwrap();
+        assert!(!wants_kdf_upgrade(&config));
+
+        let changed = change_master_password(
+            PASSWORD.to_string(),
+            "second password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .unwrap();
+        let new_key = changed.recovery_key.unwrap();
+
+        vault.vault().lock();
+        let stale = recover_with_key(
+            old_key.as_str().to_string(),
+            "third password".to_string(),
+            vault.vault(),
+        )
+        .await;
+        assert!(matches!(stale, Err(GhostError::Invalid(_))));
+        recover_with_key(new_key, "third password".to_string(), vault.vault())
+            .await
+            .unwrap();
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn vaults_below_the_recommended_kdf_are_upgraded_after_unlock() {
+        let vault = TestVault::new().await;
+        vault.write("diary", "# Diary\n");
+        let config = vault.vault().config().unwrap();
+        assert!(config.kdf_params().unwrap().needs_upgrade());
+        assert!(wants_kdf_upgrade(&config));
+
+        // What the background upgrade started by `unlock_vault` does
+        upgrade_kdf(&vault.vault(), PASSWORD, &KdfParams::RECOMMENDED).unwrap();
+        assert!(!config.kdf_params().unwrap().needs_upgrade());
+        assert!(!wants_kdf_upgrade(&config));
+        assert_eq!(
+            read_note_content(vault.path(), "diary", &vault.vault()).unwrap(),
+            "# Diary\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn password_change_with_an_unreadable_key_changes_nothing() {
+        let vault = TestVault::new().await;
+        vault.write("diary", "# Diary\n");
+        vault.write("plans", "# Plans\n");
+        let config = vault.vault().config().unwrap();
+        let key_path = fs::read_dir(vault.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "key"))
+            .unwrap();
+        fs::write(&key_path, b"not a wrapped key").unwrap();
+
+        let err = change_master_password(
+            PASSWORD.to_string(),
+            "new password".to_string(),
+            vault.vault(),
+        )
+        .await
+        .err()
+        .unwrap();
+        let name = key_path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(err.to_string().contains(&name), "{}", err);
+
+        // The old password still opens the notes that were fine
+        assert!(matches!(
+            derive_verified_kek(&config, "new password"),
+            Err(GhostError::WrongPassword)
+        ));
+        derive_verified_kek(&config, PASSWORD).unwrap();
+        let readable = ["diary", "plans"]
+            .iter()
+            .filter(|id| read_note_content(vault.path(), id, &vault.vault()).is_ok())
+            .count();
+        assert_eq!(readable, 1);
+        assert!(!rekey_journal_path(&config).exists());
+        assert!(!staged_path(&config.verify_path).exists());
+    }
+
+    #[tokio::test]
+    async fn locking_shreds_checked_out_notes() {
+        use crate::commands::notes::checkout_note;
+
+        let vault = TestVault::new().await;
+        vault.write("diary", "# Diary\n");
+        let checkout = checkout_note("diary".to_string(), vault.state(), vault.vault()).unwrap();
+
+        lock_and_clear(&vault.vault(), &vault.state());
+        assert!(!vault.vault().is_unlocked());
+        assert!(!Path::new(&checkout).exists());
+        assert!(vault.state().checkouts.lock().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,143 @@
+use crate::commands::vault::VaultState;
+use crate::AppState;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How long the notes folder must be quiet before changes are emitted
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How long after one of our own saves its file events are ignored
+const OWN_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Event sent to the frontend for a change on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    NoteChanged,
+    NoteDeleted,
+    FolderChanged,
+}
+
+impl Change {
+    fn event_name(self) -> &'static str {
+        match self {
+            Change::NoteChanged => "note-changed",
+            Change::NoteDeleted => "note-deleted",
+            Change::FolderChanged => "folder-changed",
+        }
+    }
+}
+
+/// Remember that we're about to write a note, so the watcher doesn't echo it back
+pub(crate) fn record_own_write(state: &AppState, rel_path: &str) {
+    let mut writes = state.recent_writes.lock().unwrap();
+    writes.retain(|_, at| at.elapsed() < OWN_WRITE_WINDOW);
+    writes.insert(rel_path.to_string(), Instant::now());
+}
+
+fn is_own_write(state: &AppState, rel_path: &str) -> bool {
+    state
+        .recent_writes
+        .lock()
+        .unwrap()
+        .get(rel_path)
+        .is_some_and(|at| at.elapsed() < OWN_WRITE_WINDOW)
+}
+
+/// Work out what a filesystem event on `path` means for the UI
+///
+/// Notes are reported by their id (relative path without `.enc`), folders by
+/// their relative path. Hidden entries (the vault metadata, the trash) are
+/// never reported.
+fn classify(notes_dir: &Path, path: &Path, kind: &EventKind) -> Option<(String, Change)> {
+    let rel = path.strip_prefix(notes_dir).ok()?;
+    let hidden = rel.components().any(|c| match c {
+        Component::Normal(part) => part.to_string_lossy().starts_with('.'),
+        _ => false,
+    });
+    if hidden || rel.as_os_str().is_empty() {
+        return None;
+    }
+
+    match rel.extension().and_then(|e| e.to_str()) {
+        Some("enc") | Some("key") => {
+            let id = rel.with_extension("").to_string_lossy().replace('\\', "/");
+            let deleted = matches!(kind, EventKind::Remove(_))
+                || !notes_dir.join(rel.with_extension("enc")).exists();
+            let change = if deleted {
+                Change::NoteDeleted
+            } else {
+                Change::NoteChanged
+            };
+            Some((id, change))
+        }
+        // Folder names have no extension; only structural changes matter
+        None if matches!(
+            kind,
+            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+        ) =>
+        {
+            let rel_path = rel.to_string_lossy().replace('\\', "/");
+            Some((rel_path, Change::FolderChanged))
+        }
+        _ => None,
+    }
+}
+
+/// Watch `notes_dir` and emit `note-changed`, `note-deleted` and
+/// `folder-changed` events with the relative path
+///
+/// Replaces any previous watcher, so it's also used when the notes folder
+/// moves. Events are coalesced until the folder has been quiet for a moment,
+/// and notes we saved ourselves within the last couple of seconds are skipped.
+pub(crate) fn watch_notes_dir(app: &AppHandle, notes_dir: &Path) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    // Dropping the old watcher closes its channel, which ends its thread
+    *state.watcher.lock().unwrap() = None;
+
+    let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+    watcher
+        .watch(notes_dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {}: {}", notes_dir.display(), e))?;
+    *state.watcher.lock().unwrap() = Some(watcher);
+
+    let app = app.clone();
+    let notes_dir = notes_dir.to_path_buf();
+    std::thread::spawn(move || {
+        let mut pending: BTreeMap<String, Change> = BTreeMap::new();
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(Ok(event)) => {
+                    for path in &event.paths {
+                        if let Some((rel, change)) = classify(&notes_dir, path, &event.kind) {
+                            // Don't wait out the debounce to stop serving the old content
+                            if change != Change::FolderChanged {
+                                app.state::<VaultState>().forget_note(&rel);
+                            }
+                            pending.insert(rel, change);
+                        }
+                    }
+                }
+                Ok(Err(_)) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    let state = app.state::<AppState>();
+                    for (rel, change) in std::mem::take(&mut pending) {
+                        if change == Change::NoteChanged && is_own_write(&state, &rel) {
+                            continue;
+                        }
+                        app.emit(change.event_name(), rel).ok();
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Errors returned from commands to the frontend
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Error returned by note and vault commands
+///
+/// Serialized as `{ kind, message }`: the frontend branches on `kind` and
+/// shows `message`, which keeps the wording the old string errors had. A
+/// conflict adds both versions as `conflict`.
+#[derive(Debug)]
+pub enum GhostError {
+    /// The vault has no KEK in memory
+    VaultLocked,
+    /// The password didn't match the vault
+    WrongPassword,
+    /// A note, folder or vault file doesn't exist
+    NotFound(String),
+    /// Ciphertext failed authentication or couldn't be decrypted
+    DecryptionFailed(String),
+    /// The note is marked read-only (holds the note id)
+    NoteReadOnly(String),
+    /// Input the command can't act on
+    Invalid(String),
+    /// Filesystem failure
+    Io(String),
+    /// The note changed on disk since the content being saved was read
+    Conflict(Box<NoteConflict>),
+    /// Anything else
+    Other(String),
+}
+
+/// Both sides of a save refused so it wouldn't overwrite another change
+#[derive(Debug, Serialize)]
+pub struct NoteConflict {
+    pub path: String,
+    /// What the save would have written
+    pub ours: String,
+    /// What's on disk now
+    pub theirs: String,
+    /// Hash of `theirs`, to save over it deliberately
+    pub theirs_hash: String,
+}
+
+impl GhostError {
+    /// Stable name the frontend matches on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GhostError::VaultLocked => "VaultLocked",
+            GhostError::WrongPassword => "WrongPassword",
+            GhostError::NotFound(_) => "NotFound",
+            GhostError::DecryptionFailed(_) => "DecryptionFailed",
+            GhostError::NoteReadOnly(_) => "NoteReadOnly",
+            GhostError::Invalid(_) => "Invalid",
+            GhostError::Io(_) => "Io",
+            GhostError::Conflict(_) => "Conflict",
+            GhostError::Other(_) => "Other",
+        }
+    }
+}
+
+impl fmt::Display for GhostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GhostError::VaultLocked => write!(f, "Vault is locked"),
+            GhostError::WrongPassword => write!(f, "Wrong password"),
+            GhostError::NoteReadOnly(path) => write!(f, "'{}' is read-only", path),
+            GhostError::Conflict(conflict) => {
+                write!(
+                    f,
+                    "'{}' was changed elsewhere since it was opened",
+                    conflict.path
+                )
+            }
+            GhostError::NotFound(message)
+            | GhostError::DecryptionFailed(message)
+            | GhostError::Invalid(message)
+            | GhostError::Io(message)
+            | GhostError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for GhostError {}
+
+impl Serialize for GhostError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let conflict = match self {
+            GhostError::Conflict(conflict) => Some(conflict),
+            _ => None,
+        };
+        let mut s = serializer.serialize_struct("GhostError", 2 + conflict.iter().len())?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        if let Some(conflict) = conflict {
+            s.serialize_field("conflict", conflict)?;
+        }
+        s.end()
+    }
+}
+
+impl From<std::io::Error> for GhostError {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            GhostError::NotFound(e.to_string())
+        } else {
+            GhostError::Io(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for GhostError {
+    fn from(e: serde_json::Error) -> Self {
+        GhostError::Other(e.to_string())
+    }
+}
+
+impl From<String> for GhostError {
+    fn from(message: String) -> Self {
+        GhostError::Other(message)
+    }
+}
+
+/// Lets commands that still return `String` errors use `?` on these
+impl From<GhostError> for String {
+    fn from(e: GhostError) -> Self {
+        e.to_string()
+    }
+}
@@ -1,11 +1,15 @@
 mod commands;
+mod error;
 
 use commands::audio::RecordingState;
+use commands::notes::{LinkGraph, TagCache};
+use commands::search::{SearchIndex, TitleCache};
 use commands::vault::{VaultConfig, VaultState};
 use ringbuf::HeapCons;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tauri::{Emitter, Manager};
 
 /// Ignore SIGPIPE on Unix systems.
@@ -25,8 +29,28 @@ fn ignore_sigpipe() {
 
 pub struct AppState {
     pub notes_dir: Mutex<PathBuf>,
+    pub current_folder: Mutex<String>,
+    /// Hash of each note's content as of our last read or save
+    pub content_hashes: Mutex<HashMap<String, blake3::Hash>>,
+    /// In-memory full-text index, present only while the vault is unlocked
+    pub search_index: Mutex<Option<SearchIndex>>,
+    /// Notes per frontmatter tag, present only while the vault is unlocked
+    pub tag_cache: Mutex<Option<TagCache>>,
+    /// Every note's title, present only while the vault is unlocked
+    pub title_cache: Mutex<Option<TitleCache>>,
+    /// Wiki links between notes, present only while the vault is unlocked
+    pub link_graph: Mutex<Option<LinkGraph>>,
+    /// Notes we saved recently, so the file watcher doesn't report them back
+    pub recent_writes: Mutex<HashMap<String, Instant>>,
+    /// Plaintext copies of notes out for editing elsewhere, by temp file
+    /// path, each with the note id it checks back into
+    pub checkouts: Mutex<HashMap<PathBuf, String>>,
+    /// Watcher on the notes folder, replaced when the folder changes
+    pub watcher: Mutex<Option<notify::RecommendedWatcher>>,
     pub selected_audio_device: Mutex<Option<String>>,
     pub selected_model: Mutex<String>,
+    /// Earlier versions kept per note
+    pub max_versions: Mutex<usize>,
     pub recording: Arc<Mutex<RecordingState>>,
     pub sample_consumer: Arc<Mutex<Option<Arc<Mutex<HeapCons<f32>>>>>>,
 }
@@ -39,8 +63,18 @@ impl Default for AppState {
 
         Self {
             notes_dir: Mutex::new(default_dir),
+            current_folder: Mutex::new(String::new()),
+            content_hashes: Mutex::new(HashMap::new()),
+            search_index: Mutex::new(None),
+            tag_cache: Mutex::new(None),
+            title_cache: Mutex::new(None),
+            link_graph: Mutex::new(None),
+            recent_writes: Mutex::new(HashMap::new()),
+            checkouts: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
             selected_audio_device: Mutex::new(None),
             selected_model: Mutex::new("small.en".to_string()),
+            max_versions: Mutex::new(commands::settings::default_max_versions()),
             recording: Arc::new(Mutex::new(RecordingState::default())),
             sample_consumer: Arc::new(Mutex::new(None)),
         }
@@ -57,19 +91,36 @@ pub fn run() {
         .manage(AppState::default())
         .manage(VaultState::new())
         .setup(|app| {
-            // Ensure notes directory exists
+            let settings = commands::settings::load_settings().unwrap_or_default();
+
+            // Use the notes folder chosen in settings, if any
             let state = app.state::<AppState>();
+            if !settings.notes_dir.is_empty() {
+                *state.notes_dir.lock().unwrap() = PathBuf::from(&settings.notes_dir);
+            }
+
+            // Ensure notes directory exists
             let notes_dir = state.notes_dir.lock().unwrap().clone();
             if !notes_dir.exists() {
                 std::fs::create_dir_all(&notes_dir).ok();
                 // Create default inbox folder
-                std::fs::create_dir_all(notes_dir.join("inbox")).ok();
+                std::fs::create_dir_all(notes_dir.join(commands::notes::INBOX_DIR)).ok();
             }
 
             // Initialize vault config
             let vault_state = app.state::<VaultState>();
             vault_state.set_config(VaultConfig::new(&notes_dir));
 
+            // Tell the frontend about changes made outside the app (sync tools, other instances)
+            if let Err(e) = commands::watcher::watch_notes_dir(app.handle(), &notes_dir) {
+                eprintln!("Warning: {}", e);
+            }
+
+            // Apply the saved auto-lock timeout
+            vault_state.set_timeout(settings.auto_lock_minutes * 60);
+            vault_state.set_note_cache_bytes(settings.note_cache_bytes);
+            *state.max_versions.lock().unwrap() = settings.max_versions;
+
             // Start auto-lock timer
             let app_handle = app.handle().clone();
             std::thread::spawn(move || {
@@ -79,7 +130,8 @@ pub fn run() {
                     // Get vault state from app handle
                     if let Some(vault) = app_handle.try_state::<VaultState>() {
                         if vault.should_lock() {
-                            vault.lock();
+                            let state = app_handle.state::<AppState>();
+                            commands::vault::lock_and_clear(&vault, &state);
                             // Emit event to frontend
                             app_handle.emit("vault-locked", ()).ok();
                         }
@@ -92,18 +144,93 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Notes
             commands::notes::list_folders,
+            commands::notes::list_folders_flat,
             commands::notes::list_notes,
+            commands::notes::folder_stats,
+            commands::notes::list_notes_paged,
             commands::notes::read_note,
+            commands::notes::read_note_range,
+            commands::notes::list_recent_notes,
+            commands::notes::get_note_meta,
+            commands::notes::open_note,
+            commands::notes::export_note_plaintext,
+            commands::notes::export_note,
+            commands::notes::export_note_with_context,
+            commands::notes::checkout_note,
+            commands::notes::checkin_note,
             commands::notes::save_note,
             commands::notes::create_note,
+            commands::notes::open_daily_note,
+            commands::notes::quick_capture,
+            commands::notes::list_templates,
+            commands::notes::create_note_from_template,
+            commands::notes::duplicate_note,
             commands::notes::delete_note,
+            commands::notes::batch_delete_notes,
+            commands::notes::list_trash,
+            commands::notes::restore_note,
+            commands::notes::empty_trash,
+            commands::notes::vacuum_vault,
+            commands::notes::list_versions,
+            commands::notes::restore_version,
+            commands::notes::rekey_note,
+            commands::notes::verify_vault_integrity,
+            commands::notes::list_tags,
+            commands::notes::list_notes_by_tag,
+            commands::notes::set_pinned,
+            commands::notes::get_backlinks,
+            commands::notes::get_outgoing_links,
+            commands::notes::get_link_graph,
+            commands::notes::find_related_notes,
+            commands::attachments::save_attachment,
+            commands::attachments::read_attachment,
+            commands::notes::migrate_note_to_encrypted,
+            commands::notes::migrate_all_to_encrypted,
+            commands::notes::move_note,
+            commands::notes::rename_note,
+            commands::notes::set_note_readonly,
+            commands::notes::set_filename_locked,
+            commands::notes::set_note_compression,
+            commands::notes::resync_filenames,
             commands::notes::create_folder,
             commands::notes::delete_folder,
             commands::notes::rename_folder,
+            commands::notes::move_folder,
+            commands::notes::set_current_folder,
+            commands::notes::get_current_folder,
+            commands::notes::note_exists,
+            commands::notes::folder_exists,
             commands::notes::search_notes,
+            commands::search::search_notes_indexed,
+            commands::search::search_titles,
+            commands::notes::find_timestamp_anomalies,
+            commands::notes::fix_timestamp,
+            commands::notes::notes_changed_externally,
+            commands::notes::get_activity_heatmap,
+            commands::notes::get_note_reference,
+            commands::notes::resolve_reference,
+            commands::notes::audit_derived_state,
+            commands::notes::prune_derived_state,
+            // Export
+            commands::export::export_to_sqlite,
+            commands::export::import_from_sqlite,
+            commands::export::export_vault_backup,
+            commands::export::export_plaintext,
+            commands::export::export_tag_index,
+            commands::export::import_vault_backup,
+            commands::export::diff_snapshots,
+            commands::export::apply_snapshot_patch,
+            // Import
+            commands::import::import_bookmarks_html,
+            commands::import::import_markdown_folder,
+            commands::import::detect_encoding_issues,
+            commands::import::fix_encoding,
             // Settings
             commands::settings::get_settings,
             commands::settings::save_settings,
+            commands::settings::export_settings,
+            commands::settings::import_settings,
+            commands::settings::set_notes_dir,
             // Audio
             commands::audio::list_audio_devices,
             commands::audio::get_selected_device,
@@ -122,15 +249,35 @@ pub fn run() {
             commands::whisper::delete_model,
             // Vault
             commands::vault::setup_vault,
+            commands::vault::check_password_strength,
+            commands::vault::initialize_vault,
             commands::vault::is_vault_setup,
+            commands::vault::vault_initialized,
             commands::vault::unlock_vault,
+            commands::vault::verify_password,
             commands::vault::lock_vault,
             commands::vault::get_vault_status,
             commands::vault::vault_activity,
+            commands::vault::get_content_version,
             commands::vault::set_lock_timeout,
+            commands::vault::get_vault_cipher,
+            commands::vault::get_kdf_status,
+            commands::vault::calibrate_argon2,
+            commands::vault::set_vault_cipher,
+            commands::vault::reencrypt_vault,
             commands::vault::recover_vault,
-            commands::vault::change_password,
+            commands::vault::recover_with_key,
+            commands::vault::change_master_password,
+            commands::vault::regenerate_recovery_key,
+            commands::vault::recovery_key_status,
+            commands::vault::confirm_recovery_key_saved,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Leave no plaintext checkouts behind
+            if let tauri::RunEvent::Exit = event {
+                commands::notes::wipe_checkouts(&app.state::<AppState>());
+            }
+        });
 }
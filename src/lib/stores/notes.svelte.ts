@@ -1,3 +1,4 @@
+import { listen } from "@tauri-apps/api/event";
 import {
   listFolders,
   listNotes,
@@ -156,6 +157,24 @@ async function removeFolder(path: string) {
 loadFolders();
 loadNotes(selectedFolder);
 
+// Reload when notes change on disk outside the app (sync tools, other instances)
+function isInSelectedFolder(path: string) {
+  return path.slice(0, path.lastIndexOf("/") + 1) === `${selectedFolder}/`;
+}
+
+listen<string>("note-changed", (event) => {
+  if (isInSelectedFolder(event.payload)) loadNotes(selectedFolder);
+});
+
+listen<string>("note-deleted", (event) => {
+  if (selectedNoteId === event.payload) selectedNoteId = null;
+  if (isInSelectedFolder(event.payload)) loadNotes(selectedFolder);
+});
+
+listen<string>("folder-changed", () => {
+  loadFolders();
+});
+
 // Export reactive getters and actions
 export const notesStore = {
   get folders() {
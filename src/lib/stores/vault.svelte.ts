@@ -1,16 +1,52 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
+import { errorMessage } from '../utils/tauri-commands';
+
+interface KdfParams {
+  m_cost: number;
+  t_cost: number;
+  p_cost: number;
+}
 
 interface VaultStatus {
   initialized: boolean;
-  locked: boolean;
-  timeout_remaining: number;
+  unlocked: boolean;
+  seconds_until_lock: number | null;
+  cipher: string;
+  argon2_params: KdfParams | null;
+}
+
+interface KdfStatus {
+  current: KdfParams;
+  recommended: KdfParams;
+  upgrade_available: boolean;
+}
+
+export interface ReencryptReport {
+  reencrypted: number;
+  already_done: number;
+  failed: { path: string; error: string }[];
+}
+
+export interface RecoveryKeyStatus {
+  exists: boolean;
+  valid: boolean;
+  confirmed_saved: boolean;
+}
+
+export interface PasswordStrength {
+  score: number;
+  crack_time: string;
+  warning: string | null;
+  suggestions: string[];
 }
 
 let status = $state<VaultStatus>({
   initialized: false,
-  locked: true,
-  timeout_remaining: 0,
+  unlocked: false,
+  seconds_until_lock: null,
+  cipher: '',
+  argon2_params: null,
 });
 
 let error = $state<string | null>(null);
@@ -25,20 +61,23 @@ export const vaultStore = {
     try {
       status = await invoke<VaultStatus>('get_vault_status');
     } catch (e) {
-      error = String(e);
+      error = errorMessage(e);
     }
   },
 
   async setup(password: string) {
     try {
-      const result = await invoke<{ recovery_key: string }>('setup_vault', { password });
+      // Pick key derivation costs this device can handle in the time settings allow
+      const { argon2_target_ms } = await invoke<{ argon2_target_ms: number }>('get_settings');
+      const kdf = await invoke<KdfParams>('calibrate_argon2', { targetMs: argon2_target_ms });
+      const result = await invoke<{ recovery_key: string }>('setup_vault', { password, kdf });
       recoveryKey = result.recovery_key;
       // NOTE: Don't call checkStatus() here! That would set initialized=true
       // and App.svelte would switch away from SetupWizard before user sees recovery key.
       // The SetupWizard's finish() function calls clearRecoveryKey() which triggers
       // the transition after user confirms they saved the key.
     } catch (e) {
-      error = String(e);
+      error = errorMessage(e);
       throw e;
     }
   },
@@ -49,11 +88,36 @@ export const vaultStore = {
       error = null;
       await this.checkStatus();
     } catch (e) {
-      error = String(e);
+      error = errorMessage(e);
       throw e;
     }
   },
 
+  async isInitialized(): Promise<boolean> {
+    return invoke<boolean>('vault_initialized');
+  },
+
+  async getKdfStatus(): Promise<KdfStatus> {
+    return invoke<KdfStatus>('get_kdf_status');
+  },
+
+  async checkPasswordStrength(password: string): Promise<PasswordStrength> {
+    return invoke<PasswordStrength>('check_password_strength', { password });
+  },
+
+  async reencryptVault(
+    targetCipher: 'aes256-gcm' | 'x-cha-cha20-poly1305',
+    password: string,
+  ): Promise<ReencryptReport> {
+    const report = await invoke<ReencryptReport>('reencrypt_vault', { targetCipher, password });
+    await this.checkStatus();
+    return report;
+  },
+
+  async verifyPassword(password: string): Promise<boolean> {
+    return invoke<boolean>('verify_password', { password });
+  },
+
   async lock() {
     await invoke('lock_vault');
     await this.checkStatus();
@@ -61,19 +125,36 @@ export const vaultStore = {
 
   async recover(recoveryKeyInput: string, newPassword: string) {
     try {
-      const result = await invoke<{ recovery_key: string }>('recover_vault', {
+      const result = await invoke<{ recovery_key: string | null }>('recover_vault', {
         recoveryKeyInput,
         newPassword,
       });
-      recoveryKey = result.recovery_key;
-      // NOTE: Don't call checkStatus() here! User needs to see and save
-      // the new recovery key before transitioning to the app.
+      if (result.recovery_key) {
+        // NOTE: Don't call checkStatus() here! Old recovery data was replaced,
+        // so the user needs to save the new key before entering the app.
+        recoveryKey = result.recovery_key;
+        return;
+      }
+      await this.checkStatus();
     } catch (e) {
-      error = String(e);
+      error = errorMessage(e);
       throw e;
     }
   },
 
+  async regenerateRecoveryKey(password: string): Promise<string> {
+    const result = await invoke<{ recovery_key: string }>('regenerate_recovery_key', { password });
+    return result.recovery_key;
+  },
+
+  async getRecoveryKeyStatus(): Promise<RecoveryKeyStatus> {
+    return invoke<RecoveryKeyStatus>('recovery_key_status');
+  },
+
+  async confirmRecoveryKeySaved(): Promise<void> {
+    await invoke('confirm_recovery_key_saved');
+  },
+
   clearRecoveryKey() {
     recoveryKey = null;
   },
@@ -87,3 +168,10 @@ export const vaultStore = {
 listen('vault-locked', () => {
   vaultStore.checkStatus();
 });
+
+// A background KDF upgrade replaced recovery data too old to carry over
+listen<{ recovery_key: string | null }>('kdf-upgraded', (event) => {
+  if (event.payload.recovery_key) {
+    recoveryKey = event.payload.recovery_key;
+  }
+});
@@ -5,6 +5,14 @@ export interface FolderInfo {
   name: string;
   path: string;
   children: FolderInfo[];
+  note_count: number | null;
+  total_count: number | null;
+}
+
+export interface FlatFolder {
+  name: string;
+  path: string;
+  depth: number;
 }
 
 export interface NoteMeta {
@@ -13,29 +21,123 @@ export interface NoteMeta {
   title: string;
   preview: string;
   modified: string;
+  modified_ms: number | null;
+  created: string;
+  created_ms: number | null;
   word_count: number;
+  char_count: number | null;
+  tags: string[];
+  pinned: boolean;
+  corrupted: boolean;
+  error: string | null;
+}
+
+export type NoteSort = "ModifiedDesc" | "ModifiedAsc" | "TitleAsc" | "Created" | "CreatedAsc";
+
+export interface NotePage {
+  notes: NoteMeta[];
+  total: number;
+}
+
+export interface TagCount {
+  tag: string;
+  count: number;
 }
 
 export interface NoteContent {
   path: string;
   content: string;
+  content_hash: string;
+  had_invalid_utf8: boolean;
 }
 
 export interface SearchResult {
   path: string;
   title: string;
   matches: SearchMatch[];
+  corrupted: boolean;
+  error: string | null;
+  score: number;
 }
 
 export interface SearchMatch {
   line_number: number;
   line_content: string;
+  context_before: string[];
+  context_after: string[];
+  /** Char range of the match within line_content */
+  match_start: number;
+  match_end: number;
+}
+
+export interface TitleMatch {
+  path: string;
+  title: string;
+  score: number;
+  /** Char positions in title that matched the query */
+  indices: number[];
+}
+
+// Error returned by note and vault commands
+export interface GhostError {
+  kind:
+    | "VaultLocked"
+    | "WrongPassword"
+    | "NotFound"
+    | "DecryptionFailed"
+    | "NoteReadOnly"
+    | "Invalid"
+    | "Io"
+    | "Conflict"
+    | "Other";
+  message: string;
+  // Only for "Conflict": the refused save and what's on disk now
+  conflict?: NoteConflict;
+}
+
+export interface NoteConflict {
+  path: string;
+  ours: string;
+  theirs: string;
+  theirs_hash: string;
+}
+
+// Each path paired with how its delete went
+export type BatchDeleteResult = [string, { Ok: null } | { Err: GhostError }];
+
+export function isGhostError(e: unknown): e is GhostError {
+  return typeof e === "object" && e !== null && "kind" in e && "message" in e;
+}
+
+// Human-readable message for anything a command rejects with
+export function errorMessage(e: unknown): string {
+  if (isGhostError(e)) return e.message;
+  return e instanceof Error ? e.message : String(e);
 }
 
 export interface AppSettings {
   notes_dir: string;
   model: string;
+  theme: string;
+  /** 8 to 48 */
   font_size: number;
+  /** 1 to 240 */
+  auto_lock_minutes: number;
+  /** 0 to 1000 */
+  max_versions: number;
+  default_folder: string;
+  default_cipher: "aes256-gcm" | "x-cha-cha20-poly1305";
+  /** 250 to 10000 */
+  argon2_target_ms: number;
+  /** {date} is replaced with today's date */
+  daily_note_template: string;
+  /** 0 to 268435456 (256 MiB); 0 turns the note cache off */
+  note_cache_bytes: number;
+  filename_scheme: "DatePrefix" | "SlugOnly" | "Timestamp" | "Ulid";
+  recovery_key_saved: boolean;
+  /** 1 to 35 */
+  secure_delete_passes: number;
+  auto_upgrade_kdf: boolean;
 }
 
 export interface AudioDevice {
@@ -71,32 +173,240 @@ export interface AudioSamples {
 }
 
 // Note commands
-export async function listFolders(): Promise<FolderInfo[]> {
-  return invoke<FolderInfo[]>("list_folders");
+export async function listFolders(withCounts?: boolean): Promise<FolderInfo[]> {
+  return invoke<FolderInfo[]>("list_folders", { withCounts });
+}
+
+export async function listFoldersFlat(): Promise<FlatFolder[]> {
+  return invoke<FlatFolder[]>("list_folders_flat");
 }
 
 export async function listNotes(folder: string): Promise<NoteMeta[]> {
   return invoke<NoteMeta[]>("list_notes", { folder });
 }
 
-export async function readNote(path: string): Promise<NoteContent> {
-  return invoke<NoteContent>("read_note", { path });
+export interface FolderStats {
+  total_notes: number;
+  total_words: number;
+  total_chars: number;
+  average_words: number;
+  corrupted: number;
+}
+
+export async function folderStats(folder: string): Promise<FolderStats> {
+  return invoke<FolderStats>("folder_stats", { folder });
+}
+
+export async function listNotesPaged(
+  folder: string,
+  offset: number,
+  limit: number,
+  sortBy?: NoteSort
+): Promise<NotePage> {
+  return invoke<NotePage>("list_notes_paged", { folder, offset, limit, sortBy });
+}
+
+export async function listTags(): Promise<TagCount[]> {
+  return invoke<TagCount[]>("list_tags");
+}
+
+export async function listNotesByTag(tag: string): Promise<NoteMeta[]> {
+  return invoke<NoteMeta[]>("list_notes_by_tag", { tag });
+}
+
+export async function setPinned(path: string, pinned: boolean): Promise<void> {
+  return invoke("set_pinned", { path, pinned });
+}
+
+export async function rekeyNote(path: string): Promise<void> {
+  return invoke("rekey_note", { path });
+}
+
+export type IntegrityStatus =
+  | "Ok"
+  | "MissingKey"
+  | "UnwrapFailed"
+  | "DecryptFailed"
+  | "InvalidUtf8";
+
+export interface IntegrityReport {
+  files: { path: string; status: IntegrityStatus; error: string | null }[];
+  /** Key files with no content file beside them */
+  orphan_keys: string[];
+}
+
+export async function verifyVaultIntegrity(): Promise<IntegrityReport> {
+  return invoke<IntegrityReport>("verify_vault_integrity");
+}
+
+export async function getBacklinks(path: string): Promise<NoteMeta[]> {
+  return invoke<NoteMeta[]>("get_backlinks", { path });
+}
+
+export async function getOutgoingLinks(path: string): Promise<NoteMeta[]> {
+  return invoke<NoteMeta[]>("get_outgoing_links", { path });
+}
+
+export interface GraphNode {
+  id: string;
+  title: string;
+  dangling: boolean;
+}
+
+export interface GraphEdge {
+  from: string;
+  to: string;
+}
+
+export interface NoteGraph {
+  nodes: GraphNode[];
+  edges: GraphEdge[];
+}
+
+export async function getLinkGraph(): Promise<NoteGraph> {
+  return invoke<NoteGraph>("get_link_graph");
+}
+
+export interface RelatedNote {
+  path: string;
+  title: string;
+  /** 0 to 1: how much the two notes' tags overlap */
+  score: number;
+  shared_tags: string[];
+}
+
+export async function findRelatedNotes(path: string, limit: number): Promise<RelatedNote[]> {
+  return invoke<RelatedNote[]>("find_related_notes", { path, limit });
 }
 
-export async function saveNote(path: string, content: string): Promise<void> {
-  return invoke("save_note", { path, content });
+// Attachment commands
+
+export async function saveAttachment(
+  notePath: string,
+  filename: string,
+  bytes: Uint8Array
+): Promise<string> {
+  return invoke<string>("save_attachment", { notePath, filename, bytes: Array.from(bytes) });
+}
+
+export async function readAttachment(token: string): Promise<Uint8Array> {
+  const bytes = await invoke<number[]>("read_attachment", { token });
+  return new Uint8Array(bytes);
+}
+
+export async function getNoteMeta(path: string): Promise<NoteMeta> {
+  return invoke<NoteMeta>("get_note_meta", { path });
+}
+
+export async function readNote(
+  path: string,
+  allowInvalidUtf8?: boolean
+): Promise<NoteContent> {
+  return invoke<NoteContent>("read_note", { path, allowInvalidUtf8 });
+}
+
+export interface NoteRange {
+  path: string;
+  start_line: number;
+  lines: string[];
+  total_lines: number;
+}
+
+export async function readNoteRange(
+  path: string,
+  startLine: number,
+  lineCount: number
+): Promise<NoteRange> {
+  return invoke<NoteRange>("read_note_range", { path, startLine, lineCount });
+}
+
+export async function listRecentNotes(limit?: number): Promise<NoteMeta[]> {
+  return invoke<NoteMeta[]>("list_recent_notes", { limit });
+}
+
+// Decrypts to a private temp file for an external editor; returns its path
+export async function checkoutNote(path: string): Promise<string> {
+  return invoke<string>("checkout_note", { path });
+}
+
+export async function checkinNote(path: string, tempPath: string): Promise<void> {
+  return invoke("checkin_note", { path, tempPath });
+}
+
+export async function saveNote(
+  path: string,
+  content: string,
+  expectedHash?: string
+): Promise<void> {
+  return invoke("save_note", { path, content, expectedHash });
 }
 
 export async function createNote(folder: string, title?: string): Promise<string> {
   return invoke<string>("create_note", { folder, title });
 }
 
+export async function openDailyNote(folder: string): Promise<string> {
+  return invoke<string>("open_daily_note", { folder });
+}
+
+export async function quickCapture(text: string): Promise<string> {
+  return invoke<string>("quick_capture", { text });
+}
+
+export interface TemplateInfo {
+  /** Path under .templates/ */
+  id: string;
+  title: string;
+}
+
+export async function listTemplates(): Promise<TemplateInfo[]> {
+  return invoke<TemplateInfo[]>("list_templates");
+}
+
+export async function createNoteFromTemplate(
+  folder: string,
+  templateId: string,
+  title?: string
+): Promise<string> {
+  return invoke<string>("create_note_from_template", { folder, templateId, title });
+}
+
+export async function duplicateNote(path: string): Promise<string> {
+  return invoke<string>("duplicate_note", { path });
+}
+
 export async function deleteNote(path: string): Promise<void> {
   return invoke("delete_note", { path });
 }
 
-export async function searchNotes(query: string): Promise<SearchResult[]> {
-  return invoke<SearchResult[]>("search_notes", { query });
+export async function batchDeleteNotes(paths: string[]): Promise<BatchDeleteResult[]> {
+  return invoke<BatchDeleteResult[]>("batch_delete_notes", { paths });
+}
+
+export type SearchMode = "Substring" | "WholeWord" | "Regex";
+
+export async function searchNotes(
+  query: string,
+  contextLines?: number,
+  mode?: SearchMode,
+  caseSensitive?: boolean,
+  folder?: string,
+  ranked?: boolean,
+  boostTitle?: boolean
+): Promise<SearchResult[]> {
+  return invoke<SearchResult[]>("search_notes", {
+    query,
+    contextLines,
+    mode,
+    caseSensitive,
+    folder,
+    ranked,
+    boostTitle,
+  });
+}
+
+export async function searchTitles(query: string): Promise<TitleMatch[]> {
+  return invoke<TitleMatch[]>("search_titles", { query });
 }
 
 export async function createFolder(name: string, parent?: string): Promise<string> {
@@ -107,8 +417,58 @@ export async function deleteFolder(path: string): Promise<void> {
   return invoke("delete_folder", { path });
 }
 
-export async function renameFolder(oldPath: string, newName: string): Promise<string> {
-  return invoke<string>("rename_folder", { oldPath, newName });
+export interface Renamed {
+  path: string;
+  /** Notes whose [[links]] were rewritten to follow the rename */
+  relinked: string[];
+}
+
+export async function renameNote(
+  path: string,
+  newTitle: string,
+  updateHeading?: boolean,
+  updateLinks?: boolean
+): Promise<Renamed> {
+  return invoke<Renamed>("rename_note", { path, newTitle, updateHeading, updateLinks });
+}
+
+export async function renameFolder(
+  oldPath: string,
+  newName: string,
+  updateLinks?: boolean
+): Promise<Renamed> {
+  return invoke<Renamed>("rename_folder", { oldPath, newName, updateLinks });
+}
+
+export async function moveFolder(path: string, newParent: string): Promise<string> {
+  return invoke<string>("move_folder", { path, newParent });
+}
+
+export interface VacuumReport {
+  history_versions: number;
+  trashed_notes: number;
+  orphan_keys: number;
+  bytes_reclaimed: number;
+}
+
+export async function vacuumVault(options: {
+  trashOlderThanDays?: number;
+  dryRun?: boolean;
+}): Promise<VacuumReport> {
+  return invoke<VacuumReport>("vacuum_vault", {
+    options: {
+      trash_older_than_days: options.trashOlderThanDays ?? null,
+      dry_run: options.dryRun ?? false,
+    },
+  });
+}
+
+export async function noteExists(path: string): Promise<boolean> {
+  return invoke<boolean>("note_exists", { path });
+}
+
+export async function folderExists(path: string): Promise<boolean> {
+  return invoke<boolean>("folder_exists", { path });
 }
 
 // Settings commands
@@ -120,6 +480,14 @@ export async function saveSettings(settings: AppSettings): Promise<void> {
   return invoke("save_settings", { settings });
 }
 
+export async function exportSettings(): Promise<string> {
+  return invoke<string>("export_settings");
+}
+
+export async function importSettings(json: string): Promise<AppSettings> {
+  return invoke<AppSettings>("import_settings", { json });
+}
+
 // Audio device commands
 export async function listAudioDevices(): Promise<AudioDevice[]> {
   return invoke<AudioDevice[]>("list_audio_devices");